@@ -0,0 +1,80 @@
+/// Tally the byte values in the diff, most frequent first. Values with the
+/// same count are ordered by value.
+pub fn value_frequencies(diffs: &[(usize, u8)]) -> Vec<(u8, usize)> {
+    let mut counts = [0usize; 256];
+    for &(_, byte) in diffs {
+        counts[byte as usize] += 1;
+    }
+    let mut tally: Vec<(u8, usize)> = (0..=255u8)
+        .zip(counts)
+        .filter(|&(_, count)| count > 0)
+        .collect();
+    tally.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    tally
+}
+
+/// Integer widths in bytes shown by the data inspector.
+const INSPECT_WIDTHS: [usize; 3] = [2, 4, 8];
+
+/// One row of the data inspector: the integer starting at an offset, read in
+/// one width and byte order. The values are `None` when fewer bytes than the
+/// width remain past the offset.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InspectedValue {
+    pub label: String,
+    pub unsigned: Option<u64>,
+    pub signed: Option<i64>,
+}
+
+/// Read the 2-, 4- and 8-byte integers starting at `offset` in `data`, each
+/// as little- and then big-endian.
+pub fn inspect_values(data: &[u8], offset: usize) -> Vec<InspectedValue> {
+    let mut values = Vec::new();
+    for width in INSPECT_WIDTHS {
+        let bytes = offset
+            .checked_add(width)
+            .and_then(|end| data.get(offset..end));
+        for (order, little) in [("le", true), ("be", false)] {
+            let unsigned = bytes.map(|bytes| {
+                let fold = |value: u64, &byte: &u8| value << 8 | u64::from(byte);
+                if little {
+                    bytes.iter().rev().fold(0, fold)
+                } else {
+                    bytes.iter().fold(0, fold)
+                }
+            });
+            // Sign-extend by moving the top bit of the value up to bit 63
+            let shift = 64 - width as u32 * 8;
+            values.push(InspectedValue {
+                label: format!("{}-bit {}", width * 8, order),
+                unsigned,
+                signed: unsigned.map(|value| ((value << shift) as i64) >> shift),
+            });
+        }
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frequencies_are_sorted_by_count_then_value() {
+        let diffs = [(0, 0x41), (3, 0x00), (4, 0x41), (9, 0xff), (10, 0x00)];
+        assert_eq!(
+            value_frequencies(&diffs),
+            vec![(0x00, 2), (0x41, 2), (0xff, 1)]
+        );
+        assert_eq!(value_frequencies(&[]), vec![]);
+    }
+
+    #[test]
+    fn every_byte_value_is_tallied() {
+        let diffs: Vec<(usize, u8)> = (0..=255u8).rev().map(|b| (b as usize, b)).collect();
+        let tally = value_frequencies(&diffs);
+        assert_eq!(tally.len(), 256);
+        assert_eq!(tally[0], (0x00, 1));
+        assert_eq!(tally[255], (0xff, 1));
+    }
+}
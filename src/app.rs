@@ -1,88 +1,1724 @@
-use std::error;
+use std::{
+    collections::HashMap,
+    ops::Range,
+    time::{Duration, Instant},
+};
+
+use ratatui::layout::Rect;
+
+use crate::{
+    analysis::value_frequencies,
+    archive::extract_entries,
+    clipboard::{
+        copy_text, format_offsets, osc52_fits, write_overflow, ClipboardBackend, CopyFormat,
+        Selection,
+    },
+    codepage::{decode_utf8_at, CodePage},
+    export::{
+        fold_rows, folded_lines, format_debugger_expr, format_offset, line_of_row, DebuggerStyle,
+        FoldedLine, OffsetBase, Report,
+    },
+    file::{
+        aligned_index, apply_edits, byte_histogram, diff_aligned, diff_files_pairs_ignoring,
+        diff_summary, filter_text, group_regions, is_ignored, largest_deltas, offset_in_second,
+        read_file, AlignedChange, DiffSummary, FileData, TextFilter, STDIN_PATH,
+    },
+    input::{
+        parse_byte, parse_offset, validate_byte, validate_decimal_offset, validate_hex_offset,
+        validate_match_number, InputPrompt,
+    },
+    replay::Replay,
+    search::{
+        hex_query_state, validate_hex_query, HexQueryState, SearchKind, SearchScope, SearchSource,
+        SearchState,
+    },
+    section::Section,
+    session::{SavedSearch, Session},
+    ui::{bytes_per_line, file_view_rows, inspector_height, split_heights, PrintableSet},
+};
+
+/// Number of largest-magnitude diffs cycled through by repeated delta jumps.
+const TOP_DELTAS: usize = 10;
+
+/// Sampling strides cycled through by the sample key; 1 shows every diff.
+const SAMPLE_STRIDES: [usize; 5] = [1, 16, 64, 256, 1024];
+
+/// Bytes per row of the whole-file view.
+pub const FILE_VIEW_BYTES_PER_ROW: usize = 16;
+
+/// Runs of more unchanged rows than this are folded in the whole-file view.
+const FILE_VIEW_FOLD_ROWS: usize = 4;
+
+/// How long a status message stays in the info bar.
+const STATUS_DURATION: Duration = Duration::from_secs(3);
 
 pub struct App {
     pub running: bool,
-    pub file1_data: Vec<u8>,
-    pub file2_data: Vec<u8>,
+    pub file1_data: FileData,
+    pub file2_data: FileData,
     pub diffs: Vec<(usize, u8)>,
+    pub pairs: Vec<(usize, u8, u8)>,
     pub cursor_pos: usize,
     pub scroll: usize,
     pub bytes_per_line: usize,
+    pub annotations: HashMap<usize, String>,
+    pub prompt: Option<Prompt>,
+    pub reverse_bytes: bool,
+    pub watch_values: Vec<u8>,
+    pub swapped: bool,
+    pub file1_path: String,
+    pub file2_path: String,
+    pub edits: HashMap<usize, u8>,
+    pub status: Option<(String, Instant)>,
+    pub offset_base: OffsetBase,
+    pub nibble: bool,
+    pub printable: PrintableSet,
+    pub codepage: CodePage,
+    pub following: bool,
+    pub search: SearchState,
+    pub match_list: Option<usize>,
+    pub replay: Option<Replay>,
+    pub section: Option<Section>,
+    pub zip_entry: Option<String>,
+    pub scrollbar: Option<Rect>,
+    pub hex_cells: Option<Rect>,
+    pub hex2_cells: Option<Rect>,
+    pub ascii_cells: Option<Rect>,
+    pub dragging_scrollbar: bool,
+    pub started: Instant,
+    pub show_timer: bool,
+    /// Whole seconds of elapsed time last shown, so the timer only redraws
+    /// when its text changes.
+    timer_seconds: u64,
+    pub debugger_style: DebuggerStyle,
+    pub all_pairs: Vec<(usize, u8, u8)>,
+    pub text_filter: TextFilter,
+    pub text_filter_mode: TextFilter,
+    /// Show only every Nth diff, 1 to show them all.
+    pub sample_stride: usize,
+    pub separators: bool,
+    pub width: Option<usize>,
+    pub frequency: Option<FrequencyCursor>,
+    pub anchor: Option<usize>,
+    pub selection: Option<Selection>,
+    pub show_help: bool,
+    /// The summary overlay, while it's open.
+    pub summary: Option<DiffSummary>,
+    /// Bookmarked file offsets, in ascending order.
+    pub bookmarks: Vec<usize>,
+    /// Offset ranges left out of the diff, kept to recompute it on reload.
+    pub ignore: Vec<Range<usize>>,
+    /// How often each byte value occurs among the diffs, while the histogram
+    /// overlay is open.
+    pub histogram: Option<[usize; 256]>,
+    /// The aligned diff, while its overlay is open.
+    pub alignment: Option<AlignmentList>,
+    /// The whole-file view, while its overlay is open.
+    pub file_view: Option<FileView>,
+    /// Whether the data inspector panel is shown below the panes.
+    pub show_inspector: bool,
+    pub clipboard: ClipboardBackend,
+    pub copy_format: CopyFormat,
+    /// Whether text copied as ASCII keeps tabs and newlines instead of
+    /// turning them into `.`.
+    pub copy_whitespace: bool,
+    pub view: ViewMode,
+    pub split: Option<Split>,
+    pub focused_pane: Pane,
+    pub decode: DecodeMode,
+    /// Whether the last key was a `g`, the first half of `gg`.
+    pub pending_g: bool,
+    /// Rank of the diff the last `D` jumped to, while `D` is pressed again.
+    pub delta_rank: Option<usize>,
+    /// Whether searches run as the query is typed.
+    pub incremental_search: bool,
+    /// Cursor and scroll position from before an incremental search moved
+    /// them, restored if the search is cancelled or finds nothing.
+    search_origin: Option<(usize, usize)>,
+}
+
+/// Position of the by-frequency walk: the value being visited, its rank in
+/// the frequency tally, and which of its occurrences the cursor is on.
+#[derive(Clone, Copy, Debug)]
+pub struct FrequencyCursor {
+    pub value: u8,
+    pub rank: usize,
+    pub nth: usize,
+    pub count: usize,
+}
+
+/// The changes of an aligned diff, in which inserted and deleted bytes don't
+/// shift the rest, listed in an overlay with one of them selected.
+#[derive(Clone, Debug)]
+pub struct AlignmentList {
+    pub changes: Vec<AlignedChange>,
+    pub selected: usize,
+}
+
+/// A hexdump of the whole first file, unchanged bytes and all, in which long
+/// runs of unchanged rows can be folded away.
+#[derive(Clone, Debug)]
+pub struct FileView {
+    pub lines: Vec<FoldedLine>,
+    pub folded: bool,
+    /// Line at the top of the view.
+    pub scroll: usize,
+}
+
+/// How the diff bytes are laid out.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ViewMode {
+    /// One hex pane with file1's bytes.
+    #[default]
+    Single,
+    /// Hex panes for file1 and file2 next to each other.
+    SideBySide,
+}
+
+/// The pane the cursor moves in, drawn with the full cursor while the other
+/// shows a dimmed one.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Pane {
+    #[default]
+    Hex,
+    /// The ASCII pane, where the cursor moves a whole character at a time
+    /// when decoding UTF-8.
+    Ascii,
+}
+
+/// Which half of a split view holds the cursor.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SplitFocus {
+    Top,
+    Bottom,
+}
+
+/// A view split into two panes over the same diff. The focused pane scrolls
+/// with the cursor; the other stays frozen where it was left.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Split {
+    /// Diff index at the start of the frozen pane's top row, which stays put
+    /// when the rows are reflowed.
+    pub top: usize,
+    /// Cursor to restore when the frozen pane gets the focus back.
+    pub cursor_pos: usize,
+    pub focus: SplitFocus,
+}
+
+/// How the bytes in the ASCII pane are turned into characters.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DecodeMode {
+    /// Each byte on its own, through the code page.
+    #[default]
+    Ascii,
+    /// The file's bytes decoded as UTF-8.
+    Utf8,
+}
+
+/// What a submitted prompt applies to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PromptKind {
+    Annotate,
+    Watch,
+    Edit,
+    Write,
+    Report,
+    Goto,
+    Search(SearchKind),
+    MatchNumber,
+}
+
+/// An open modal text prompt.
+#[derive(Debug)]
+pub struct Prompt {
+    pub kind: PromptKind,
+    pub input: InputPrompt,
 }
 
 impl App {
-    pub fn new(file1_data: Vec<u8>, file2_data: Vec<u8>, diffs: Vec<(usize, u8)>) -> Self {
+    pub fn new(
+        file1_data: impl Into<FileData>,
+        file2_data: impl Into<FileData>,
+        diffs: Vec<(usize, u8)>,
+        pairs: Vec<(usize, u8, u8)>,
+    ) -> Self {
         Self {
             running: true,
-            file1_data,
-            file2_data,
+            file1_data: file1_data.into(),
+            file2_data: file2_data.into(),
             diffs,
+            all_pairs: pairs.clone(),
+            pairs,
             cursor_pos: 0,
             scroll: 0,
-            bytes_per_line: 0,
+            bytes_per_line: 1,
+            annotations: HashMap::new(),
+            prompt: None,
+            reverse_bytes: false,
+            watch_values: Vec::new(),
+            swapped: false,
+            file1_path: String::new(),
+            file2_path: String::new(),
+            edits: HashMap::new(),
+            status: None,
+            offset_base: OffsetBase::Hex,
+            nibble: false,
+            printable: PrintableSet::default(),
+            codepage: CodePage::default(),
+            following: false,
+            search: SearchState::default(),
+            match_list: None,
+            replay: None,
+            section: None,
+            zip_entry: None,
+            scrollbar: None,
+            hex_cells: None,
+            hex2_cells: None,
+            ascii_cells: None,
+            dragging_scrollbar: false,
+            started: Instant::now(),
+            show_timer: false,
+            timer_seconds: 0,
+            debugger_style: DebuggerStyle::default(),
+            text_filter: TextFilter::Off,
+            text_filter_mode: TextFilter::Either,
+            sample_stride: 1,
+            separators: false,
+            width: None,
+            frequency: None,
+            anchor: None,
+            selection: None,
+            show_help: false,
+            summary: None,
+            bookmarks: Vec::new(),
+            ignore: Vec::new(),
+            histogram: None,
+            alignment: None,
+            file_view: None,
+            show_inspector: false,
+            clipboard: ClipboardBackend::default(),
+            copy_format: CopyFormat::default(),
+            copy_whitespace: false,
+            view: ViewMode::default(),
+            split: None,
+            focused_pane: Pane::default(),
+            decode: DecodeMode::default(),
+            pending_g: false,
+            delta_rank: None,
+            incremental_search: true,
+            search_origin: None,
         }
     }
 
-    pub fn tick(&mut self) -> Result<(), Box<dyn error::Error>> {
-        Ok(())
+    /// Handles the tick event of the terminal, advancing a running replay.
+    /// Returns whether anything on screen changed, so idle ticks can skip
+    /// the redraw.
+    pub fn tick(&mut self, terminal_height: u16) -> bool {
+        let mut changed = false;
+        let seconds = self.started.elapsed().as_secs();
+        if self.show_timer && seconds != self.timer_seconds {
+            self.timer_seconds = seconds;
+            changed = true;
+        }
+        if self
+            .status
+            .as_ref()
+            .is_some_and(|(_, shown)| shown.elapsed() >= STATUS_DURATION)
+        {
+            self.status = None;
+            changed = true;
+        }
+        if let Some(replay) = self.replay.as_mut() {
+            if replay.tick() {
+                self.center_replay_region(terminal_height);
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Show a transient message in the info bar. It is cleared by the next
+    /// key press or once it has been shown for [`STATUS_DURATION`].
+    pub fn set_status(&mut self, message: impl Into<String>) {
+        self.status = Some((message.into(), Instant::now()));
+    }
+
+    /// Start walking through the diff regions, showing each for
+    /// `ticks_per_region` ticks.
+    pub fn start_replay(&mut self, ticks_per_region: u64, terminal_height: u16) {
+        self.replay = Some(Replay::new(group_regions(&self.diffs), ticks_per_region));
+        self.center_replay_region(terminal_height);
+    }
+
+    /// Put the cursor on the start of the replay's current region and scroll
+    /// so that region sits in the middle of the view.
+    fn center_replay_region(&mut self, terminal_height: u16) {
+        let Some(region) = self.replay.as_ref().and_then(Replay::current_region) else {
+            return;
+        };
+        let lines = self.visible_rows(terminal_height);
+        let first_row = region.start / self.bytes_per_line;
+        let last_row = (region.end - 1) / self.bytes_per_line;
+        let middle = (first_row + last_row) / 2;
+        self.cursor_pos = region.start;
+        self.scroll = middle.saturating_sub(lines / 2).min(first_row);
     }
 
+    /// Move the cursor down one display row, keeping its column. On the last
+    /// row the cursor stays put; a shorter final row clamps it to its end.
     pub fn move_cursor_down(&mut self, terminal_height: u16) {
-        let lines = (terminal_height - 5) as usize;
         let max_cursor_pos = self.diffs.len().saturating_sub(1);
+        let rows = self.diffs.len().div_ceil(self.bytes_per_line);
 
-        // Increment cursor position if not at the end of diffs
-        if self.cursor_pos < max_cursor_pos {
+        // Move down a row if there is one below the cursor
+        if self.cursor_pos / self.bytes_per_line + 1 < rows {
             self.cursor_pos += self.bytes_per_line;
             self.cursor_pos = self.cursor_pos.min(max_cursor_pos);
         }
+        self.ensure_cursor_visible(terminal_height);
+    }
 
-        // Adjust scrolling if cursor moves beyond the visible area
-        if (self.cursor_pos / self.bytes_per_line) >= (self.scroll + lines)
-            && (self.scroll + lines)
-                < ((self.diffs.len() + self.bytes_per_line - 1) / self.bytes_per_line)
-        {
-            self.scroll += 1;
+    /// Move the cursor up one display row, keeping its column.
+    pub fn move_cursor_up(&mut self, terminal_height: u16) {
+        if self.cursor_pos >= self.bytes_per_line {
+            self.cursor_pos -= self.bytes_per_line;
         }
+        self.ensure_cursor_visible(terminal_height);
     }
 
-    pub fn move_cursor_up(&mut self) {
-        if self.cursor_pos >= self.bytes_per_line {
-            self.cursor_pos = self.cursor_pos.saturating_sub(self.bytes_per_line);
+    /// Move the cursor to the next diff entry, wrapping onto the next row.
+    /// Unlike a row move, the distance doesn't depend on the terminal width.
+    pub fn move_cursor_right(&mut self, terminal_height: u16) {
+        if self.cursor_pos + 1 < self.diffs.len() {
+            self.cursor_pos += 1;
         }
+        self.ensure_cursor_visible(terminal_height);
+    }
 
-        // Adjust scrolling if cursor moves above the visible area
-        if self.cursor_pos / self.bytes_per_line < self.scroll {
-            self.scroll = self.scroll.saturating_sub(1);
+    /// Move the cursor to the previous diff entry, wrapping onto the previous
+    /// row.
+    pub fn move_cursor_left(&mut self, terminal_height: u16) {
+        if self.cursor_pos > 0 {
+            self.cursor_pos -= 1;
         }
+        self.ensure_cursor_visible(terminal_height);
     }
 
-    pub fn move_cursor_right(&mut self, terminal_height: u16) {
-        let lines = (terminal_height - 5) as usize;
-        let max_cursor_pos = self.diffs.len().saturating_sub(1);
+    /// Move the cursor to the next character of the ASCII pane. Decoding
+    /// UTF-8, that skips the other diff entries of the character under the
+    /// cursor; otherwise every byte is a character of its own.
+    pub fn move_char_right(&mut self, terminal_height: u16) {
+        if self.decode == DecodeMode::Utf8 {
+            let lead = self.char_lead(self.cursor_pos);
+            while self.cursor_pos + 1 < self.diffs.len()
+                && self.char_lead(self.cursor_pos + 1) == lead
+            {
+                self.cursor_pos += 1;
+            }
+        }
+        self.move_cursor_right(terminal_height);
+    }
 
-        // Move cursor right if not at the end of diffs
-        if self.cursor_pos < max_cursor_pos {
-            self.cursor_pos += 1;
+    /// Move the cursor to the first diff entry of the previous character of
+    /// the ASCII pane.
+    pub fn move_char_left(&mut self, terminal_height: u16) {
+        self.move_cursor_left(terminal_height);
+        if self.decode == DecodeMode::Utf8 {
+            let lead = self.char_lead(self.cursor_pos);
+            while self.cursor_pos > 0 && self.char_lead(self.cursor_pos - 1) == lead {
+                self.cursor_pos -= 1;
+            }
+            self.ensure_cursor_visible(terminal_height);
         }
+    }
 
-        // Special handling for bottom right movement
-        let cursor_line = self.cursor_pos / self.bytes_per_line;
-        if cursor_line >= self.scroll + lines {
-            self.scroll = cursor_line + 1 - lines;
+    /// File offset of the first byte of the UTF-8 character that diff index
+    /// `pos` is part of.
+    fn char_lead(&self, pos: usize) -> usize {
+        self.utf8_char_at(self.diffs[pos].0).0
+    }
+
+    /// Decode the UTF-8 character covering file `offset` of file1 as shown,
+    /// returning the offset of its first byte and the character. Offsets
+    /// past the end of file1 decode to U+FFFD on their own.
+    pub fn utf8_char_at(&self, offset: usize) -> (usize, char) {
+        let data = &self.file1_data;
+        let start = offset.saturating_sub(3);
+        let end = (offset + 4).min(data.len());
+        if offset >= end {
+            return (offset, char::REPLACEMENT_CHARACTER);
         }
+        let window: Vec<u8> = (start..end)
+            .map(|o| self.display_byte(o, data[o]))
+            .collect();
+        let (lead, c) = decode_utf8_at(&window, offset - start);
+        (start + lead, c)
     }
 
-    pub fn move_cursor_left(&mut self) {
-        // Move cursor left if not at the start
-        if self.cursor_pos > 0 {
-            self.cursor_pos -= 1;
+    /// Move the cursor to the other pane: from the hex pane to the ASCII
+    /// pane, and from there back to the hex pane, in the other half of a
+    /// split view if there is one.
+    pub fn cycle_focus(&mut self, terminal_height: u16) {
+        self.focused_pane = match self.focused_pane {
+            Pane::Hex => Pane::Ascii,
+            Pane::Ascii => {
+                self.switch_split_focus(terminal_height);
+                Pane::Hex
+            }
+        };
+    }
+
+    /// Move the cursor down a screenful of rows, keeping its column, and scroll
+    /// the view by the same amount. Near the end it lands on the last diff.
+    pub fn page_down(&mut self, terminal_height: u16) {
+        let lines = self.visible_rows(terminal_height);
+        let rows = self.diffs.len().div_ceil(self.bytes_per_line);
+        self.cursor_pos =
+            (self.cursor_pos + lines * self.bytes_per_line).min(self.diffs.len().saturating_sub(1));
+        self.scroll = (self.scroll + lines).min(rows.saturating_sub(lines));
+        self.ensure_cursor_visible(terminal_height);
+    }
+
+    /// Move the cursor up a screenful of rows, keeping its column, and scroll
+    /// the view by the same amount. Near the start it lands on the first diff.
+    pub fn page_up(&mut self, terminal_height: u16) {
+        let lines = self.visible_rows(terminal_height);
+        self.cursor_pos = self.cursor_pos.saturating_sub(lines * self.bytes_per_line);
+        self.scroll = self.scroll.saturating_sub(lines);
+        self.ensure_cursor_visible(terminal_height);
+    }
+
+    /// Reflow the rows for a new terminal size, keeping the cursor on screen.
+    pub fn resize(&mut self, terminal_width: u16, terminal_height: u16) {
+        self.bytes_per_line = bytes_per_line(terminal_width, self.width, self.view);
+        self.ensure_cursor_visible(terminal_height);
+    }
+
+    /// Cycle the row width through 8, 16 and 32 bytes and back to filling the
+    /// terminal. The cursor stays on the same byte; if its row would leave the
+    /// screen, the view is centered on it.
+    pub fn cycle_width(&mut self, terminal_width: u16, terminal_height: u16) {
+        self.width = match self.width {
+            None => Some(8),
+            Some(width) if width < 16 => Some(16),
+            Some(width) if width < 32 => Some(32),
+            Some(_) => None,
+        };
+        self.reflow(terminal_width, terminal_height);
+        self.set_status(match self.width {
+            Some(width) => format!("{} bytes per line", width),
+            None => "Filling the terminal width".to_string(),
+        });
+    }
+
+    /// Switch between the single hex pane and the side-by-side view of both
+    /// files. The wider view fits fewer bytes per row, so the rows are
+    /// reflowed around the cursor.
+    pub fn toggle_view(&mut self, terminal_width: u16, terminal_height: u16) {
+        self.view = match self.view {
+            ViewMode::Single => ViewMode::SideBySide,
+            ViewMode::SideBySide => ViewMode::Single,
+        };
+        self.reflow(terminal_width, terminal_height);
+    }
+
+    /// Split the view into two panes, both starting where the view is now, or
+    /// go back to one. The bottom pane gets the cursor and the top one stays
+    /// frozen.
+    pub fn toggle_split(&mut self, terminal_height: u16) {
+        self.split = match self.split {
+            Some(_) => None,
+            None => Some(Split {
+                top: self.scroll * self.bytes_per_line,
+                cursor_pos: self.cursor_pos,
+                focus: SplitFocus::Bottom,
+            }),
+        };
+        self.ensure_cursor_visible(terminal_height);
+    }
+
+    /// Move the cursor to the other pane of a split view, where it was last
+    /// left, freezing the pane it leaves.
+    pub fn switch_split_focus(&mut self, terminal_height: u16) {
+        let Some(split) = self.split.as_mut() else {
+            return;
+        };
+        let top = split.top;
+        split.top = self.scroll * self.bytes_per_line;
+        self.scroll = top / self.bytes_per_line;
+        std::mem::swap(&mut split.cursor_pos, &mut self.cursor_pos);
+        split.focus = match split.focus {
+            SplitFocus::Top => SplitFocus::Bottom,
+            SplitFocus::Bottom => SplitFocus::Top,
+        };
+        self.ensure_cursor_visible(terminal_height);
+    }
+
+    /// Number of diff rows visible in the pane holding the cursor, in a
+    /// terminal `terminal_height` rows tall. Always at least 1.
+    fn visible_rows(&self, terminal_height: u16) -> usize {
+        // Below the panes are the info bar and maybe the inspector, and each
+        // pane has its borders
+        let height = terminal_height.saturating_sub(3 + inspector_height(self));
+        let pane = match self.split {
+            None => height,
+            Some(split) => {
+                let (top, bottom) = split_heights(height);
+                match split.focus {
+                    SplitFocus::Top => top,
+                    SplitFocus::Bottom => bottom,
+                }
+            }
+        };
+        (pane.saturating_sub(2) as usize).max(1)
+    }
+
+    /// Switch the ASCII pane between decoding bytes one at a time and as
+    /// UTF-8.
+    pub fn toggle_decode(&mut self) {
+        self.decode = match self.decode {
+            DecodeMode::Ascii => DecodeMode::Utf8,
+            DecodeMode::Utf8 => DecodeMode::Ascii,
+        };
+    }
+
+    /// Open the summary overlay of the whole comparison, or close it.
+    pub fn toggle_summary(&mut self) {
+        self.summary = match self.summary {
+            Some(_) => None,
+            None => Some(diff_summary(&self.file1_data, &self.file2_data)),
+        };
+    }
+
+    /// Open the histogram of the diff byte values, or close it.
+    pub fn toggle_histogram(&mut self) {
+        self.histogram = match self.histogram {
+            Some(_) => None,
+            None => Some(byte_histogram(&self.diffs)),
+        };
+    }
+
+    /// Show the data inspector below the panes, or hide it. The panes shrink
+    /// to make room, so the view scrolls if the cursor would be covered.
+    pub fn toggle_inspector(&mut self, terminal_height: u16) {
+        self.show_inspector = !self.show_inspector;
+        self.ensure_cursor_visible(terminal_height);
+    }
+
+    /// Recompute the bytes per row after a layout change, keeping the cursor
+    /// on the same byte and centering the view on it if its row would leave
+    /// the screen.
+    fn reflow(&mut self, terminal_width: u16, terminal_height: u16) {
+        self.bytes_per_line = bytes_per_line(terminal_width, self.width, self.view);
+        let lines = self.visible_rows(terminal_height);
+        let cursor_line = self.cursor_pos / self.bytes_per_line;
+        if cursor_line < self.scroll || cursor_line >= self.scroll + lines {
+            self.scroll = cursor_line.saturating_sub(lines / 2);
         }
+    }
 
-        // Special handling for top left movement
+    /// Scroll just enough to bring the cursor's row into the visible window.
+    /// Every cursor movement ends here, so they all scroll the same way.
+    pub fn ensure_cursor_visible(&mut self, terminal_height: u16) {
+        let lines = self.visible_rows(terminal_height);
         let cursor_line = self.cursor_pos / self.bytes_per_line;
         if cursor_line < self.scroll {
             self.scroll = cursor_line;
+        } else if cursor_line >= self.scroll + lines {
+            self.scroll = cursor_line + 1 - lines;
+        }
+    }
+
+    /// Jump to the diff with the largest change between the two files. Repeated
+    /// presses cycle through the next largest ones.
+    pub fn jump_to_largest_delta(&mut self, terminal_height: u16) {
+        let ranked = largest_deltas(&self.pairs, TOP_DELTAS);
+        if ranked.is_empty() {
+            self.set_status("No differences");
+            return;
+        }
+
+        // Only a repeated press, with the cursor still where the last one left
+        // it, moves on down the ranking
+        let rank = match self.delta_rank {
+            Some(rank) if ranked.get(rank) == Some(&self.cursor_pos) => (rank + 1) % ranked.len(),
+            _ => 0,
+        };
+        self.delta_rank = Some(rank);
+        self.jump_to(ranked[rank], terminal_height);
+    }
+
+    /// Jump to the start of the next region of contiguous differing offsets.
+    pub fn next_region(&mut self, terminal_height: u16) {
+        let next = group_regions(&self.diffs)
+            .into_iter()
+            .map(|region| region.start)
+            .find(|&start| start > self.cursor_pos);
+        match next {
+            Some(start) => self.jump_to(start, terminal_height),
+            None => self.set_status("No next region"),
+        }
+    }
+
+    /// Jump to the start of the previous region, or of the current one when
+    /// the cursor is inside it.
+    pub fn prev_region(&mut self, terminal_height: u16) {
+        let prev = group_regions(&self.diffs)
+            .into_iter()
+            .map(|region| region.start)
+            .rfind(|&start| start < self.cursor_pos);
+        match prev {
+            Some(start) => self.jump_to(start, terminal_height),
+            None => self.set_status("No previous region"),
+        }
+    }
+
+    /// Swap which file is treated as the base. The differing offsets don't
+    /// change, so the pairs are flipped in place and the cursor stays on the
+    /// same offset.
+    pub fn swap_files(&mut self, terminal_height: u16) {
+        std::mem::swap(&mut self.file1_data, &mut self.file2_data);
+        std::mem::swap(&mut self.file1_path, &mut self.file2_path);
+        for pair in &mut self.all_pairs {
+            *pair = (pair.0, pair.2, pair.1);
+        }
+        self.swapped = !self.swapped;
+        if self.text_filter == TextFilter::File1 {
+            // Which bytes pass the filter depends on the base file
+            let offset = self.cursor_offset();
+            self.apply_text_filter();
+            self.scroll = 0;
+            self.jump_to_offset(offset, terminal_height);
+        } else {
+            self.apply_text_filter();
+        }
+    }
+
+    /// Rebuild the diff shown from all the differences, the text filter and
+    /// the sampling stride. Search matches and the frequency walk refer to
+    /// diff indices, so they are cleared.
+    pub fn apply_text_filter(&mut self) {
+        self.pairs = filter_text(&self.all_pairs, self.text_filter);
+        if self.sample_stride > 1 {
+            self.pairs = self
+                .pairs
+                .iter()
+                .copied()
+                .step_by(self.sample_stride)
+                .collect();
+        }
+        self.diffs = self
+            .pairs
+            .iter()
+            .map(|&(offset, old, _)| (offset, old))
+            .collect();
+        self.search.invalidate();
+        self.match_list = None;
+        self.frequency = None;
+        self.selection = None;
+    }
+
+    /// Show only text changes or every change again, keeping the cursor on
+    /// the nearest offset.
+    pub fn toggle_text_filter(&mut self, terminal_height: u16) {
+        let offset = self.cursor_offset();
+        self.text_filter = if self.text_filter == TextFilter::Off {
+            self.text_filter_mode
+        } else {
+            TextFilter::Off
+        };
+        self.apply_text_filter();
+        self.scroll = 0;
+        self.jump_to_offset(offset, terminal_height);
+    }
+
+    /// Show only every Nth diff, cycling N through [`SAMPLE_STRIDES`] and
+    /// back to every diff, keeping the cursor on the nearest offset.
+    pub fn cycle_sample(&mut self, terminal_height: u16) {
+        let offset = self.cursor_offset();
+        let next = SAMPLE_STRIDES
+            .iter()
+            .position(|&stride| stride == self.sample_stride)
+            .map_or(0, |idx| (idx + 1) % SAMPLE_STRIDES.len());
+        self.sample_stride = SAMPLE_STRIDES[next];
+        self.apply_text_filter();
+        self.scroll = 0;
+        self.jump_to_offset(offset, terminal_height);
+    }
+
+    /// Bookmark the offset under the cursor, or remove its bookmark.
+    pub fn toggle_bookmark(&mut self) {
+        let Some(offset) = self.cursor_offset() else {
+            return;
+        };
+        let offset_text = format_offset(offset, self.offset_base);
+        match self.bookmarks.binary_search(&offset) {
+            Ok(idx) => {
+                self.bookmarks.remove(idx);
+                self.set_status(format!("Removed the bookmark at {}", offset_text));
+            }
+            Err(idx) => {
+                self.bookmarks.insert(idx, offset);
+                self.set_status(format!("Bookmarked {}", offset_text));
+            }
+        }
+    }
+
+    /// Move to the first bookmark past the cursor, wrapping around to the
+    /// first bookmark.
+    pub fn next_bookmark(&mut self, terminal_height: u16) {
+        let Some(&first) = self.bookmarks.first() else {
+            self.set_status("No bookmarks");
+            return;
+        };
+        let offset = self
+            .cursor_offset()
+            .and_then(|cursor| self.bookmarks.iter().find(|&&offset| offset > cursor))
+            .copied()
+            .unwrap_or(first);
+        self.jump_to_offset(Some(offset), terminal_height);
+    }
+
+    /// Where the viewer is now, to save with `--session`. The files are kept
+    /// in the order they were given, even when swapped.
+    pub fn session(&self) -> Session {
+        let (file1, file2) = if self.swapped {
+            (&self.file2_path, &self.file1_path)
+        } else {
+            (&self.file1_path, &self.file2_path)
+        };
+        Session {
+            file1: file1.clone(),
+            file2: file2.clone(),
+            diffs: self.all_pairs.len(),
+            cursor: self.cursor_offset(),
+            top: self.scroll * self.bytes_per_line,
+            bookmarks: self.bookmarks.clone(),
+            annotations: self.annotations.clone().into_iter().collect(),
+            search: self.search.kind.map(|kind| SavedSearch {
+                kind,
+                scope: self.search.scope,
+                query: self.search.query.clone(),
+            }),
+        }
+    }
+
+    /// Go back to where a saved session left off: its view, cursor,
+    /// bookmarks, annotations and search. A session for other files, or for files that
+    /// have changed since, is skipped with a warning.
+    pub fn restore_session(&mut self, session: &Session, terminal_height: u16) {
+        let current = self.session();
+        if session.file1 != current.file1
+            || session.file2 != current.file2
+            || session.diffs != current.diffs
+        {
+            self.set_status("The files changed since the session was saved, starting fresh");
+            return;
+        }
+        self.bookmarks = session.bookmarks.clone();
+        self.annotations = session.annotations.clone().into_iter().collect();
+        if let Some(search) = &session.search {
+            self.search.scope = search.scope;
+            self.run_search(&search.query, search.kind);
+        }
+        let rows = self.diffs.len().div_ceil(self.bytes_per_line);
+        self.scroll = (session.top / self.bytes_per_line).min(rows.saturating_sub(1));
+        self.jump_to_offset(session.cursor, terminal_height);
+    }
+
+    /// Whether the screen cell at `column`, `row` lies on the scrollbar track.
+    pub fn on_scrollbar(&self, column: u16, row: u16) -> bool {
+        self.scrollbar.is_some_and(|track| {
+            column == track.x && row >= track.y && row < track.y + track.height
+        })
+    }
+
+    /// Scroll the view by `rows`, up if negative, without scrolling past the
+    /// last row. The cursor only moves, keeping its column, if its row would
+    /// leave the screen.
+    pub fn scroll_by(&mut self, rows: isize, terminal_height: u16) {
+        let lines = self.visible_rows(terminal_height);
+        let total_rows = self.diffs.len().div_ceil(self.bytes_per_line);
+        self.scroll = self
+            .scroll
+            .saturating_add_signed(rows)
+            .min(total_rows.saturating_sub(lines));
+
+        let column = self.cursor_pos % self.bytes_per_line;
+        let cursor_line = self.cursor_pos / self.bytes_per_line;
+        let line = cursor_line.clamp(self.scroll, self.scroll + lines - 1);
+        if line != cursor_line {
+            self.cursor_pos =
+                (line * self.bytes_per_line + column).min(self.diffs.len().saturating_sub(1));
+        }
+    }
+
+    /// Diff index drawn at the screen cell `column`, `row` and the pane it's
+    /// in, if it lies on a byte in the hex or ASCII pane.
+    pub fn diff_at(&self, column: u16, row: u16) -> Option<(usize, Pane)> {
+        let hit = |area: Rect, cell_width: u16| {
+            let inside = column >= area.x
+                && column < area.x + area.width
+                && row >= area.y
+                && row < area.y + area.height;
+            inside.then(|| ((column - area.x) / cell_width) as usize)
+        };
+        let (cell, pane) = self
+            .hex_cells
+            .and_then(|area| hit(area, 3))
+            .or_else(|| self.hex2_cells.and_then(|area| hit(area, 3)))
+            .map(|cell| (cell, Pane::Hex))
+            .or_else(|| {
+                self.ascii_cells
+                    .and_then(|area| hit(area, 1))
+                    .map(|cell| (cell, Pane::Ascii))
+            })?;
+        if cell >= self.bytes_per_line {
+            return None;
+        }
+        // Reversed rows are right-aligned, so the last cell is the row's first byte
+        let cell = if self.reverse_bytes {
+            self.bytes_per_line - 1 - cell
+        } else {
+            cell
+        };
+        let index = (self.scroll + (row - self.hex_cells?.y) as usize) * self.bytes_per_line + cell;
+        (index < self.diffs.len()).then_some((index, pane))
+    }
+
+    /// Scroll to the relative position of screen row `row` on the scrollbar
+    /// track, keeping the cursor's column but moving it into the new top row.
+    pub fn seek_scrollbar(&mut self, row: u16, terminal_height: u16) {
+        let Some(track) = self.scrollbar else {
+            return;
+        };
+        let lines = self.visible_rows(terminal_height);
+        let rows = self.diffs.len().div_ceil(self.bytes_per_line);
+        let max_scroll = rows.saturating_sub(lines);
+        self.scroll = scroll_for_track_row(row.saturating_sub(track.y), track.height, max_scroll);
+
+        let column = self.cursor_pos % self.bytes_per_line;
+        self.cursor_pos =
+            (self.scroll * self.bytes_per_line + column).min(self.diffs.len().saturating_sub(1));
+    }
+
+    /// Move the cursor to the first diff.
+    pub fn goto_start(&mut self) {
+        self.cursor_pos = 0;
+        self.scroll = 0;
+    }
+
+    /// Move the cursor to the last diff.
+    pub fn goto_end(&mut self, terminal_height: u16) {
+        self.jump_to(self.diffs.len().saturating_sub(1), terminal_height);
+    }
+
+    /// Whether the last row of the diff is on screen.
+    pub fn at_bottom(&self, terminal_height: u16) -> bool {
+        let lines = self.visible_rows(terminal_height);
+        let rows = self.diffs.len().div_ceil(self.bytes_per_line);
+        self.scroll + lines >= rows
+    }
+
+    /// Whether a re-diff should scroll to the end: only when following and
+    /// already at the bottom, so scrolling back up pauses following.
+    pub fn should_follow(&self, terminal_height: u16) -> bool {
+        self.following && self.at_bottom(terminal_height)
+    }
+
+    /// Start or stop following new changes at the end of the diff.
+    pub fn toggle_follow(&mut self) {
+        self.following = !self.following;
+    }
+
+    /// Re-read both files from disk and recompute the diff. When following
+    /// from the bottom the view moves to the new end, otherwise the cursor
+    /// keeps its file offset where possible. Files that change are read
+    /// rather than mapped, as a mapping of a file truncated later can't be
+    /// read safely.
+    pub fn reload(&mut self, terminal_height: u16) {
+        if self.file1_path == STDIN_PATH || self.file2_path == STDIN_PATH {
+            self.set_status("Can't reload data read from stdin");
+            return;
+        }
+        let (file1_data, file2_data) =
+            match (read_file(&self.file1_path), read_file(&self.file2_path)) {
+                (Ok(file1_data), Ok(file2_data)) => (file1_data, file2_data),
+                (Err(err), _) | (_, Err(err)) => {
+                    self.set_status(format!("Could not reload: {}", err));
+                    return;
+                }
+            };
+        let (file1_data, file2_data) = match &self.zip_entry {
+            Some(name) => match extract_entries(
+                name,
+                (&self.file1_path, &file1_data),
+                (&self.file2_path, &file2_data),
+            ) {
+                Ok(entries) => entries,
+                Err(err) => {
+                    self.set_status(format!("Could not reload: {}", err));
+                    return;
+                }
+            },
+            None => (file1_data, file2_data),
+        };
+        let (file1_data, file2_data) = match &self.section {
+            Some(section) => match Section::extract(&section.name, &file1_data, &file2_data) {
+                Ok((section, file1_data, file2_data)) => {
+                    self.section = Some(section);
+                    (file1_data, file2_data)
+                }
+                Err(err) => {
+                    self.set_status(format!("Could not reload: {}", err));
+                    return;
+                }
+            },
+            None => (file1_data, file2_data),
+        };
+
+        let follow = self.should_follow(terminal_height);
+        let offset = self.cursor_offset();
+        self.all_pairs = diff_files_pairs_ignoring(&file1_data, &file2_data, &self.ignore);
+        self.file1_data = file1_data.into();
+        self.file2_data = file2_data.into();
+        let (query, kind) = (self.search.query.clone(), self.search.kind);
+        self.apply_text_filter();
+        if let Some(kind) = kind {
+            self.run_search(&query, kind);
+        }
+
+        if follow {
+            self.goto_end(terminal_height);
+        } else {
+            self.scroll = 0;
+            self.jump_to_offset(offset, terminal_height);
+        }
+    }
+
+    /// Move the cursor to the first diff at or after `offset`, or to the last
+    /// diff if there is none.
+    fn jump_to_offset(&mut self, offset: Option<usize>, terminal_height: u16) {
+        let index = offset.map_or(0, |offset| self.diffs.partition_point(|&(o, _)| o < offset));
+        self.jump_to(
+            index.min(self.diffs.len().saturating_sub(1)),
+            terminal_height,
+        );
+    }
+
+    /// Move the cursor to the given diff index and scroll it into view.
+    fn jump_to(&mut self, index: usize, terminal_height: u16) {
+        self.cursor_pos = index;
+        self.ensure_cursor_visible(terminal_height);
+    }
+
+    /// File offset of the byte under the cursor, if any.
+    pub fn cursor_offset(&self) -> Option<usize> {
+        self.diffs.get(self.cursor_pos).map(|&(offset, _)| offset)
+    }
+
+    /// Anchor offsets at the cursor so positions are shown relative to it,
+    /// moving the anchor if one is already set.
+    pub fn set_anchor(&mut self) {
+        if let Some(offset) = self.cursor_offset() {
+            self.anchor = Some(offset);
+            self.set_status(format!(
+                "Offsets relative to {}",
+                format_offset(offset, self.offset_base)
+            ));
+        }
+    }
+
+    /// Drop the anchor, going back to absolute offsets.
+    pub fn clear_anchor(&mut self) {
+        if self.anchor.take().is_some() {
+            self.set_status("Absolute offsets");
+        }
+    }
+
+    /// Annotation attached to the offset under the cursor, if any.
+    pub fn current_annotation(&self) -> Option<&String> {
+        self.cursor_offset()
+            .and_then(|offset| self.annotations.get(&offset))
+    }
+
+    /// Open the annotation prompt, pre-filled with any existing note.
+    pub fn start_annotation(&mut self) {
+        if self.cursor_offset().is_some() {
+            let note = self.current_annotation().cloned().unwrap_or_default();
+            self.prompt = Some(Prompt {
+                kind: PromptKind::Annotate,
+                input: InputPrompt::new("Annotate", &note),
+            });
+        }
+    }
+
+    /// Store a note on the offset under the cursor. An empty note removes the
+    /// annotation.
+    pub fn annotate(&mut self, note: &str) {
+        let Some(offset) = self.cursor_offset() else {
+            return;
+        };
+
+        let note = note.trim();
+        if note.is_empty() {
+            self.annotations.remove(&offset);
+        } else {
+            self.annotations.insert(offset, note.to_string());
+        }
+    }
+
+    /// Open the prompt for a byte value to watch.
+    pub fn start_watch(&mut self) {
+        self.prompt = Some(Prompt {
+            kind: PromptKind::Watch,
+            input: InputPrompt::new("Watch byte", "").with_validator(validate_byte),
+        });
+    }
+
+    /// Start highlighting every occurrence of `value`, or stop if it is
+    /// already watched.
+    pub fn toggle_watch(&mut self, value: u8) {
+        match self.watch_values.iter().position(|&v| v == value) {
+            Some(idx) => {
+                self.watch_values.remove(idx);
+            }
+            None => self.watch_values.push(value),
+        }
+    }
+
+    /// Number of diffs holding each watched value, in watch order.
+    pub fn watch_counts(&self) -> Vec<(u8, usize)> {
+        let mut counts = [0usize; 256];
+        if !self.watch_values.is_empty() {
+            for &(_, byte) in &self.diffs {
+                counts[byte as usize] += 1;
+            }
+        }
+        self.watch_values
+            .iter()
+            .map(|&value| (value, counts[value as usize]))
+            .collect()
+    }
+
+    /// Open the prompt to overwrite the byte under the cursor.
+    pub fn start_edit(&mut self) {
+        if let Some(offset) = self.cursor_offset() {
+            let current = self.display_byte(offset, self.diffs[self.cursor_pos].1);
+            self.prompt = Some(Prompt {
+                kind: PromptKind::Edit,
+                input: InputPrompt::new("Edit byte", &format!("{:02x}", current))
+                    .with_validator(validate_byte),
+            });
+        }
+    }
+
+    /// The byte shown for `offset`, taking any pending edit into account.
+    pub fn display_byte(&self, offset: usize, byte: u8) -> u8 {
+        self.edits.get(&offset).copied().unwrap_or(byte)
+    }
+
+    /// Open the prompt for where to write the patched base file.
+    pub fn start_write(&mut self) {
+        if self.edits.is_empty() {
+            self.set_status("No edits to write");
+            return;
+        }
+        if self.zip_entry.is_some() {
+            self.set_status("Can't write edits back into a zip entry");
+            return;
+        }
+        if self.section.is_some() && self.file1_path == STDIN_PATH {
+            self.set_status("Can't write a section of data read from stdin");
+            return;
+        }
+        self.prompt = Some(Prompt {
+            kind: PromptKind::Write,
+            input: InputPrompt::new(
+                "Write patched file to",
+                &format!("{}.patched", self.file1_path),
+            )
+            .with_validator(validate_path),
+        });
+    }
+
+    /// Open the prompt for where to write a markdown report of the session.
+    pub fn start_report(&mut self) {
+        self.prompt = Some(Prompt {
+            kind: PromptKind::Report,
+            input: InputPrompt::new("Write report to", &format!("{}.report.md", self.file1_path))
+                .with_validator(validate_path),
+        });
+    }
+
+    /// Write a markdown report of the diff and the annotations to `path`.
+    fn write_report(&mut self, path: &str) {
+        let diffs: Vec<(usize, u8)> = self
+            .all_pairs
+            .iter()
+            .map(|&(offset, old, _)| (offset, old))
+            .collect();
+        let report = Report {
+            file1: &self.file1_path,
+            file2: &self.file2_path,
+            file1_len: self.file1_data.len(),
+            file2_len: self.file2_data.len(),
+            diffs: &diffs,
+            annotations: &self.annotations,
+            base: self.offset_base,
+        };
+        self.set_status(match std::fs::write(path, report.to_markdown()) {
+            Ok(()) => format!("Wrote report to {}", path),
+            Err(err) => format!("Could not write {}: {}", path, err),
+        });
+    }
+
+    /// Write the base file with the pending edits applied to `path`. When
+    /// comparing a section, the edits are spliced back into the whole base
+    /// file, read again from disk.
+    fn write_patched(&mut self, path: &str) {
+        let patched = match &self.section {
+            Some(section) => match read_file(&self.file1_path) {
+                Ok(whole) => {
+                    let edits = self
+                        .edits
+                        .iter()
+                        .map(|(&offset, &byte)| (section.file1_start + offset, byte))
+                        .collect();
+                    apply_edits(&whole, &edits)
+                }
+                Err(err) => {
+                    self.set_status(format!("Could not read {}: {}", self.file1_path, err));
+                    return;
+                }
+            },
+            None => apply_edits(&self.file1_data, &self.edits),
+        };
+        self.set_status(match std::fs::write(path, patched) {
+            Ok(()) => format!("Wrote {} edits to {}", self.edits.len(), path),
+            Err(err) => format!("Could not write {}: {}", path, err),
+        });
+    }
+
+    /// Open the prompt for a file offset to jump to.
+    pub fn start_goto(&mut self) {
+        let validator = match self.offset_base {
+            OffsetBase::Hex => validate_hex_offset,
+            OffsetBase::Decimal => validate_decimal_offset,
+        };
+        self.prompt = Some(Prompt {
+            kind: PromptKind::Goto,
+            input: InputPrompt::new("Go to offset", "").with_validator(validator),
+        });
+    }
+
+    /// Move the cursor to the first diff at or after file `offset`.
+    fn goto_offset(&mut self, offset: usize, terminal_height: u16) {
+        let index = self.diffs.partition_point(|&(o, _)| o < offset);
+        if index < self.diffs.len() {
+            self.jump_to(index, terminal_height);
+        } else {
+            self.set_status(format!(
+                "No diff at or after {}",
+                format_offset(offset, self.offset_base)
+            ));
+        }
+    }
+
+    /// Open the prompt for an ASCII or hex search of the diff bytes.
+    pub fn start_search(&mut self, kind: SearchKind) {
+        let input = match kind {
+            SearchKind::Ascii => InputPrompt::new("Search", ""),
+            SearchKind::Hex => {
+                InputPrompt::new("Hex search", "").with_validator(validate_hex_query)
+            }
+        }
+        .with_history(self.search.history.queries(kind).to_vec());
+        self.prompt = Some(Prompt {
+            kind: PromptKind::Search(kind),
+            input,
+        });
+        self.search_origin = Some((self.cursor_pos, self.scroll));
+    }
+
+    /// Rerun the search for the query typed so far, moving the cursor to the
+    /// first match, or back to where it was before the search if there is
+    /// none. An empty query clears the matches. Does nothing unless a search
+    /// prompt is open and searches are incremental; hex queries with an
+    /// invalid character keep the last matches until they are fixed.
+    pub fn update_search(&mut self, terminal_height: u16) {
+        let Some(Prompt {
+            kind: PromptKind::Search(kind),
+            input,
+        }) = &self.prompt
+        else {
+            return;
+        };
+        if !self.incremental_search {
+            return;
+        }
+        let (kind, query) = (*kind, input.text().to_string());
+        if kind == SearchKind::Hex && matches!(hex_query_state(&query), HexQueryState::Invalid(_)) {
+            return;
+        }
+
+        if query.is_empty() {
+            self.search.cancel();
+        } else {
+            self.run_search(&query, kind);
+        }
+        match self.current_match_target() {
+            Some(Ok(pos)) => self.jump_to(pos, terminal_height),
+            _ => self.restore_search_origin(),
+        }
+    }
+
+    /// Search the bytes of the current search scope for `query`.
+    fn run_search(&mut self, query: &str, kind: SearchKind) {
+        let source = match self.search.scope {
+            SearchScope::Diffs => SearchSource::Diffs(&self.diffs),
+            SearchScope::File1 => SearchSource::File(&self.file1_data),
+            SearchScope::File2 => SearchSource::File(&self.file2_data),
+        };
+        self.search.submit(query, kind, source);
+    }
+
+    /// Search the next scope: the diffs, then all of file1, then all of
+    /// file2. An open search prompt reruns its query in the new scope.
+    pub fn cycle_search_scope(&mut self, terminal_height: u16) {
+        self.search.scope = self.search.scope.next();
+        self.search.cancel();
+        self.update_search(terminal_height);
+    }
+
+    /// Put the cursor and view back where they were when the search prompt
+    /// opened.
+    fn restore_search_origin(&mut self) {
+        if let Some((cursor_pos, scroll)) = self.search_origin {
+            self.cursor_pos = cursor_pos;
+            self.scroll = scroll;
+        }
+    }
+
+    /// Select the next search match and move the cursor to it.
+    pub fn next_match(&mut self, terminal_height: u16) {
+        self.search.next_match();
+        self.goto_current_match(terminal_height);
+    }
+
+    /// Select the previous search match and move the cursor to it.
+    pub fn prev_match(&mut self, terminal_height: u16) {
+        self.search.prev_match();
+        self.goto_current_match(terminal_height);
+    }
+
+    /// Open the prompt for the number of the search match to jump to,
+    /// starting with `digit`. Does nothing without matches.
+    pub fn start_match_number(&mut self, digit: char) {
+        if self.search.matches.is_empty() {
+            return;
+        }
+        self.prompt = Some(Prompt {
+            kind: PromptKind::MatchNumber,
+            input: InputPrompt::new("Go to match", &digit.to_string())
+                .with_validator(validate_match_number),
+        });
+    }
+
+    /// Open the list of search matches with the current one selected, or
+    /// close it if it is open.
+    pub fn toggle_match_list(&mut self) {
+        self.match_list = match self.match_list {
+            Some(_) => None,
+            None if self.search.matches.is_empty() => {
+                self.report_no_matches();
+                None
+            }
+            None => Some(self.search.current_match.unwrap_or(0)),
+        };
+    }
+
+    /// Move the match list selection by `delta` entries, clamped to the list.
+    pub fn move_match_list(&mut self, delta: isize) {
+        if let Some(selected) = self.match_list {
+            let last = self.search.matches.len().saturating_sub(1);
+            self.match_list = Some(selected.saturating_add_signed(delta).min(last));
+        }
+    }
+
+    /// Make the selected entry of the match list the current match, move the
+    /// cursor to it and close the list.
+    pub fn select_match_list(&mut self, terminal_height: u16) {
+        if let Some(selected) = self.match_list.take() {
+            self.search.current_match = Some(selected);
+            self.goto_current_match(terminal_height);
+        }
+    }
+
+    /// Open the overlay listing the changes of the files aligned by content,
+    /// selecting the first one at or after the cursor, or close it if it is
+    /// open.
+    pub fn toggle_alignment(&mut self) {
+        if self.alignment.take().is_some() {
+            return;
+        }
+        let mut changes = diff_aligned(&self.file1_data, &self.file2_data);
+        changes.retain(|change| !is_ignored(change.offset1, &self.ignore));
+        if changes.is_empty() {
+            self.set_status("No differences once the files are aligned");
+            return;
+        }
+        let index = aligned_index(&changes, self.cursor_offset().unwrap_or(0));
+        let selected = index.min(changes.len() - 1);
+        self.alignment = Some(AlignmentList { changes, selected });
+    }
+
+    /// Move the alignment overlay selection by `delta` changes, clamped to
+    /// the list.
+    pub fn move_alignment(&mut self, delta: isize) {
+        if let Some(list) = self.alignment.as_mut() {
+            let last = list.changes.len() - 1;
+            list.selected = list.selected.saturating_add_signed(delta).min(last);
+        }
+    }
+
+    /// Close the alignment overlay and move the cursor to the first diff at
+    /// or after the selected change's offset in the first file.
+    pub fn select_alignment(&mut self, terminal_height: u16) {
+        if let Some(list) = self.alignment.take() {
+            self.goto_offset(list.changes[list.selected].offset1, terminal_height);
+        }
+    }
+
+    /// Select the first aligned change at or after the first file's `offset`,
+    /// and tell where `offset` lines up in the second file.
+    fn goto_aligned(&mut self, offset: usize) {
+        let Some(list) = self.alignment.as_mut() else {
+            return;
+        };
+        list.selected = aligned_index(&list.changes, offset).min(list.changes.len() - 1);
+        let other = offset_in_second(&list.changes, offset);
+        self.set_status(format!(
+            "{} lines up with {} in {}",
+            format_offset(offset, self.offset_base),
+            format_offset(other, self.offset_base),
+            self.file2_path
+        ));
+    }
+
+    /// Open the whole-file view folded, with the cursor's row centered, or
+    /// close it if it is open. The cursor stays where it was.
+    pub fn toggle_file_view(&mut self, terminal_height: u16) {
+        if self.file_view.take().is_some() {
+            return;
+        }
+        let lines = self.file_view_lines(true);
+        let row = self.cursor_offset().unwrap_or(0) / FILE_VIEW_BYTES_PER_ROW;
+        let scroll = line_of_row(&lines, row)
+            .min(lines.len().saturating_sub(1))
+            .saturating_sub(file_view_rows(terminal_height) / 2);
+        self.file_view = Some(FileView {
+            lines,
+            folded: true,
+            scroll,
+        });
+    }
+
+    /// Fold the long unchanged runs of the whole-file view, or expand them,
+    /// keeping the row at the top of the view in place.
+    pub fn toggle_folds(&mut self) {
+        let folded = match &self.file_view {
+            Some(view) => !view.folded,
+            None => return,
+        };
+        let lines = self.file_view_lines(folded);
+        if let Some(view) = self.file_view.as_mut() {
+            let top = view
+                .lines
+                .get(view.scroll)
+                .map_or(0, |line| line.rows().start);
+            view.scroll = line_of_row(&lines, top).min(lines.len().saturating_sub(1));
+            view.lines = lines;
+            view.folded = folded;
+        }
+    }
+
+    /// Scroll the whole-file view by `lines`, up if negative.
+    pub fn scroll_file_view(&mut self, lines: isize) {
+        if let Some(view) = self.file_view.as_mut() {
+            let last = view.lines.len().saturating_sub(1);
+            view.scroll = view.scroll.saturating_add_signed(lines).min(last);
+        }
+    }
+
+    /// Lines of the whole-file view, with long unchanged runs folded if
+    /// `folded`.
+    fn file_view_lines(&self, folded: bool) -> Vec<FoldedLine> {
+        let len = self.file1_data.len();
+        let folds = if folded {
+            let offsets: Vec<usize> = self
+                .all_pairs
+                .iter()
+                .map(|&(offset, _, _)| offset)
+                .collect();
+            fold_rows(&offsets, len, FILE_VIEW_BYTES_PER_ROW, FILE_VIEW_FOLD_ROWS)
+        } else {
+            Vec::new()
+        };
+        folded_lines(len, FILE_VIEW_BYTES_PER_ROW, &folds)
+    }
+
+    fn goto_current_match(&mut self, terminal_height: u16) {
+        match self.current_match_target() {
+            Some(Ok(pos)) => self.jump_to(pos, terminal_height),
+            Some(Err(offset)) => self.set_status(format!(
+                "Match at {} covers no differences",
+                format_offset(offset, self.offset_base)
+            )),
+            None => self.report_no_matches(),
+        }
+    }
+
+    /// Diff index to move the cursor to for the current match: where it
+    /// starts, or for a whole-file search the first differing byte it
+    /// covers. A match covering no differing byte gives its file offset as
+    /// the error.
+    fn current_match_target(&self) -> Option<Result<usize, usize>> {
+        let start = self.search.current_match_pos()?;
+        if self.search.scope == SearchScope::Diffs {
+            return Some(Ok(start));
+        }
+        let index = self.diffs.partition_point(|&(offset, _)| offset < start);
+        let covered = self
+            .diffs
+            .get(index)
+            .is_some_and(|&(offset, _)| offset < start + self.search.pattern_len);
+        Some(if covered { Ok(index) } else { Err(start) })
+    }
+
+    /// Explain why there is no search match to move to.
+    fn report_no_matches(&mut self) {
+        if self.search.kind.is_none() {
+            self.set_status("No search");
+        } else {
+            self.set_status(format!("No matches for {}", self.search.query));
+        }
+    }
+
+    /// Apply the submitted text of the open prompt and close it.
+    pub fn submit_prompt(&mut self, text: &str, terminal_height: u16) {
+        let Some(prompt) = self.prompt.take() else {
+            return;
+        };
+
+        match prompt.kind {
+            PromptKind::Annotate => self.annotate(text),
+            PromptKind::Watch => {
+                if let Some(value) = parse_byte(text) {
+                    self.toggle_watch(value);
+                }
+            }
+            PromptKind::Edit => {
+                if let (Some(offset), Some(value)) = (self.cursor_offset(), parse_byte(text)) {
+                    self.edits.insert(offset, value);
+                }
+            }
+            PromptKind::Write => self.write_patched(text.trim()),
+            PromptKind::Report => self.write_report(text.trim()),
+            PromptKind::Goto => match parse_offset(text, self.offset_base) {
+                Some(offset) if self.alignment.is_some() => self.goto_aligned(offset),
+                Some(offset) => self.goto_offset(offset, terminal_height),
+                None => {}
+            },
+            PromptKind::Search(kind) => {
+                self.run_search(text, kind);
+                self.goto_current_match(terminal_height);
+                self.search_origin = None;
+                self.search.history.remember(kind, text);
+                // History is a convenience, so failing to save it isn't reported
+                let _ = self.search.history.save();
+            }
+            PromptKind::MatchNumber => {
+                if let Ok(n) = text.parse() {
+                    self.search.goto_match(n);
+                    self.goto_current_match(terminal_height);
+                }
+            }
+        }
+    }
+
+    /// Move to the next diff in frequency order: every occurrence of the most
+    /// common byte value, then of the next most common, and so on, wrapping
+    /// around after the rarest.
+    pub fn next_by_frequency(&mut self, terminal_height: u16) {
+        let tally = value_frequencies(&self.diffs);
+        if tally.is_empty() {
+            self.set_status("No differences");
+            return;
+        }
+
+        let (mut rank, mut nth) = match self.frequency {
+            Some(current) if current.rank < tally.len() => (current.rank, current.nth + 1),
+            _ => (0, 0),
+        };
+        if nth >= tally[rank].1 {
+            rank = (rank + 1) % tally.len();
+            nth = 0;
+        }
+
+        let (value, count) = tally[rank];
+        let index = self
+            .diffs
+            .iter()
+            .enumerate()
+            .filter(|&(_, &(_, byte))| byte == value)
+            .nth(nth)
+            .map_or(0, |(index, _)| index);
+        self.frequency = Some(FrequencyCursor {
+            value,
+            rank,
+            nth,
+            count,
+        });
+        self.jump_to(index, terminal_height);
+    }
+
+    /// Copy a debugger expression for the byte under the cursor.
+    pub fn copy_debugger_expr(&mut self) {
+        let Some(&(offset, byte)) = self.diffs.get(self.cursor_pos) else {
+            self.set_status("Nothing to copy");
+            return;
+        };
+        let byte = self.display_byte(offset, byte);
+        let expr = format_debugger_expr(offset, byte, self.debugger_style, self.offset_base);
+        self.set_status(match copy_text(&expr, self.clipboard) {
+            Ok(()) => format!("Copied {}", expr),
+            Err(err) => format!("Could not copy: {}", err),
+        });
+    }
+
+    /// Start selecting from the cursor, or stop selecting.
+    pub fn toggle_selection(&mut self) {
+        self.selection = match self.selection {
+            Some(_) => None,
+            None => Some(Selection::new(self.cursor_pos)),
+        };
+    }
+
+    /// Bytes of the selection as displayed, or the byte under the cursor when
+    /// nothing is selected.
+    fn selected_bytes(&self) -> Vec<u8> {
+        let range = match self.selection {
+            Some(selection) => selection.start()..selection.end() + 1,
+            None => self.cursor_pos..self.cursor_pos + 1,
+        };
+        self.diffs
+            .get(range)
+            .unwrap_or_default()
+            .iter()
+            .map(|&(offset, byte)| self.display_byte(offset, byte))
+            .collect()
+    }
+
+    /// Switch `y` to the next copy format.
+    pub fn cycle_copy_format(&mut self) {
+        self.copy_format = self.copy_format.next();
+        self.set_status(format!("Copying as {}", self.copy_format.label()));
+    }
+
+    /// Copy the selected bytes in `format` and stop selecting.
+    pub fn copy_selection(&mut self, format: CopyFormat) {
+        let bytes = self.selected_bytes();
+        if bytes.is_empty() {
+            self.set_status("Nothing to copy");
+            return;
+        }
+        self.selection = None;
+        self.set_status(
+            match copy_text(&format.format(&bytes, self.copy_whitespace), self.clipboard) {
+                Ok(()) => format!(
+                    "Copied {} byte{} as {}",
+                    bytes.len(),
+                    if bytes.len() == 1 { "" } else { "s" },
+                    format.label()
+                ),
+                Err(err) => format!("Could not copy: {}", err),
+            },
+        );
+    }
+
+    /// Copy every diff byte in the copy format. Text too long for OSC 52 is
+    /// written to a file in the temp directory instead.
+    pub fn copy_all(&mut self) {
+        if self.diffs.is_empty() {
+            self.set_status("Nothing to copy");
+            return;
+        }
+        let bytes: Vec<u8> = self
+            .diffs
+            .iter()
+            .map(|&(offset, byte)| self.display_byte(offset, byte))
+            .collect();
+        let text = self.copy_format.format(&bytes, self.copy_whitespace);
+        let count = format!(
+            "{} byte{} as {}",
+            bytes.len(),
+            if bytes.len() == 1 { "" } else { "s" },
+            self.copy_format.label()
+        );
+        if self.clipboard == ClipboardBackend::Osc52 && !osc52_fits(&text) {
+            self.set_status(match write_overflow(&text) {
+                Ok(path) => format!("Too long to copy, wrote {} to {}", count, path.display()),
+                Err(err) => format!("Could not write a temp file: {}", err),
+            });
+            return;
+        }
+        self.set_status(match copy_text(&text, self.clipboard) {
+            Ok(()) => format!("Copied all {}", count),
+            Err(err) => format!("Could not copy: {}", err),
+        });
+    }
+
+    /// Copy the file offsets of the selection, or of the byte under the
+    /// cursor, and stop selecting.
+    pub fn copy_offsets(&mut self) {
+        if self.diffs.is_empty() {
+            self.set_status("Nothing to copy");
+            return;
+        }
+        let selection = self
+            .selection
+            .take()
+            .unwrap_or(Selection::new(self.cursor_pos));
+        let offsets = format_offsets(&self.diffs, selection, self.offset_base);
+        self.set_status(match copy_text(&offsets, self.clipboard) {
+            Ok(()) => format!(
+                "Copied {} offset{}",
+                selection.end() - selection.start() + 1,
+                if selection.start() == selection.end() {
+                    ""
+                } else {
+                    "s"
+                }
+            ),
+            Err(err) => format!("Could not copy: {}", err),
+        });
+    }
+
+    /// Close the open prompt without applying it. Cancelling an incremental
+    /// search also drops its matches and puts the cursor back.
+    pub fn cancel_prompt(&mut self) {
+        let prompt = self.prompt.take();
+        if let Some(Prompt {
+            kind: PromptKind::Search(_),
+            ..
+        }) = prompt
+        {
+            if self.incremental_search {
+                self.search.cancel();
+                self.restore_search_origin();
+            }
+            self.search_origin = None;
         }
     }
 
@@ -90,3 +1726,555 @@ impl App {
         self.running = false;
     }
 }
+
+/// Scroll offset for a click on row `row` of a scrollbar track `track_len`
+/// rows tall, when the view can scroll down `max_scroll` rows. The top row
+/// maps to the start and the bottom row to the end; rows past the track are
+/// clamped.
+pub fn scroll_for_track_row(row: u16, track_len: u16, max_scroll: usize) -> usize {
+    if track_len <= 1 {
+        return 0;
+    }
+    let last = (track_len - 1) as usize;
+    let row = (row as usize).min(last);
+    (row * max_scroll + last / 2) / last
+}
+
+/// Validator for prompts asking for a file path.
+fn validate_path(path: &str) -> Result<(), String> {
+    if path.trim().is_empty() {
+        Err("expected a path".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::{diff_files, diff_files_pairs};
+
+    /// A viewer over the diff of two in-memory files.
+    fn app_for(file1: &[u8], file2: &[u8]) -> App {
+        App::new(
+            file1.to_vec(),
+            file2.to_vec(),
+            diff_files(file1, file2),
+            diff_files_pairs(file1, file2),
+        )
+    }
+
+    #[test]
+    fn idle_ticks_redraw_only_when_the_timer_text_changes() {
+        let mut app = app_for(b"abc", b"abd");
+        // The timer is off by default, so nothing changes
+        assert!(!app.tick(24));
+        app.show_timer = true;
+        assert!(!app.tick(24));
+        app.started -= Duration::from_secs(2);
+        assert!(app.tick(24));
+        assert!(!app.tick(24));
+    }
+
+    /// Panic unless the cursor's row is among the rows on screen.
+    fn assert_cursor_visible(app: &App, terminal_height: u16, step: &str) {
+        let row = app.cursor_pos / app.bytes_per_line;
+        let lines = app.visible_rows(terminal_height);
+        assert!(
+            app.scroll <= row && row < app.scroll + lines,
+            "after {}: cursor row {} outside rows {}..{}",
+            step,
+            row,
+            app.scroll,
+            app.scroll + lines
+        );
+        assert!(app.cursor_pos < app.diffs.len(), "after {}", step);
+    }
+
+    #[test]
+    fn cursor_stays_visible_through_mixed_movement() {
+        let mut app = app_for(&[0; 1_000], &[1; 1_000]);
+        let (mut width, mut height) = (80, 24);
+        app.resize(width, height);
+        app.scrollbar = Some(Rect::new(79, 1, 1, 20));
+
+        // Each step may change the terminal width and height
+        type Step = fn(&mut App, &mut u16, &mut u16);
+        let steps: [(&str, Step); 14] = [
+            ("page down", |app, _, h| app.page_down(*h)),
+            ("right", |app, _, h| app.move_cursor_right(*h)),
+            ("page down", |app, _, h| app.page_down(*h)),
+            ("cycle width", |app, w, h| app.cycle_width(*w, *h)),
+            ("down", |app, _, h| app.move_cursor_down(*h)),
+            ("goto end", |app, _, h| app.goto_end(*h)),
+            ("cycle width", |app, w, h| app.cycle_width(*w, *h)),
+            ("page up", |app, _, h| app.page_up(*h)),
+            ("left", |app, _, h| app.move_cursor_left(*h)),
+            ("narrow and short", |app, w, h| {
+                (*w, *h) = (40, 10);
+                app.resize(*w, *h)
+            }),
+            ("seek scrollbar", |app, _, h| app.seek_scrollbar(15, *h)),
+            ("wide and tall", |app, w, h| {
+                (*w, *h) = (200, 60);
+                app.resize(*w, *h)
+            }),
+            ("page up", |app, _, h| app.page_up(*h)),
+            ("cycle width", |app, w, h| app.cycle_width(*w, *h)),
+        ];
+        for _ in 0..3 {
+            for (step, apply) in steps {
+                apply(&mut app, &mut width, &mut height);
+                assert_cursor_visible(&app, height, step);
+            }
+        }
+    }
+
+    #[test]
+    fn row_moves_keep_the_column_and_entry_moves_wrap() {
+        let mut app = app_for(&[0; 100], &[1; 100]);
+        app.bytes_per_line = 8;
+        app.cursor_pos = 3;
+        app.move_cursor_down(24);
+        assert_eq!(app.cursor_pos, 11);
+        app.move_cursor_down(24);
+        app.move_cursor_up(24);
+        assert_eq!(app.cursor_pos % 8, 3);
+
+        // One entry at a time, wrapping onto the next row
+        app.cursor_pos = 7;
+        app.move_cursor_right(24);
+        assert_eq!(app.cursor_pos, 8);
+        app.move_cursor_left(24);
+        app.move_cursor_down(24);
+        assert_eq!(app.cursor_pos, 15);
+
+        // A row move covers a different distance at another width
+        app.bytes_per_line = 16;
+        app.cursor_pos = 3;
+        app.move_cursor_down(24);
+        assert_eq!(app.cursor_pos, 19);
+        app.move_cursor_right(24);
+        assert_eq!(app.cursor_pos, 20);
+
+        // Into the shorter last row, the column holds or clamps to its end
+        app.bytes_per_line = 8;
+        app.cursor_pos = 91;
+        app.move_cursor_down(24);
+        assert_eq!(app.cursor_pos, 99);
+        app.cursor_pos = 94;
+        app.move_cursor_down(24);
+        assert_eq!(app.cursor_pos, 99);
+    }
+
+    #[test]
+    fn a_search_without_matches_says_so_and_stays_put() {
+        let mut app = app_for(b"abcdef", b"ABCDEF");
+        app.next_match(24);
+        assert_eq!(
+            app.status.as_ref().map(|(message, _)| message.as_str()),
+            Some("No search")
+        );
+
+        app.cursor_pos = 2;
+        // As submitting the prompt does, without saving the history
+        app.run_search("zzz", SearchKind::Ascii);
+        app.goto_current_match(24);
+        assert_eq!(app.cursor_pos, 2);
+        assert_eq!(
+            app.status.as_ref().map(|(message, _)| message.as_str()),
+            Some("No matches for zzz")
+        );
+        app.next_match(24);
+        assert_eq!(app.cursor_pos, 2);
+
+        // The message is transient
+        if let Some((_, shown)) = app.status.as_mut() {
+            *shown -= STATUS_DURATION;
+        }
+        assert!(app.tick(24));
+        assert!(app.status.is_none());
+    }
+
+    #[test]
+    fn reload_follows_new_changes_only_from_the_bottom() {
+        let dir = std::env::temp_dir();
+        let path1 = dir.join(format!("dring-follow-{}-1", std::process::id()));
+        let path2 = dir.join(format!("dring-follow-{}-2", std::process::id()));
+        let mut app = app_for(&[0; 64], &[1; 64]);
+        app.file1_path = path1.to_string_lossy().into_owned();
+        app.file2_path = path2.to_string_lossy().into_owned();
+        app.resize(80, 24);
+        app.toggle_follow();
+        app.goto_end(24);
+        assert!(app.should_follow(24));
+
+        std::fs::write(&path1, [0; 4096]).unwrap();
+        std::fs::write(&path2, [1; 4096]).unwrap();
+        app.reload(24);
+        assert_eq!(app.cursor_pos, 4095);
+        assert!(app.at_bottom(24));
+
+        // Scrolled back up, following pauses and the cursor keeps its byte
+        app.goto_start();
+        assert!(!app.should_follow(24));
+        std::fs::write(&path1, [0; 8192]).unwrap();
+        std::fs::write(&path2, [1; 8192]).unwrap();
+        app.reload(24);
+        assert_eq!(app.cursor_pos, 0);
+        assert_eq!(app.diffs.len(), 8192);
+
+        app.toggle_follow();
+        app.goto_end(24);
+        assert!(!app.should_follow(24));
+        std::fs::remove_file(path1).unwrap();
+        std::fs::remove_file(path2).unwrap();
+    }
+
+    #[test]
+    fn match_list_selects_a_match_and_moves_there() {
+        let mut app = app_for(b"xaxaxa", b"......");
+        app.toggle_match_list();
+        assert_eq!(app.match_list, None);
+
+        app.run_search("a", SearchKind::Ascii);
+        app.goto_current_match(24);
+        assert_eq!(app.cursor_pos, 1);
+        app.toggle_match_list();
+        assert_eq!(app.match_list, Some(0));
+        app.move_match_list(5);
+        assert_eq!(app.match_list, Some(2));
+        app.move_match_list(-1);
+        app.select_match_list(24);
+        assert_eq!(app.cursor_pos, 3);
+        assert_eq!(app.match_list, None);
+
+        // Reopened on the current match, and closed by toggling again
+        app.toggle_match_list();
+        assert_eq!(app.match_list, Some(1));
+        app.move_match_list(-10);
+        assert_eq!(app.match_list, Some(0));
+        app.toggle_match_list();
+        assert_eq!(app.match_list, None);
+        assert_eq!(app.cursor_pos, 3);
+    }
+
+    #[test]
+    fn anchor_follows_the_cursor_until_cleared() {
+        let mut app = app_for(b"abcdef", b"aXcdYf");
+        app.clear_anchor();
+        assert!(app.status.is_none());
+
+        app.set_anchor();
+        assert_eq!(app.anchor, Some(1));
+        app.move_cursor_right(24);
+        app.set_anchor();
+        assert_eq!(app.anchor, Some(4));
+        assert_eq!(
+            app.status.as_ref().map(|(message, _)| message.as_str()),
+            Some("Offsets relative to 00000004")
+        );
+        app.clear_anchor();
+        assert_eq!(app.anchor, None);
+        assert_eq!(
+            app.status.as_ref().map(|(message, _)| message.as_str()),
+            Some("Absolute offsets")
+        );
+    }
+
+    #[test]
+    fn width_changes_keep_the_cursor_on_its_byte() {
+        let mut app = app_for(&[0; 500], &[1; 500]);
+        app.resize(80, 24);
+        app.jump_to(321, 24);
+        for _ in 0..4 {
+            app.cycle_width(80, 24);
+            assert_eq!(app.cursor_pos, 321);
+            assert_cursor_visible(&app, 24, "cycle width");
+        }
+    }
+
+    #[test]
+    fn paging_stops_at_either_end() {
+        let mut app = app_for(&[0; 300], &[1; 300]);
+        app.width = Some(8);
+        app.resize(80, 24);
+        let rows = app.diffs.len().div_ceil(app.bytes_per_line);
+        let lines = app.visible_rows(24);
+        for _ in 0..10 {
+            app.page_down(24);
+            assert!(app.scroll + lines <= rows);
+        }
+        assert_eq!(app.cursor_pos, 299);
+        assert!(app.at_bottom(24));
+        for _ in 0..10 {
+            app.page_up(24);
+        }
+        // Near the start the cursor lands on the first diff
+        assert_eq!((app.cursor_pos, app.scroll), (0, 0));
+        app.goto_end(24);
+        assert_eq!(app.scroll, rows - lines);
+        assert_cursor_visible(&app, 24, "goto end");
+    }
+
+    #[test]
+    fn scrollbar_rows_map_onto_the_whole_scroll_range() {
+        assert_eq!(scroll_for_track_row(0, 20, 500), 0);
+        assert_eq!(scroll_for_track_row(19, 20, 500), 500);
+        // Rows past the track, as when dragging below it, clamp to the end
+        assert_eq!(scroll_for_track_row(40, 20, 500), 500);
+        assert_eq!(scroll_for_track_row(10, 21, 500), 250);
+        // A track too short to drag, or nothing to scroll
+        assert_eq!(scroll_for_track_row(0, 1, 500), 0);
+        assert_eq!(scroll_for_track_row(5, 0, 500), 0);
+        assert_eq!(scroll_for_track_row(5, 20, 0), 0);
+        let scrolls: Vec<usize> = (0..20)
+            .map(|row| scroll_for_track_row(row, 20, 7))
+            .collect();
+        assert!(scrolls.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn annotations_are_restored_from_a_saved_session() {
+        let mut app = app_for(b"abcd", b"xbyd");
+        app.annotate("magic \"number\" = 1");
+        app.jump_to_largest_delta(24);
+        app.annotate("second");
+        let saved = Session::parse(&app.session().serialize()).unwrap();
+
+        let mut restored = app_for(b"abcd", b"xbyd");
+        restored.restore_session(&saved, 24);
+        assert_eq!(restored.annotations, app.annotations);
+        assert_eq!(restored.current_annotation(), app.current_annotation());
+    }
+
+    #[test]
+    fn goto_in_the_alignment_maps_offsets_through_it() {
+        // Two bytes inserted after "ab" and one deleted after "ghij"
+        let mut app = app_for(b"abcdefghijKlm", b"abXYcdefghijlm");
+        app.file2_path = "new.bin".to_string();
+        app.toggle_alignment();
+        let changes = app.alignment.as_ref().unwrap().changes.clone();
+        assert_eq!(changes.len(), 3);
+
+        // Offset 4 comes after the insertion, so it selects the deletion and
+        // lines up with offset 6 of the second file
+        app.start_goto();
+        app.submit_prompt("4", 24);
+        assert_eq!(app.alignment.as_ref().unwrap().selected, 2);
+        assert_eq!(
+            app.status.as_ref().map(|(message, _)| message.as_str()),
+            Some("00000004 lines up with 00000006 in new.bin")
+        );
+        // Offset 2 lands on the bytes inserted just before it
+        app.start_goto();
+        app.submit_prompt("2", 24);
+        assert_eq!(app.alignment.as_ref().unwrap().selected, 0);
+
+        // Choosing a change goes back to the positional diff at its offset
+        app.move_alignment(5);
+        app.select_alignment(24);
+        assert!(app.alignment.is_none());
+        assert_eq!(app.cursor_offset(), Some(10));
+    }
+
+    #[test]
+    fn alignment_opens_at_the_cursor() {
+        let mut app = app_for(b"abcdefgh", b"aXcdefYh");
+        app.jump_to(1, 24);
+        app.toggle_alignment();
+        assert_eq!(app.alignment.as_ref().unwrap().selected, 1);
+        app.toggle_alignment();
+        assert!(app.alignment.is_none());
+
+        let mut app = app_for(b"abc", b"abc");
+        app.toggle_alignment();
+        assert!(app.alignment.is_none());
+    }
+
+    #[test]
+    fn folding_the_file_view_keeps_the_cursor_and_the_top_row() {
+        let file1 = vec![0; 4096];
+        let mut file2 = file1.clone();
+        (file2[0x13], file2[0x800], file2[0x801]) = (1, 2, 3);
+        let mut app = app_for(&file1, &file2);
+        app.resize(80, 24);
+        app.jump_to(1, 24);
+        let scroll = app.scroll;
+
+        app.toggle_file_view(24);
+        let view = app.file_view.as_ref().unwrap();
+        assert!(view.folded);
+        assert_eq!(view.lines.len(), 5);
+        assert_eq!(view.scroll, 0);
+
+        // Expanding keeps the row at the top of the view, here 0x800's
+        app.scroll_file_view(3);
+        app.toggle_folds();
+        let view = app.file_view.as_ref().unwrap();
+        assert!(!view.folded);
+        assert_eq!(view.lines.len(), 256);
+        assert_eq!(view.scroll, 0x80);
+        // Folding again lands on the line that now holds that row
+        app.scroll_file_view(5);
+        app.toggle_folds();
+        assert_eq!(app.file_view.as_ref().unwrap().scroll, 4);
+        app.toggle_folds();
+        assert_eq!(app.file_view.as_ref().unwrap().scroll, 0x81);
+
+        app.toggle_file_view(24);
+        assert!(app.file_view.is_none());
+        assert_eq!((app.cursor_pos, app.scroll), (1, scroll));
+        assert_eq!(app.cursor_offset(), Some(0x800));
+    }
+
+    #[cfg(all(feature = "mmap", unix))]
+    #[test]
+    fn the_viewer_works_on_mapped_files() {
+        use crate::file::load_input;
+
+        let path = std::env::temp_dir().join(format!("dring-app-mmap-{}", std::process::id()));
+        std::fs::write(&path, b"abcd").unwrap();
+        let file1 = load_input(path.to_str().unwrap()).unwrap();
+        assert!(matches!(file1, FileData::Mapped(_)));
+        let mut app = App::new(
+            file1,
+            b"abxd".to_vec(),
+            diff_files(b"abcd", b"abxd"),
+            diff_files_pairs(b"abcd", b"abxd"),
+        );
+        app.swap_files(24);
+        app.swap_files(24);
+        assert!(matches!(app.file1_data, FileData::Mapped(_)));
+        assert_eq!(&*app.file1_data, b"abcd");
+        assert_eq!(app.cursor_offset(), Some(2));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn largest_delta_jumps_cycle_and_wrap() {
+        // Deltas: 1, 255, 1, 255, 128. The cursor starts on diff 0, which is
+        // ranked too, but the first press still goes to the largest
+        let mut app = app_for(b"\x00\x00\x00\xff\x80", b"\x01\xff\x01\x00\x00");
+        let mut visited = Vec::new();
+        for _ in 0..6 {
+            app.jump_to_largest_delta(24);
+            visited.push(app.cursor_pos);
+        }
+        assert_eq!(visited, vec![1, 3, 4, 0, 2, 1]);
+
+        // Any other key starts over from the largest
+        app.jump_to_largest_delta(24);
+        app.delta_rank = None;
+        app.jump_to_largest_delta(24);
+        assert_eq!(app.cursor_pos, 1);
+        // So does moving the cursor off the ranked diff
+        app.jump_to_largest_delta(24);
+        app.move_cursor_right(24);
+        app.jump_to_largest_delta(24);
+        assert_eq!(app.cursor_pos, 1);
+    }
+
+    #[test]
+    fn largest_delta_jump_starts_from_the_top_off_the_ranking() {
+        let mut file2 = vec![0; 2 * TOP_DELTAS];
+        for (i, byte) in file2.iter_mut().enumerate() {
+            *byte = i as u8 + 1;
+        }
+        let mut app = app_for(&[0; 2 * TOP_DELTAS], &file2);
+        // The smallest deltas are not among the ranked ones
+        app.cursor_pos = 0;
+        app.jump_to_largest_delta(24);
+        assert_eq!(app.cursor_pos, 2 * TOP_DELTAS - 1);
+    }
+
+    #[test]
+    fn largest_delta_jump_without_diffs_reports_it() {
+        let mut app = app_for(b"same", b"same");
+        app.jump_to_largest_delta(24);
+        assert_eq!(app.cursor_pos, 0);
+        assert_eq!(
+            app.status.as_ref().map(|(message, _)| message.as_str()),
+            Some("No differences")
+        );
+    }
+
+    #[test]
+    fn section_edits_are_spliced_back_into_the_whole_file() {
+        let dir = std::env::temp_dir();
+        let base = dir.join(format!("dring-splice-{}", std::process::id()));
+        let out = dir.join(format!("dring-splice-{}.patched", std::process::id()));
+        std::fs::write(&base, b"head[sect]tail").unwrap();
+        let mut app = app_for(b"sect", b"sxcx");
+        app.file1_path = base.to_string_lossy().into_owned();
+        app.section = Some(Section {
+            name: ".data".to_string(),
+            file1_start: 5,
+            file2_start: 5,
+        });
+        app.start_edit();
+        app.submit_prompt("41", 24);
+        app.start_write();
+        app.submit_prompt(&out.to_string_lossy(), 24);
+        assert_eq!(std::fs::read(&out).unwrap(), b"head[sAct]tail");
+        std::fs::remove_file(base).unwrap();
+        std::fs::remove_file(out).unwrap();
+    }
+
+    #[test]
+    fn edits_to_a_zip_entry_are_not_written() {
+        let mut app = app_for(b"abc", b"abd");
+        app.zip_entry = Some("inner.bin".to_string());
+        app.start_edit();
+        app.submit_prompt("41", 24);
+        app.start_write();
+        assert!(app.prompt.is_none());
+        assert_eq!(
+            app.status.as_ref().map(|(message, _)| message.as_str()),
+            Some("Can't write edits back into a zip entry")
+        );
+    }
+
+    #[test]
+    fn swapping_twice_restores_the_files_pairs_and_edits() {
+        let mut app = app_for(b"abcdef", b"aXcYef");
+        app.file1_path = "old.bin".to_string();
+        app.file2_path = "new.bin".to_string();
+        app.move_cursor_right(24);
+        app.start_edit();
+        app.submit_prompt("41", 24);
+        let (pairs, edits, cursor) = (app.all_pairs.clone(), app.edits.clone(), app.cursor_pos);
+
+        app.swap_files(24);
+        assert_eq!(app.all_pairs, vec![(1, b'X', b'b'), (3, b'Y', b'd')]);
+        assert_eq!(&*app.file1_data, b"aXcYef");
+        assert_eq!(app.file1_path, "new.bin");
+        assert!(app.swapped);
+
+        app.swap_files(24);
+        assert_eq!(&*app.file1_data, b"abcdef");
+        assert_eq!(&*app.file2_data, b"aXcYef");
+        assert_eq!(
+            (app.file1_path.as_str(), app.file2_path.as_str()),
+            ("old.bin", "new.bin")
+        );
+        assert_eq!(app.all_pairs, pairs);
+        assert_eq!(app.pairs, pairs);
+        assert_eq!(app.edits, edits);
+        assert_eq!(app.cursor_pos, cursor);
+        assert!(!app.swapped);
+    }
+
+    #[test]
+    fn data_read_from_stdin_is_not_reloaded() {
+        let mut app = app_for(b"abc", b"abd");
+        app.file1_path = STDIN_PATH.to_string();
+        app.file2_path = "never-read".to_string();
+        app.reload(24);
+        assert_eq!(
+            app.status.as_ref().map(|(message, _)| message.as_str()),
+            Some("Can't reload data read from stdin")
+        );
+        assert_eq!(app.diffs.len(), 1);
+    }
+}
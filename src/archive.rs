@@ -0,0 +1,89 @@
+/// Extract the entry called `name` from two archives, each given with its
+/// path for error messages.
+pub fn extract_entries(
+    name: &str,
+    (path1, data1): (&str, &[u8]),
+    (path2, data2): (&str, &[u8]),
+) -> Result<(Vec<u8>, Vec<u8>), String> {
+    let entry1 = extract_entry(data1, name).map_err(|err| format!("{}: {}", path1, err))?;
+    let entry2 = extract_entry(data2, name).map_err(|err| format!("{}: {}", path2, err))?;
+    Ok((entry1, entry2))
+}
+
+/// Read the entry called `name` out of the ZIP archive in `data`.
+#[cfg(feature = "zip")]
+fn extract_entry(data: &[u8], name: &str) -> Result<Vec<u8>, String> {
+    use std::io::{Cursor, Read};
+
+    let mut archive = zip::ZipArchive::new(Cursor::new(data))
+        .map_err(|err| format!("not a readable ZIP archive: {}", err))?;
+    let mut entry = archive
+        .by_name(name)
+        .map_err(|_| format!("no entry named {} in the archive", name))?;
+    let mut contents = Vec::new();
+    entry
+        .read_to_end(&mut contents)
+        .map_err(|err| format!("could not extract {}: {}", name, err))?;
+    Ok(contents)
+}
+
+#[cfg(not(feature = "zip"))]
+fn extract_entry(_data: &[u8], _name: &str) -> Result<Vec<u8>, String> {
+    Err("--zip-entry requires dring to be built with the `zip` feature".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An in-memory ZIP archive holding `entries`, each a name and contents.
+    #[cfg(feature = "zip")]
+    fn zip_of(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        use std::io::{Cursor, Write};
+        use zip::{write::SimpleFileOptions, ZipWriter};
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in entries {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn extracts_the_named_entry_from_both_archives() {
+        let old = zip_of(&[("a.txt", b"unrelated"), ("lib/x.so", b"\x7fELF old")]);
+        let new = zip_of(&[("lib/x.so", b"\x7fELF new")]);
+        let (entry1, entry2) =
+            extract_entries("lib/x.so", ("old.zip", &old), ("new.zip", &new)).unwrap();
+        assert_eq!(entry1, b"\x7fELF old");
+        assert_eq!(entry2, b"\x7fELF new");
+    }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn a_missing_entry_names_the_archive() {
+        let old = zip_of(&[("a.txt", b"a")]);
+        let new = zip_of(&[("b.txt", b"b")]);
+        assert_eq!(
+            extract_entries("a.txt", ("old.zip", &old), ("new.zip", &new)).unwrap_err(),
+            "new.zip: no entry named a.txt in the archive"
+        );
+        assert!(
+            extract_entries("a.txt", ("old.zip", b"not a zip"), ("new.zip", &new))
+                .unwrap_err()
+                .starts_with("old.zip: not a readable ZIP archive")
+        );
+    }
+
+    #[cfg(not(feature = "zip"))]
+    #[test]
+    fn entries_need_the_feature() {
+        assert!(extract_entries("a", ("x", b""), ("y", b""))
+            .unwrap_err()
+            .contains("`zip` feature"));
+    }
+}
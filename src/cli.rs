@@ -0,0 +1,472 @@
+use std::{ffi::OsString, ops::Range};
+
+use clap::{error::ErrorKind, ArgGroup, CommandFactory, Parser};
+
+use crate::{
+    codepage::CodePage,
+    export::{DebuggerStyle, OffsetBase},
+    file::{TextFilter, STDIN_PATH},
+    input::parse_offset,
+    theme::Theme,
+    ui::PrintableSet,
+};
+
+/// When to style the output with colors.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ColorChoice {
+    /// Use colors if the terminal appears to support them.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Flags that pick what dring writes instead of starting the viewer.
+const OUTPUT_MODES: [&str; 11] = [
+    "json",
+    "expect",
+    "batch",
+    "print",
+    "no_tui",
+    "dump",
+    "offsets_only",
+    "patch",
+    "report",
+    "apply",
+    "diff_of_diffs",
+];
+
+/// Command-line options for a comparison run.
+#[derive(Debug, Parser)]
+#[command(
+    name = "dring",
+    version,
+    about,
+    override_usage = "dring [OPTIONS] <file1> <file2>
+       dring --apply <patch> <file1>
+       dring --diff-of-diffs <diff1.json> <diff2.json>
+       dring --manifest <pairs.txt> [--batch] [--fail-fast]",
+    after_help = "Either file may be `-` to read it from stdin.
+
+Exit status: 0 if the files are identical, 1 if they differ, 2 if they \
+can't be compared, e.g. a file can't be read, and 3 if --timeout runs out. \
+Output modes such as --json and --dump exit the same way; only one of them \
+can be given."
+)]
+// The output modes each produce the whole output and exit, so only one of
+// them can run
+#[command(group(ArgGroup::new("output").multiple(false).args(OUTPUT_MODES)))]
+pub struct Args {
+    /// The files to compare
+    #[arg(value_name = "FILE", num_args = 0..=2)]
+    files: Vec<String>,
+    #[arg(skip)]
+    pub file1: String,
+    #[arg(skip)]
+    pub file2: String,
+    /// Show the bytes of each row right-to-left
+    #[arg(long)]
+    pub reverse_bytes: bool,
+    /// Use colors: auto (default), always or never
+    #[arg(long, value_name = "WHEN", value_parser = parse_color)]
+    pub color: Option<ColorChoice>,
+    /// Color theme: dark (default), light or monochrome, which tells bytes
+    /// apart by text attributes as when colors are off
+    #[arg(long, value_name = "NAME", value_parser = parse_theme)]
+    pub theme: Option<Theme>,
+    /// Show N bytes per line, e.g. 16 to line up with xxd, instead of filling
+    /// the terminal; rows too wide for the terminal are cut off
+    #[arg(long, value_name = "N", value_parser = parse_width)]
+    pub width: Option<usize>,
+    /// Characters shown in the ASCII pane: whitespace (default) or graphic,
+    /// which also draws whitespace as `.`
+    #[arg(long, value_name = "SET", value_parser = parse_printable, default_value = "whitespace")]
+    pub printable: PrintableSet,
+    /// Keep tabs, newlines and other whitespace in bytes copied as ASCII
+    /// instead of replacing them with `.`
+    #[arg(long)]
+    pub copy_whitespace: bool,
+    /// Draw bytes 0x80-0xFF in the ASCII pane using a code page: ascii
+    /// (default), cp437, latin1 or windows-1252
+    #[arg(long, value_name = "CP", value_parser = parse_codepage, default_value = "ascii")]
+    pub codepage: CodePage,
+    /// Step through the changed regions, centering each for SECS seconds;
+    /// space pauses, any other key stops
+    #[arg(long, value_name = "SECS")]
+    pub replay: Option<u64>,
+    /// Save where the viewer leaves off, with its bookmarks and notes, to
+    /// FILE on quit, and pick up from there when started again with the
+    /// same files
+    #[arg(long, value_name = "FILE")]
+    pub session: Option<String>,
+    /// Milliseconds between the viewer's ticks, which update the timer,
+    /// expire status messages and advance --replay
+    #[arg(long, value_name = "MS", value_parser = parse_tick_rate, default_value = "1000")]
+    pub tick_rate: u64,
+    /// Show only changes where either byte, or the first file's byte with
+    /// `file1`, is printable ASCII; `p` toggles it
+    #[arg(long, value_name = "WHICH", value_parser = parse_text_filter)]
+    pub text_only: Option<TextFilter>,
+    /// Start at the end of the diff and follow new changes
+    #[arg(long)]
+    pub tail: bool,
+    /// Re-read and re-diff the files whenever either changes on disk,
+    /// keeping the cursor on the same offset where possible
+    #[arg(long)]
+    pub watch: bool,
+    /// Expression copied with `X`: assert (default), which checks the byte,
+    /// or write, which stores it
+    #[arg(long, value_name = "STYLE", value_parser = parse_debugger_style, default_value = "assert")]
+    pub debugger_style: DebuggerStyle,
+    /// Underline rows that end a region, where the offsets of the next row
+    /// jump ahead by more than a row
+    #[arg(long)]
+    pub separators: bool,
+    /// Show the elapsed session time in the info bar
+    #[arg(long)]
+    pub timer: bool,
+    /// Search only when the query is submitted with Enter, instead of as it
+    /// is typed
+    #[arg(long)]
+    pub no_incremental_search: bool,
+    /// Give up if comparing the files takes longer than SECS seconds, exiting
+    /// with status 3
+    #[arg(long, value_name = "SECS")]
+    pub timeout: Option<u64>,
+    /// Print the diff as JSON and exit
+    #[arg(long)]
+    pub json: bool,
+    /// With --json, write offsets as "0x.." hex strings instead of numbers
+    #[arg(long, requires = "json")]
+    pub hex_offsets: bool,
+    /// Check the diff against a recorded JSON diff
+    #[arg(long, value_name = "FILE")]
+    pub expect: Option<String>,
+    /// Print one `offset: old new` line per difference and exit with status 0
+    /// if the files match and 1 if they differ
+    #[arg(long)]
+    pub batch: bool,
+    /// With --batch, align the files by content so inserted and deleted bytes
+    /// don't shift everything after them, printing `offset1 offset2: old new`
+    /// lines with `--` for a missing byte
+    #[arg(long, requires = "batch", conflicts_with_all = ["context", "fold", "nibble"])]
+    pub align: bool,
+    /// With --batch, print hexdump blocks with N bytes of context
+    #[arg(long, value_name = "N", requires = "batch")]
+    pub context: Option<usize>,
+    /// With --batch, print a hexdump of the whole first file, folding runs of
+    /// more than N unchanged lines
+    #[arg(long, value_name = "N", requires = "batch", conflicts_with = "context")]
+    pub fold: Option<usize>,
+    /// Compare at nibble resolution: --batch prints one line per changed
+    /// nibble and the viewer highlights changed nibbles
+    #[arg(long)]
+    pub nibble: bool,
+    /// Print every changed line of the second file as a colored hexdump and
+    /// exit, without starting the viewer
+    #[arg(long)]
+    pub print: bool,
+    /// Print a plain hexdump of the differing bytes and exit with status 0 if
+    /// the files match and 1 if they differ
+    #[arg(long)]
+    pub no_tui: bool,
+    /// Print the changed bytes of the second file as an xxd hexdump, which
+    /// `xxd -r` can apply to a copy of the first file, and exit
+    #[arg(long)]
+    pub dump: bool,
+    /// Print only the differing offsets, one per line, and exit
+    #[arg(long)]
+    pub offsets_only: bool,
+    /// Compare only the named ELF/PE section, e.g. .text, with offsets
+    /// relative to it (needs the `sections` feature)
+    #[arg(long, value_name = "NAME")]
+    pub section: Option<String>,
+    /// Leave the bytes from START up to, not including, END out of the diff,
+    /// e.g. 0x1000-0x1040; can be repeated
+    #[arg(long, value_name = "START-END", value_parser = parse_range)]
+    pub ignore: Vec<Range<usize>>,
+    /// Compare the named entry of two ZIP archives instead of the raw archives
+    /// (needs the `zip` feature)
+    #[arg(long, value_name = "PATH")]
+    pub zip_entry: Option<String>,
+    /// Show offsets in decimal instead of hex
+    #[arg(long)]
+    decimal: bool,
+    #[arg(skip)]
+    pub offset_base: OffsetBase,
+    /// Write a binary patch that rebuilds file2 from file1
+    #[arg(long, value_name = "FILE")]
+    pub patch: Option<String>,
+    /// Write a markdown report of the diff regions and exit
+    #[arg(long, value_name = "FILE")]
+    pub report: Option<String>,
+    /// Compare every `fileA fileB` pair listed in FILE, printing a line per
+    /// pair, or the --batch diff of each; exits 0 if all match, 1 if any
+    /// differ and 2 if any can't be read
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with = "files",
+        conflicts_with_all = OUTPUT_MODES.iter().filter(|&&mode| mode != "batch")
+    )]
+    pub manifest: Option<String>,
+    /// With --manifest, stop at the first pair that can't be read
+    #[arg(long)]
+    pub fail_fast: bool,
+    /// Apply a patch to file1 and write the result to stdout
+    #[arg(long, value_name = "PATCH")]
+    pub apply: Option<String>,
+    /// Compare two JSON diffs of the same baseline and print where their
+    /// changes diverge
+    #[arg(long)]
+    pub diff_of_diffs: bool,
+}
+
+/// Parse the command-line arguments, including the program name.
+pub fn parse_args<I, T>(args: I) -> Result<Args, clap::Error>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let mut parsed = Args::try_parse_from(args)?;
+    if parsed.decimal {
+        parsed.offset_base = OffsetBase::Decimal;
+    }
+    parsed.replay = parsed.replay.map(|secs| secs.max(1));
+    if parsed.manifest.is_some() {
+        return Ok(parsed);
+    }
+    // Checked by hand: clap drops the requirement when files are given,
+    // since they conflict with --manifest
+    if parsed.fail_fast {
+        return Err(usage_error(
+            ErrorKind::MissingRequiredArgument,
+            "--fail-fast requires --manifest",
+        ));
+    }
+
+    let files = std::mem::take(&mut parsed.files);
+    if parsed.apply.is_some() {
+        let [file1]: [String; 1] = files
+            .try_into()
+            .map_err(|_| wrong_files("--apply expects exactly one file"))?;
+        parsed.file1 = file1;
+        return Ok(parsed);
+    }
+
+    let [file1, file2]: [String; 2] = files
+        .try_into()
+        .map_err(|_| wrong_files("expected exactly two files"))?;
+    if file1 == STDIN_PATH && file2 == STDIN_PATH {
+        return Err(wrong_files("only one file can be read from stdin"));
+    }
+    parsed.file1 = file1;
+    parsed.file2 = file2;
+    Ok(parsed)
+}
+
+/// A usage error found after parsing, formatted like clap's own.
+fn usage_error(kind: ErrorKind, message: &str) -> clap::Error {
+    Args::command().error(kind, message)
+}
+
+/// A usage error about the number of file arguments.
+fn wrong_files(message: &str) -> clap::Error {
+    usage_error(ErrorKind::WrongNumberOfValues, message)
+}
+
+/// Parse a manifest of file pairs to compare, one `fileA fileB` pair per
+/// line. Blank lines and lines starting with `#` are skipped.
+pub fn parse_manifest(text: &str) -> Result<Vec<(String, String)>, String> {
+    text.lines()
+        .enumerate()
+        .map(|(idx, line)| (idx + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields[..] {
+                [file1, file2] => Ok((file1.to_string(), file2.to_string())),
+                _ => Err(format!(
+                    "line {}: expected two files, got {:?}",
+                    number, line
+                )),
+            }
+        })
+        .collect()
+}
+
+/// Parse a code page name for `--codepage`.
+fn parse_codepage(text: &str) -> Result<CodePage, String> {
+    match text.to_lowercase().as_str() {
+        "ascii" => Ok(CodePage::Ascii),
+        "cp437" => Ok(CodePage::Cp437),
+        "latin1" | "latin-1" => Ok(CodePage::Latin1),
+        "windows-1252" | "cp1252" => Ok(CodePage::Windows1252),
+        _ => Err("expects ascii, cp437, latin1 or windows-1252".to_string()),
+    }
+}
+
+/// Parse the `--text-only` filter: `either` or `file1`.
+fn parse_text_filter(text: &str) -> Result<TextFilter, String> {
+    match text {
+        "either" => Ok(TextFilter::Either),
+        "file1" => Ok(TextFilter::File1),
+        _ => Err("expects either or file1".to_string()),
+    }
+}
+
+/// Parse the `--debugger-style`: `assert` or `write`.
+fn parse_debugger_style(text: &str) -> Result<DebuggerStyle, String> {
+    match text {
+        "assert" => Ok(DebuggerStyle::Assert),
+        "write" => Ok(DebuggerStyle::Write),
+        _ => Err("expects assert or write".to_string()),
+    }
+}
+
+/// Parse the `--printable` set: `whitespace` or `graphic`.
+fn parse_printable(text: &str) -> Result<PrintableSet, String> {
+    match text {
+        "whitespace" => Ok(PrintableSet::Whitespace),
+        "graphic" => Ok(PrintableSet::Graphic),
+        _ => Err("expects whitespace or graphic".to_string()),
+    }
+}
+
+/// Parse a color setting: `auto`, `always` or `never`.
+pub fn parse_color(text: &str) -> Result<ColorChoice, String> {
+    match text {
+        "auto" => Ok(ColorChoice::Auto),
+        "always" => Ok(ColorChoice::Always),
+        "never" => Ok(ColorChoice::Never),
+        other => Err(format!("expects auto, always or never, got {}", other)),
+    }
+}
+
+/// Parse a color theme: `dark`, `light` or `monochrome`.
+pub fn parse_theme(text: &str) -> Result<Theme, String> {
+    match text {
+        "dark" => Ok(Theme::DARK),
+        "light" => Ok(Theme::LIGHT),
+        "monochrome" => Ok(Theme::MONOCHROME),
+        other => Err(format!("expects dark, light or monochrome, got {}", other)),
+    }
+}
+
+/// Parse an `--ignore` offset range, `START-END` with END excluded. Offsets
+/// are decimal, or hex with a `0x` prefix.
+fn parse_range(text: &str) -> Result<Range<usize>, String> {
+    let offset = |text| parse_offset(text, OffsetBase::Decimal);
+    let (start, end) = text
+        .split_once('-')
+        .and_then(|(start, end)| Some((offset(start)?, offset(end)?)))
+        .ok_or_else(|| format!("expects START-END, e.g. 0x1000-0x1040, got {}", text))?;
+    if start >= end {
+        return Err(format!("expects START before END, got {}", text));
+    }
+    Ok(start..end)
+}
+
+/// Parse a `--tick-rate`, a positive number of milliseconds.
+fn parse_tick_rate(text: &str) -> Result<u64, String> {
+    match text.parse() {
+        Ok(ms) if ms > 0 => Ok(ms),
+        _ => Err(format!(
+            "expects a positive number of milliseconds, got {}",
+            text
+        )),
+    }
+}
+
+/// Parse a bytes-per-line setting, a positive number.
+pub fn parse_width(text: &str) -> Result<usize, String> {
+    match text.parse() {
+        Ok(width) if width > 0 => Ok(width),
+        _ => Err(format!("expects a positive number, got {}", text)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parse `args` after the program name.
+    fn parse(args: &[&str]) -> Result<Args, clap::Error> {
+        parse_args(std::iter::once("dring").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn output_modes_conflict() {
+        for args in [
+            &["--json", "--print", "a", "b"][..],
+            &["--batch", "--dump", "a", "b"],
+            &["--offsets-only", "--no-tui", "a", "b"],
+            &["--patch", "p", "--report", "r", "a", "b"],
+            &["--expect", "e.json", "--json", "a", "b"],
+            &["--apply", "p", "--json", "a"],
+            &["--manifest", "m", "--json"],
+        ] {
+            let err = parse(args).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::ArgumentConflict, "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn one_output_mode_with_its_options_parses() {
+        assert!(parse(&["--json", "--hex-offsets", "a", "b"]).unwrap().json);
+        assert!(parse(&["--batch", "--nibble", "a", "b"]).unwrap().batch);
+        assert!(parse(&["--manifest", "m", "--batch"]).unwrap().batch);
+        let args = parse(&["--apply", "p", "a"]).unwrap();
+        assert_eq!(
+            (args.apply.as_deref(), args.file1.as_str()),
+            (Some("p"), "a")
+        );
+    }
+
+    #[test]
+    fn decimal_switches_the_offset_base() {
+        assert_eq!(parse(&["a", "b"]).unwrap().offset_base, OffsetBase::Hex);
+        let args = parse(&["--offsets-only", "--decimal", "a", "b"]).unwrap();
+        assert!(args.offsets_only);
+        assert_eq!(args.offset_base, OffsetBase::Decimal);
+    }
+
+    #[test]
+    fn debugger_style_defaults_to_assert() {
+        let style = |args: &[&str]| parse(args).map(|args| args.debugger_style);
+        assert_eq!(style(&["a", "b"]).unwrap(), DebuggerStyle::Assert);
+        assert_eq!(
+            style(&["--debugger-style", "write", "a", "b"]).unwrap(),
+            DebuggerStyle::Write
+        );
+        assert!(style(&["--debugger-style", "read", "a", "b"]).is_err());
+    }
+
+    #[test]
+    fn manifest_pairs_skip_blanks_and_comments() {
+        let text = "# nightly builds\n\n  old.bin   new.bin  \na b\n";
+        assert_eq!(
+            parse_manifest(text).unwrap(),
+            vec![
+                ("old.bin".to_string(), "new.bin".to_string()),
+                ("a".to_string(), "b".to_string()),
+            ]
+        );
+        assert_eq!(
+            parse_manifest("a b\n\nonly-one\n").unwrap_err(),
+            "line 3: expected two files, got \"only-one\""
+        );
+        assert!(parse_manifest("a b c").is_err());
+    }
+
+    #[test]
+    fn only_one_file_reads_stdin() {
+        let args = parse(&["-", "b"]).unwrap();
+        assert_eq!((args.file1.as_str(), args.file2.as_str()), (STDIN_PATH, "b"));
+        assert_eq!(parse(&["a", "-"]).unwrap().file2, STDIN_PATH);
+
+        let err = parse(&["-", "-"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::WrongNumberOfValues);
+        assert!(err.to_string().contains("only one file can be read from stdin"));
+    }
+}
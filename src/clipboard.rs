@@ -0,0 +1,411 @@
+use std::{
+    fs::OpenOptions,
+    io::{self, Write},
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{
+    export::{format_offset, OffsetBase},
+    file::group_regions,
+};
+
+/// Base64 alphabet used by the OSC 52 clipboard sequence.
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encode `data` as padded base64.
+fn base64(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// A range of diff indices selected in visual mode, between where the
+/// selection was started and the cursor, inclusive.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Selection {
+    pub anchor: usize,
+    pub head: usize,
+}
+
+impl Selection {
+    /// Constructs a selection of the single diff index `pos`.
+    pub fn new(pos: usize) -> Self {
+        Self {
+            anchor: pos,
+            head: pos,
+        }
+    }
+
+    /// First selected diff index.
+    pub fn start(&self) -> usize {
+        self.anchor.min(self.head)
+    }
+
+    /// Last selected diff index.
+    pub fn end(&self) -> usize {
+        self.anchor.max(self.head)
+    }
+
+    /// Whether the diff index `pos` is selected.
+    pub fn contains(&self, pos: usize) -> bool {
+        (self.start()..=self.end()).contains(&pos)
+    }
+}
+
+/// Format bytes as space-separated hex pairs, e.g. `de ad be ef`.
+pub fn format_hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Format bytes as ASCII, with `.` for anything but printable characters and
+/// the space. Tabs, newlines and other whitespace become `.` too, so the text
+/// stays on one line, unless `keep_whitespace` is set.
+pub fn format_ascii(bytes: &[u8], keep_whitespace: bool) -> String {
+    bytes
+        .iter()
+        .map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' || (keep_whitespace && b.is_ascii_whitespace()) {
+                b as char
+            } else {
+                '.'
+            }
+        })
+        .collect()
+}
+
+/// Format the file offsets of the selected diffs in `base`, as in the
+/// gutter, collapsing runs of adjacent offsets into ranges, e.g.
+/// `00001a4f-00001a53, 00001a60`.
+pub fn format_offsets(diffs: &[(usize, u8)], selection: Selection, base: OffsetBase) -> String {
+    let selected = diffs
+        .get(selection.start()..selection.end() + 1)
+        .unwrap_or_default();
+    group_regions(selected)
+        .into_iter()
+        .map(|region| {
+            let first = selected[region.start].0;
+            let last = selected[region.end - 1].0;
+            if first == last {
+                format_offset(first, base)
+            } else {
+                format!(
+                    "{}-{}",
+                    format_offset(first, base),
+                    format_offset(last, base)
+                )
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Bytes per line of a copied array literal before it wraps.
+const ARRAY_BYTES_PER_LINE: usize = 12;
+
+/// Format bytes as a C array initializer, e.g. `{ 0xff, 0x0a, 0x00 }`.
+pub fn format_c_array(bytes: &[u8]) -> String {
+    format_array(bytes, ('{', '}'), " ")
+}
+
+/// Format bytes as a Rust array literal, e.g. `[0xff, 0x0a, 0x00]`.
+pub fn format_rust_array(bytes: &[u8]) -> String {
+    format_array(bytes, ('[', ']'), "")
+}
+
+/// Format bytes as `0x..` literals between `brackets`, on one line padded by
+/// `pad`, or past [`ARRAY_BYTES_PER_LINE`] bytes, one indented line per row.
+fn format_array(bytes: &[u8], (open, close): (char, char), pad: &str) -> String {
+    let literals = |bytes: &[u8]| {
+        bytes
+            .iter()
+            .map(|b| format!("0x{:02x}", b))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    if bytes.is_empty() {
+        return format!("{}{}", open, close);
+    }
+    if bytes.len() <= ARRAY_BYTES_PER_LINE {
+        return format!("{}{}{}{}{}", open, pad, literals(bytes), pad, close);
+    }
+    let rows: String = bytes
+        .chunks(ARRAY_BYTES_PER_LINE)
+        .map(|row| format!("    {},\n", literals(row)))
+        .collect();
+    format!("{}\n{}{}", open, rows, close)
+}
+
+/// How `y` formats the copied bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CopyFormat {
+    /// Space-separated hex pairs, e.g. `de ad be ef`.
+    #[default]
+    Hex,
+    /// The bytes as text, see [`format_ascii`].
+    Ascii,
+    /// A C array initializer, see [`format_c_array`].
+    CArray,
+    /// A Rust array literal, see [`format_rust_array`].
+    RustArray,
+}
+
+impl CopyFormat {
+    /// The format after this one, wrapping around.
+    pub fn next(self) -> Self {
+        match self {
+            Self::Hex => Self::Ascii,
+            Self::Ascii => Self::CArray,
+            Self::CArray => Self::RustArray,
+            Self::RustArray => Self::Hex,
+        }
+    }
+
+    /// Name shown in status messages.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Hex => "hex",
+            Self::Ascii => "ASCII",
+            Self::CArray => "C array",
+            Self::RustArray => "Rust array",
+        }
+    }
+
+    /// Format `bytes` for copying, keeping whitespace in ASCII text if
+    /// `keep_whitespace` is set.
+    pub fn format(self, bytes: &[u8], keep_whitespace: bool) -> String {
+        match self {
+            Self::Hex => format_hex(bytes),
+            Self::Ascii => format_ascii(bytes, keep_whitespace),
+            Self::CArray => format_c_array(bytes),
+            Self::RustArray => format_rust_array(bytes),
+        }
+    }
+}
+
+/// Longest encoded payload sent with OSC 52. Terminals drop or truncate
+/// longer sequences; xterm's default limit is about this size.
+const OSC52_MAX_LEN: usize = 100_000;
+
+/// Whether `text` is short enough to copy with OSC 52.
+pub fn osc52_fits(text: &str) -> bool {
+    text.len().div_ceil(3) * 4 <= OSC52_MAX_LEN
+}
+
+/// Files written by [`write_overflow`] so far, to keep their names unique.
+static OVERFLOW_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// Write `text`, too long to copy with OSC 52, to a new file in the temp
+/// directory and return its path. The file is always created fresh, never
+/// opened if it already exists, so another user can't plant a file or
+/// symlink at the name beforehand.
+pub fn write_overflow(text: &str) -> io::Result<PathBuf> {
+    loop {
+        let path = std::env::temp_dir().join(format!(
+            "dead-ringer-diff-{}-{}.txt",
+            std::process::id(),
+            OVERFLOW_FILES.fetch_add(1, Ordering::Relaxed)
+        ));
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => return file.write_all(text.as_bytes()).map(|()| path),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Copy `text` to the system clipboard with the OSC 52 escape sequence, which
+/// the terminal forwards to the clipboard, even over SSH.
+pub fn osc52_copy(text: &str) -> io::Result<()> {
+    let mut out = io::stderr();
+    write!(out, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    out.flush()
+}
+
+/// How copied text reaches the clipboard.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClipboardBackend {
+    /// The OSC 52 escape sequence, handled by the terminal.
+    Osc52,
+    /// The operating system's clipboard, falling back to OSC 52 if it can't
+    /// be reached, e.g. over SSH without a display.
+    Native,
+}
+
+impl Default for ClipboardBackend {
+    /// The native clipboard when built with the `native-clipboard` feature,
+    /// since many terminals silently ignore OSC 52.
+    fn default() -> Self {
+        if cfg!(feature = "native-clipboard") {
+            Self::Native
+        } else {
+            Self::Osc52
+        }
+    }
+}
+
+/// Copy `text` to the clipboard through `backend`.
+pub fn copy_text(text: &str, backend: ClipboardBackend) -> io::Result<()> {
+    match backend {
+        ClipboardBackend::Osc52 => osc52_copy(text),
+        ClipboardBackend::Native => native_copy(text).or_else(|_| osc52_copy(text)),
+    }
+}
+
+/// Copy `text` to the system clipboard by piping it to the platform's
+/// clipboard command, trying each candidate until one succeeds.
+#[cfg(feature = "native-clipboard")]
+fn native_copy(text: &str) -> io::Result<()> {
+    use std::process::{Command, Stdio};
+
+    let candidates: &[&[&str]] = if cfg!(target_os = "macos") {
+        &[&["pbcopy"]]
+    } else if cfg!(windows) {
+        &[&["clip"]]
+    } else {
+        &[
+            &["wl-copy"],
+            &["xclip", "-selection", "clipboard"],
+            &["xsel", "--clipboard", "--input"],
+        ]
+    };
+    let mut last_err = io::Error::new(io::ErrorKind::NotFound, "no clipboard command");
+    for command in candidates {
+        let result = Command::new(command[0])
+            .args(&command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .and_then(|mut child| {
+                // Close stdin once written so the command sees the end
+                child
+                    .stdin
+                    .take()
+                    .expect("stdin is piped")
+                    .write_all(text.as_bytes())?;
+                child.wait()
+            });
+        match result {
+            Ok(status) if status.success() => return Ok(()),
+            Ok(status) => {
+                last_err = io::Error::other(format!("{} exited with {}", command[0], status))
+            }
+            Err(err) => last_err = err,
+        }
+    }
+    Err(last_err)
+}
+
+#[cfg(not(feature = "native-clipboard"))]
+fn native_copy(_text: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "built without the native-clipboard feature",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_pads_partial_groups() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(
+            base64(b"*(unsigned char*)0x10"),
+            "Kih1bnNpZ25lZCBjaGFyKikweDEw"
+        );
+        assert_eq!(base64(&[0xff, 0x00, 0xfe]), "/wD+");
+    }
+
+    #[test]
+    fn overflow_files_are_new_and_unique() {
+        let first = write_overflow("first").unwrap();
+        let second = write_overflow("second").unwrap();
+        assert_ne!(first, second);
+        assert_eq!(std::fs::read_to_string(&first).unwrap(), "first");
+        assert_eq!(std::fs::read_to_string(&second).unwrap(), "second");
+        std::fs::remove_file(first).unwrap();
+        std::fs::remove_file(second).unwrap();
+    }
+
+    #[test]
+    fn offsets_follow_the_offset_base() {
+        let diffs = [(0x1a4f, 0), (0x1a50, 0), (0x1a51, 0), (0x1a60, 0)];
+        let all = Selection { anchor: 0, head: 3 };
+        assert_eq!(
+            format_offsets(&diffs, all, OffsetBase::Hex),
+            "00001a4f-00001a51, 00001a60"
+        );
+        assert_eq!(
+            format_offsets(&diffs, all, OffsetBase::Decimal),
+            "6735-6737, 6752"
+        );
+        // Only the selection, whichever way it was made
+        let back = Selection { anchor: 3, head: 2 };
+        assert_eq!(
+            format_offsets(&diffs, back, OffsetBase::Decimal),
+            "6737, 6752"
+        );
+    }
+
+    #[test]
+    fn ascii_replaces_whitespace_by_default() {
+        assert_eq!(format_ascii(b"a\tb\nc d\r\x00", false), "a.b.c d..");
+        assert_eq!(CopyFormat::Ascii.format(b"x\ty", false), "x.y");
+    }
+
+    #[test]
+    fn ascii_can_keep_whitespace() {
+        assert_eq!(format_ascii(b"a\tb\nc d\r\x00", true), "a\tb\nc d\r.");
+        assert_eq!(CopyFormat::Ascii.format(b"x\ty", true), "x\ty");
+        // Other formats don't depend on it
+        assert_eq!(CopyFormat::Hex.format(b"\t", true), "09");
+    }
+
+    #[test]
+    fn arrays_of_no_bytes_are_empty() {
+        assert_eq!(format_c_array(&[]), "{}");
+        assert_eq!(format_rust_array(&[]), "[]");
+    }
+
+    #[test]
+    fn arrays_wrap_past_twelve_bytes_per_line() {
+        let twelve: Vec<u8> = (0..12).collect();
+        assert_eq!(
+            format_rust_array(&twelve),
+            "[0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b]"
+        );
+        assert_eq!(format_c_array(&[0xff, 0x0a]), "{ 0xff, 0x0a }");
+
+        let thirteen: Vec<u8> = (0..13).collect();
+        assert_eq!(
+            format_c_array(&thirteen),
+            "{\n    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b,\n    0x0c,\n}"
+        );
+        assert_eq!(
+            format_rust_array(&thirteen),
+            "[\n    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b,\n    0x0c,\n]"
+        );
+    }
+}
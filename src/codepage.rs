@@ -0,0 +1,146 @@
+/// Code page used to draw the high bytes (0x80–0xFF) in the ASCII pane.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CodePage {
+    /// Plain ASCII: high bytes are drawn as `.`.
+    #[default]
+    Ascii,
+    /// IBM PC / DOS code page 437.
+    Cp437,
+    /// ISO 8859-1.
+    Latin1,
+    /// Windows-1252, Latin-1 with printable characters in 0x80–0x9F.
+    Windows1252,
+}
+
+/// CP437 glyphs for 0x80–0xFF.
+const CP437_HIGH: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å', //
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ', //
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»', //
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐', //
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧', //
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀', //
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩', //
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+/// Windows-1252 glyphs for 0x80–0x9F; `None` where the code page leaves the
+/// byte undefined.
+const WINDOWS_1252_C1: [Option<char>; 32] = [
+    Some('€'),
+    None,
+    Some('‚'),
+    Some('ƒ'),
+    Some('„'),
+    Some('…'),
+    Some('†'),
+    Some('‡'),
+    Some('ˆ'),
+    Some('‰'),
+    Some('Š'),
+    Some('‹'),
+    Some('Œ'),
+    None,
+    Some('Ž'),
+    None,
+    None,
+    Some('‘'),
+    Some('’'),
+    Some('“'),
+    Some('”'),
+    Some('•'),
+    Some('–'),
+    Some('—'),
+    Some('˜'),
+    Some('™'),
+    Some('š'),
+    Some('›'),
+    Some('œ'),
+    None,
+    Some('ž'),
+    Some('Ÿ'),
+];
+
+impl CodePage {
+    /// Glyph for a high byte under this code page, or `None` for bytes below
+    /// 0x80 and bytes the code page has no printable character for.
+    pub fn glyph(self, byte: u8) -> Option<char> {
+        if byte < 0x80 {
+            return None;
+        }
+        match self {
+            CodePage::Ascii => None,
+            CodePage::Cp437 => Some(CP437_HIGH[(byte - 0x80) as usize]),
+            CodePage::Latin1 if byte < 0xa0 => None,
+            CodePage::Latin1 => Some(byte as char),
+            CodePage::Windows1252 if byte < 0xa0 => WINDOWS_1252_C1[(byte - 0x80) as usize],
+            CodePage::Windows1252 => Some(byte as char),
+        }
+    }
+}
+
+/// Decode the UTF-8 sequence covering `data[index]`, returning the index of
+/// its lead byte and the character. A byte that isn't part of a valid
+/// sequence, including one cut off by the end of `data`, decodes on its own
+/// to U+FFFD.
+pub fn decode_utf8_at(data: &[u8], index: usize) -> (usize, char) {
+    let invalid = (index, char::REPLACEMENT_CHARACTER);
+    // A sequence is at most 4 bytes, so its lead is at most 3 bytes back
+    let Some(start) = (index.saturating_sub(3)..=index)
+        .rev()
+        .find(|&i| data[i] & 0xc0 != 0x80)
+    else {
+        return invalid;
+    };
+    let len = match data[start] {
+        0x00..=0x7f => 1,
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => return invalid,
+    };
+    if start + len <= index {
+        return invalid;
+    }
+    match data
+        .get(start..start + len)
+        .and_then(|bytes| std::str::from_utf8(bytes).ok())
+    {
+        Some(text) => (start, text.chars().next().unwrap_or(invalid.1)),
+        None => invalid,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn high_bytes_map_through_each_table() {
+        assert_eq!(CodePage::Cp437.glyph(0x80), Some('Ç'));
+        assert_eq!(CodePage::Cp437.glyph(0xb0), Some('░'));
+        assert_eq!(CodePage::Cp437.glyph(0xdb), Some('█'));
+        assert_eq!(CodePage::Cp437.glyph(0xe1), Some('ß'));
+        assert_eq!(CodePage::Latin1.glyph(0xe9), Some('é'));
+        assert_eq!(CodePage::Latin1.glyph(0xff), Some('ÿ'));
+        assert_eq!(CodePage::Windows1252.glyph(0x80), Some('€'));
+        assert_eq!(CodePage::Windows1252.glyph(0x9f), Some('Ÿ'));
+        assert_eq!(CodePage::Windows1252.glyph(0xc1), Some('Á'));
+    }
+
+    #[test]
+    fn unprintable_bytes_have_no_glyph() {
+        // Drawn as the placeholder by the ASCII pane
+        assert_eq!(CodePage::Ascii.glyph(0xc1), None);
+        assert_eq!(CodePage::Latin1.glyph(0x85), None);
+        assert_eq!(CodePage::Windows1252.glyph(0x81), None);
+        for page in [
+            CodePage::Ascii,
+            CodePage::Cp437,
+            CodePage::Latin1,
+            CodePage::Windows1252,
+        ] {
+            assert_eq!(page.glyph(b'A'), None);
+        }
+    }
+}
@@ -3,12 +3,15 @@ use std::sync::mpsc;
 use std::thread;
 use std::time::{Duration, Instant};
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 pub enum Event {
     Tick,
     Key(KeyEvent),
     Mouse(MouseEvent),
     Resize(u16, u16),
+    Paste(String),
+    /// A watched file changed on disk.
+    FilesChanged,
 }
 
 #[allow(dead_code)]
@@ -40,7 +43,7 @@ impl EventHandler {
                             CEvent::Resize(w, h) => sender.send(Event::Resize(w, h)),
                             CEvent::FocusGained => Ok(()),
                             CEvent::FocusLost => Ok(()),
-                            CEvent::Paste(_) => unimplemented!(),
+                            CEvent::Paste(text) => sender.send(Event::Paste(text)),
                         }
                         .expect("Failed to send terminal event")
                     }
@@ -59,6 +62,11 @@ impl EventHandler {
         }
     }
 
+    /// A sender for feeding events from other threads into the loop.
+    pub fn sender(&self) -> mpsc::Sender<Event> {
+        self.sender.clone()
+    }
+
     /// Receive the next event from the handler thread.
     pub fn next(&self) -> Result<Event, mpsc::RecvError> {
         self.receiver.recv()
@@ -0,0 +1,962 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{self, Write},
+    iter::Peekable,
+    ops::Range,
+    str::Chars,
+};
+
+use crate::file::{group_regions, AlignedChange, Change};
+
+/// Bytes shown per line in the hexdump-style batch output.
+const BATCH_BYTES_PER_LINE: usize = 16;
+
+/// Number base used when printing file offsets.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OffsetBase {
+    #[default]
+    Hex,
+    Decimal,
+}
+
+/// Format a file offset in the given base.
+pub fn format_offset(offset: usize, base: OffsetBase) -> String {
+    match base {
+        OffsetBase::Hex => format!("{:08x}", offset),
+        OffsetBase::Decimal => offset.to_string(),
+    }
+}
+
+/// Format the signed distance from `anchor` to `offset`, e.g. `+0x20`, in
+/// the given base.
+pub fn format_relative(offset: usize, anchor: usize, base: OffsetBase) -> String {
+    let sign = if offset < anchor { '-' } else { '+' };
+    let distance = offset.abs_diff(anchor);
+    match base {
+        OffsetBase::Hex => format!("{}{:#x}", sign, distance),
+        OffsetBase::Decimal => format!("{}{}", sign, distance),
+    }
+}
+
+/// Which debugger expression to produce for a byte.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DebuggerStyle {
+    /// Check that memory holds the byte: `*(unsigned char*)0x1040 == 0xff`.
+    #[default]
+    Assert,
+    /// Write the byte to memory: `*(unsigned char*)0x1040 = 0xff`.
+    Write,
+}
+
+/// Format a gdb/lldb expression reading or writing `byte` at `offset`. The
+/// address follows the offset base.
+pub fn format_debugger_expr(
+    offset: usize,
+    byte: u8,
+    style: DebuggerStyle,
+    base: OffsetBase,
+) -> String {
+    let address = match base {
+        OffsetBase::Hex => format!("{:#x}", offset),
+        OffsetBase::Decimal => offset.to_string(),
+    };
+    let operator = match style {
+        DebuggerStyle::Assert => "==",
+        DebuggerStyle::Write => "=",
+    };
+    format!("*(unsigned char*){} {} {:#04x}", address, operator, byte)
+}
+
+/// Write the differing byte pairs to `out` as they come, as a JSON array of
+/// `{ "offset": .., "old": .., "new": .. }` objects. Offsets are decimal
+/// numbers, or `"0x.."` strings with `hex_offsets`.
+pub fn write_json<W: Write>(
+    out: &mut W,
+    pairs: impl IntoIterator<Item = (usize, u8, u8)>,
+    hex_offsets: bool,
+) -> io::Result<()> {
+    let mut separator = "[\n";
+    for (offset, old, new) in pairs {
+        let offset = if hex_offsets {
+            format!("\"{:#x}\"", offset)
+        } else {
+            offset.to_string()
+        };
+        write!(
+            out,
+            "{}  {{ \"offset\": {}, \"old\": {}, \"new\": {} }}",
+            separator, offset, old, new
+        )?;
+        separator = ",\n";
+    }
+    match separator {
+        "[\n" => writeln!(out, "[]"),
+        _ => writeln!(out, "\n]"),
+    }
+}
+
+/// Parse a JSON diff previously written by [`write_json`].
+pub fn parse_json(input: &str) -> Result<Vec<(usize, u8, u8)>, String> {
+    let mut parser = JsonParser {
+        chars: input.chars().peekable(),
+    };
+    let pairs = parser.parse_array()?;
+    parser.skip_whitespace();
+    if parser.chars.peek().is_some() {
+        return Err("unexpected trailing characters after the diff array".to_string());
+    }
+    Ok(pairs)
+}
+
+/// A minimal parser for the flat JSON layout produced by [`write_json`].
+struct JsonParser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl JsonParser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.chars.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(format!("expected '{}' but found '{}'", expected, c)),
+            None => Err(format!("expected '{}' but reached the end", expected)),
+        }
+    }
+
+    /// Consume `separator` if it comes next, otherwise `close`. Returns whether
+    /// another element follows.
+    fn next_element(&mut self, separator: char, close: char) -> Result<bool, String> {
+        self.skip_whitespace();
+        match self.chars.next() {
+            Some(c) if c == separator => Ok(true),
+            Some(c) if c == close => Ok(false),
+            Some(c) => Err(format!(
+                "expected '{}' or '{}' but found '{}'",
+                separator, close, c
+            )),
+            None => Err(format!("expected '{}' but reached the end", close)),
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Vec<(usize, u8, u8)>, String> {
+        self.expect('[')?;
+        self.skip_whitespace();
+        let mut pairs = Vec::new();
+        if self.chars.next_if_eq(&']').is_some() {
+            return Ok(pairs);
+        }
+        loop {
+            pairs.push(self.parse_entry()?);
+            if !self.next_element(',', ']')? {
+                return Ok(pairs);
+            }
+        }
+    }
+
+    fn parse_entry(&mut self) -> Result<(usize, u8, u8), String> {
+        self.expect('{')?;
+        let mut fields = BTreeMap::new();
+        loop {
+            let key = self.parse_string()?;
+            self.expect(':')?;
+            self.skip_whitespace();
+            let value = if self.chars.peek() == Some(&'"') {
+                self.parse_hex_string()?
+            } else {
+                self.parse_number()?
+            };
+            fields.insert(key, value);
+            if !self.next_element(',', '}')? {
+                break;
+            }
+        }
+
+        let field = |name: &str| {
+            fields
+                .get(name)
+                .copied()
+                .ok_or_else(|| format!("entry is missing \"{}\"", name))
+        };
+        let byte = |name: &str| {
+            field(name).and_then(|v| {
+                u8::try_from(v).map_err(|_| format!("\"{}\" value {} is not a byte", name, v))
+            })
+        };
+        Ok((field("offset")?, byte("old")?, byte("new")?))
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut value = String::new();
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(value),
+                Some(c) => value.push(c),
+                None => return Err("unterminated string".to_string()),
+            }
+        }
+    }
+
+    /// Parse a number written as a `"0x.."` string, as hex offsets are.
+    fn parse_hex_string(&mut self) -> Result<usize, String> {
+        let value = self.parse_string()?;
+        value
+            .strip_prefix("0x")
+            .and_then(|digits| usize::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| format!("expected a \"0x\" hex number but found \"{}\"", value))
+    }
+
+    fn parse_number(&mut self) -> Result<usize, String> {
+        self.skip_whitespace();
+        let mut digits = String::new();
+        while let Some(c) = self.chars.next_if(|c| c.is_ascii_digit()) {
+            digits.push(c);
+        }
+        digits
+            .parse()
+            .map_err(|_| "expected a non-negative integer".to_string())
+    }
+}
+
+/// A difference between an expected diff and the one actually produced.
+#[derive(Debug, PartialEq)]
+pub enum DiffMismatch {
+    /// The offset only differs in the expected diff.
+    Missing(usize, u8, u8),
+    /// The offset only differs in the actual diff.
+    Unexpected(usize, u8, u8),
+    /// Both diffs report the offset, but with different bytes.
+    Changed {
+        offset: usize,
+        expected: (u8, u8),
+        actual: (u8, u8),
+    },
+}
+
+/// Compare two diffs offset by offset and report every disagreement, in
+/// offset order.
+pub fn compare_diffs(
+    expected: &[(usize, u8, u8)],
+    actual: &[(usize, u8, u8)],
+) -> Vec<DiffMismatch> {
+    let expected: BTreeMap<usize, (u8, u8)> = expected
+        .iter()
+        .map(|&(offset, old, new)| (offset, (old, new)))
+        .collect();
+    let actual: BTreeMap<usize, (u8, u8)> = actual
+        .iter()
+        .map(|&(offset, old, new)| (offset, (old, new)))
+        .collect();
+
+    let mut offsets: Vec<usize> = expected.keys().chain(actual.keys()).copied().collect();
+    offsets.sort_unstable();
+    offsets.dedup();
+
+    offsets
+        .into_iter()
+        .filter_map(
+            |offset| match (expected.get(&offset), actual.get(&offset)) {
+                (Some(&(old, new)), None) => Some(DiffMismatch::Missing(offset, old, new)),
+                (None, Some(&(old, new))) => Some(DiffMismatch::Unexpected(offset, old, new)),
+                (Some(&e), Some(&a)) if e != a => Some(DiffMismatch::Changed {
+                    offset,
+                    expected: e,
+                    actual: a,
+                }),
+                _ => None,
+            },
+        )
+        .collect()
+}
+
+impl std::fmt::Display for DiffMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiffMismatch::Missing(offset, old, new) => {
+                write!(
+                    f,
+                    "{:08x}: expected {:02x} -> {:02x}, no difference found",
+                    offset, old, new
+                )
+            }
+            DiffMismatch::Unexpected(offset, old, new) => {
+                write!(f, "{:08x}: unexpected {:02x} -> {:02x}", offset, old, new)
+            }
+            DiffMismatch::Changed {
+                offset,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{:08x}: expected {:02x} -> {:02x}, found {:02x} -> {:02x}",
+                offset, expected.0, expected.1, actual.0, actual.1
+            ),
+        }
+    }
+}
+
+/// Format the diff as one `offset: old new` line per differing byte.
+pub fn format_batch(pairs: &[(usize, u8, u8)], base: OffsetBase) -> String {
+    pairs
+        .iter()
+        .map(|&(offset, old, new)| {
+            format!("{}: {:02x} {:02x}\n", format_offset(offset, base), old, new)
+        })
+        .collect()
+}
+
+/// Format an aligned diff as one `offset1 offset2: old new` line per change,
+/// with `--` standing in for the byte an insertion or deletion lacks.
+pub fn format_aligned(changes: &[AlignedChange], base: OffsetBase) -> String {
+    changes
+        .iter()
+        .map(|change| format!("{}\n", format_aligned_change(change, base)))
+        .collect()
+}
+
+/// Format one change of an aligned diff as `offset1 offset2: old new`.
+pub fn format_aligned_change(change: &AlignedChange, base: OffsetBase) -> String {
+    let byte = |byte: Option<u8>| byte.map_or_else(|| "--".to_string(), |b| format!("{:02x}", b));
+    let (old, new) = match change.change {
+        Change::Substitute(old, new) => (Some(old), Some(new)),
+        Change::Delete(old) => (Some(old), None),
+        Change::Insert(new) => (None, Some(new)),
+    };
+    format!(
+        "{} {}: {} {}",
+        format_offset(change.offset1, base),
+        format_offset(change.offset2, base),
+        byte(old),
+        byte(new)
+    )
+}
+
+/// Format a nibble-level diff as one `offset.h|l: old new` line per differing
+/// nibble, where `h` and `l` name the high and low nibble of the byte.
+pub fn format_nibbles(nibbles: &[(usize, u8, u8)], base: OffsetBase) -> String {
+    nibbles
+        .iter()
+        .map(|&(index, old, new)| {
+            let half = if index % 2 == 0 { 'h' } else { 'l' };
+            format!(
+                "{}.{}: {:x} {:x}\n",
+                format_offset(index / 2, base),
+                half,
+                old,
+                new
+            )
+        })
+        .collect()
+}
+
+/// Format only the offsets of the diff, one per line.
+pub fn format_offsets_only(diffs: &[(usize, u8)], base: OffsetBase) -> String {
+    diffs
+        .iter()
+        .map(|&(offset, _)| format!("{}\n", format_offset(offset, base)))
+        .collect()
+}
+
+/// Ranges of `len` bytes covering `context` bytes either side of each offset.
+/// Ranges that overlap or touch are merged, and all are clamped to the data.
+pub fn context_blocks(offsets: &[usize], len: usize, context: usize) -> Vec<Range<usize>> {
+    let mut blocks: Vec<Range<usize>> = Vec::new();
+    for &offset in offsets.iter().filter(|&&offset| offset < len) {
+        let start = offset.saturating_sub(context);
+        let end = offset.saturating_add(context).saturating_add(1).min(len);
+        match blocks.last_mut() {
+            Some(last) if start <= last.end => last.end = last.end.max(end),
+            _ => blocks.push(start..end),
+        }
+    }
+    blocks
+}
+
+/// Format the diff as hexdump blocks of `data` with `context` unchanged bytes
+/// around each change. Changed bytes are marked with `*` and blocks are
+/// separated by `--`.
+pub fn format_context(data: &[u8], pairs: &[(usize, u8, u8)], context: usize) -> String {
+    let offsets: Vec<usize> = pairs.iter().map(|&(offset, _, _)| offset).collect();
+    let blocks = context_blocks(&offsets, data.len(), context);
+
+    let mut changed = offsets.iter().peekable();
+    let mut out = Vec::new();
+    for block in blocks {
+        let lines: Vec<String> = block
+            .clone()
+            .step_by(BATCH_BYTES_PER_LINE)
+            .map(|start| {
+                let end = (start + BATCH_BYTES_PER_LINE).min(block.end);
+                hexdump_line(data, start..end, &mut changed)
+            })
+            .collect();
+        out.push(lines.join("\n"));
+    }
+
+    if out.is_empty() {
+        String::new()
+    } else {
+        format!("{}\n", out.join("\n--\n"))
+    }
+}
+
+/// Format one hexdump line of `data[range]`, marking with `*` the bytes whose
+/// offsets come next in `changed`.
+fn hexdump_line<'a, I: Iterator<Item = &'a usize>>(
+    data: &[u8],
+    range: Range<usize>,
+    changed: &mut Peekable<I>,
+) -> String {
+    let mut hex = String::new();
+    let mut ascii = String::new();
+    for (offset, &byte) in range.clone().zip(&data[range.clone()]) {
+        let marked = changed.next_if(|&&o| o == offset).is_some();
+        hex.push_str(&format!("{}{:02x}", if marked { '*' } else { ' ' }, byte));
+        ascii.push(if byte.is_ascii_graphic() || byte == b' ' {
+            byte as char
+        } else {
+            '.'
+        });
+    }
+    format!(
+        "{:08x} {:<width$}  |{}|",
+        range.start,
+        hex,
+        ascii,
+        width = BATCH_BYTES_PER_LINE * 3
+    )
+}
+
+/// Ranges of row indices to fold in a listing of `len` bytes laid out
+/// `bytes_per_row` to a row: every run of more than `threshold` consecutive
+/// rows without a changed offset.
+pub fn fold_rows(
+    offsets: &[usize],
+    len: usize,
+    bytes_per_row: usize,
+    threshold: usize,
+) -> Vec<Range<usize>> {
+    let rows = len.div_ceil(bytes_per_row);
+    let mut folds = Vec::new();
+    let mut gap_start = 0;
+    let changed_rows = offsets
+        .iter()
+        .map(|&offset| offset / bytes_per_row)
+        .filter(|&row| row < rows);
+    for row in changed_rows.chain(std::iter::once(rows)) {
+        if row > gap_start && row - gap_start > threshold {
+            folds.push(gap_start..row);
+        }
+        gap_start = gap_start.max(row + 1);
+    }
+    folds
+}
+
+/// A line of a hexdump with folded rows.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FoldedLine {
+    /// The row of bytes with this index.
+    Row(usize),
+    /// A marker standing in for these folded rows.
+    Fold(Range<usize>),
+}
+
+impl FoldedLine {
+    /// The rows this line shows.
+    pub fn rows(&self) -> Range<usize> {
+        match self {
+            FoldedLine::Row(row) => *row..row + 1,
+            FoldedLine::Fold(rows) => rows.clone(),
+        }
+    }
+}
+
+/// Lay out a hexdump of `len` bytes, `bytes_per_row` to a row, with each of
+/// the `folds` from [`fold_rows`] collapsed into a single line.
+pub fn folded_lines(len: usize, bytes_per_row: usize, folds: &[Range<usize>]) -> Vec<FoldedLine> {
+    let rows = len.div_ceil(bytes_per_row);
+    let mut folds = folds.iter().peekable();
+    let mut lines = Vec::new();
+    let mut row = 0;
+    while row < rows {
+        match folds.next_if(|fold| fold.start == row) {
+            Some(fold) => {
+                lines.push(FoldedLine::Fold(fold.clone()));
+                row = fold.end;
+            }
+            None => {
+                lines.push(FoldedLine::Row(row));
+                row += 1;
+            }
+        }
+    }
+    lines
+}
+
+/// Index of the line in `lines`, from [`folded_lines`], that shows `row`.
+pub fn line_of_row(lines: &[FoldedLine], row: usize) -> usize {
+    lines.partition_point(|line| line.rows().end <= row)
+}
+
+/// The marker line for folded `rows` of a hexdump of `len` bytes.
+pub fn fold_marker(rows: &Range<usize>, len: usize, bytes_per_row: usize) -> String {
+    let bytes = (rows.end * bytes_per_row).min(len) - rows.start * bytes_per_row;
+    format!("⋯ ({} bytes unchanged) ⋯", bytes)
+}
+
+/// Format the whole of `data` as a hexdump with changed bytes marked by `*`,
+/// folding every run of more than `threshold` unchanged lines into a single
+/// marker line.
+pub fn format_folded(data: &[u8], pairs: &[(usize, u8, u8)], threshold: usize) -> String {
+    let offsets: Vec<usize> = pairs.iter().map(|&(offset, _, _)| offset).collect();
+    let folds = fold_rows(&offsets, data.len(), BATCH_BYTES_PER_LINE, threshold);
+
+    let mut changed = offsets.iter().peekable();
+    let mut out = String::new();
+    for line in folded_lines(data.len(), BATCH_BYTES_PER_LINE, &folds) {
+        match line {
+            FoldedLine::Row(row) => {
+                let start = row * BATCH_BYTES_PER_LINE;
+                let end = (start + BATCH_BYTES_PER_LINE).min(data.len());
+                out.push_str(&hexdump_line(data, start..end, &mut changed));
+            }
+            FoldedLine::Fold(rows) => {
+                out.push_str(&fold_marker(&rows, data.len(), BATCH_BYTES_PER_LINE))
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// How two diffs taken against the same baseline disagree at an offset.
+#[derive(Debug, PartialEq)]
+pub enum Divergence {
+    /// Only the first diff changes the offset, to the given byte.
+    OnlyFirst(usize, u8),
+    /// Only the second diff changes the offset, to the given byte.
+    OnlySecond(usize, u8),
+    /// Both diffs change the offset, but to different bytes.
+    Differs {
+        offset: usize,
+        first: u8,
+        second: u8,
+    },
+}
+
+/// Compare two diffs of the same baseline and report, in offset order, where
+/// their new bytes diverge.
+pub fn diff_of_diffs(first: &[(usize, u8, u8)], second: &[(usize, u8, u8)]) -> Vec<Divergence> {
+    let first: BTreeMap<usize, u8> = first
+        .iter()
+        .map(|&(offset, _, new)| (offset, new))
+        .collect();
+    let second: BTreeMap<usize, u8> = second
+        .iter()
+        .map(|&(offset, _, new)| (offset, new))
+        .collect();
+
+    let mut offsets: Vec<usize> = first.keys().chain(second.keys()).copied().collect();
+    offsets.sort_unstable();
+    offsets.dedup();
+
+    offsets
+        .into_iter()
+        .filter_map(|offset| match (first.get(&offset), second.get(&offset)) {
+            (Some(&new), None) => Some(Divergence::OnlyFirst(offset, new)),
+            (None, Some(&new)) => Some(Divergence::OnlySecond(offset, new)),
+            (Some(&a), Some(&b)) if a != b => Some(Divergence::Differs {
+                offset,
+                first: a,
+                second: b,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+impl std::fmt::Display for Divergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Divergence::OnlyFirst(offset, new) => {
+                write!(f, "{:08x}: only in first ({:02x})", offset, new)
+            }
+            Divergence::OnlySecond(offset, new) => {
+                write!(f, "{:08x}: only in second ({:02x})", offset, new)
+            }
+            Divergence::Differs {
+                offset,
+                first,
+                second,
+            } => write!(
+                f,
+                "{:08x}: first {:02x}, second {:02x}",
+                offset, first, second
+            ),
+        }
+    }
+}
+
+/// An analysis session to summarize as a markdown report.
+pub struct Report<'a> {
+    pub file1: &'a str,
+    pub file2: &'a str,
+    pub file1_len: usize,
+    pub file2_len: usize,
+    pub diffs: &'a [(usize, u8)],
+    pub annotations: &'a HashMap<usize, String>,
+    pub base: OffsetBase,
+}
+
+impl Report<'_> {
+    /// Format the report as markdown: a summary table, the diff regions and
+    /// the annotations, ordered by offset.
+    pub fn to_markdown(&self) -> String {
+        let regions: Vec<(usize, usize)> = group_regions(self.diffs)
+            .into_iter()
+            .map(|region| (self.diffs[region.start].0, region.len()))
+            .collect();
+        let largest = regions
+            .iter()
+            .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))
+            .map_or("none".to_string(), |&(start, len)| {
+                let unit = if len == 1 { "byte" } else { "bytes" };
+                format!("{} {} at {}", len, unit, format_offset(start, self.base))
+            });
+
+        let mut out = format!(
+            "# Diff report\n\n`{}` vs `{}`\n\n## Summary\n\n|Statistic|Value|\n|---|---|\n",
+            self.file1, self.file2
+        );
+        out.push_str(&format!("|File 1 size|{} bytes|\n", self.file1_len));
+        out.push_str(&format!("|File 2 size|{} bytes|\n", self.file2_len));
+        out.push_str(&format!("|Differing bytes|{}|\n", self.diffs.len()));
+        out.push_str(&format!("|Regions|{}|\n", regions.len()));
+        out.push_str(&format!("|Largest region|{}|\n", largest));
+
+        out.push_str("\n## Regions\n\n");
+        if regions.is_empty() {
+            out.push_str("None.\n");
+        } else {
+            out.push_str("|Start|End|Bytes|\n|---|---|---|\n");
+            for &(start, len) in &regions {
+                out.push_str(&format!(
+                    "|{}|{}|{}|\n",
+                    format_offset(start, self.base),
+                    format_offset(start + len - 1, self.base),
+                    len
+                ));
+            }
+        }
+
+        out.push_str("\n## Annotations\n\n");
+        let annotations: BTreeMap<&usize, &String> = self.annotations.iter().collect();
+        if annotations.is_empty() {
+            out.push_str("None.\n");
+        } else {
+            out.push_str("|Offset|Note|\n|---|---|\n");
+            for (&offset, note) in annotations {
+                out.push_str(&format!(
+                    "|{}|{}|\n",
+                    format_offset(offset, self.base),
+                    note.replace('|', "\\|")
+                ));
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The JSON written for `pairs`.
+    fn json(pairs: &[(usize, u8, u8)], hex_offsets: bool) -> String {
+        let mut out = Vec::new();
+        write_json(&mut out, pairs.iter().copied(), hex_offsets).expect("writing to a Vec");
+        String::from_utf8(out).expect("JSON is UTF-8")
+    }
+
+    #[test]
+    fn json_round_trips_in_both_offset_styles() {
+        let pairs = [(0, 0x00, 0xff), (0x1a4f, b'a', b'b'), (usize::MAX, 255, 0)];
+        for hex_offsets in [false, true] {
+            assert_eq!(parse_json(&json(&pairs, hex_offsets)), Ok(pairs.to_vec()));
+        }
+        assert_eq!(json(&[], false), "[]\n");
+        assert_eq!(parse_json(&json(&[], true)), Ok(vec![]));
+    }
+
+    #[test]
+    fn json_writes_hex_offsets_as_strings() {
+        assert_eq!(
+            json(&[(26, 1, 2)], true),
+            "[\n  { \"offset\": \"0x1a\", \"old\": 1, \"new\": 2 }\n]\n"
+        );
+        assert_eq!(
+            json(&[(26, 1, 2)], false),
+            "[\n  { \"offset\": 26, \"old\": 1, \"new\": 2 }\n]\n"
+        );
+    }
+
+    #[test]
+    fn json_parsing_ignores_whitespace_and_field_order() {
+        let text = " \n[ {\"new\":2,\t\"offset\" : \"0x10\" ,\"old\":1}\r\n,{ \"old\": 3, \"new\": 4, \"offset\": 17 } ]\n\n";
+        assert_eq!(parse_json(text), Ok(vec![(16, 1, 2), (17, 3, 4)]));
+    }
+
+    #[test]
+    fn json_parsing_rejects_invalid_input() {
+        let cases = [
+            ("", "expected '[' but reached the end"),
+            ("[{\"offset\": 1, \"old\": 2}]", "entry is missing \"new\""),
+            (
+                "[{\"offset\": 1, \"old\": 256, \"new\": 0}]",
+                "\"old\" value 256 is not a byte",
+            ),
+            (
+                "[{\"offset\": \"1a\", \"old\": 1, \"new\": 0}]",
+                "expected a \"0x\" hex number but found \"1a\"",
+            ),
+            (
+                "[{\"offset\": -1, \"old\": 1, \"new\": 0}]",
+                "expected a non-negative integer",
+            ),
+            (
+                "[{\"offset\": 1, \"old\": 1, \"new\": 0}",
+                "expected ']' but reached the end",
+            ),
+            (
+                "[] []",
+                "unexpected trailing characters after the diff array",
+            ),
+            ("[{\"offset", "unterminated string"),
+        ];
+        for (text, error) in cases {
+            assert_eq!(parse_json(text), Err(error.to_string()), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn only_runs_past_the_threshold_fold() {
+        // Rows 1 and 9 change in a 12-row listing
+        let folds = fold_rows(&[17, 20, 150], 190, 16, 3);
+        assert_eq!(folds, vec![2..9]);
+        assert_eq!(fold_rows(&[17, 150], 190, 16, 7), vec![]);
+        // Leading and trailing runs fold too, and a short last row counts
+        assert_eq!(fold_rows(&[100], 300, 16, 2), vec![0..6, 7..19]);
+        assert_eq!(fold_rows(&[], 64, 16, 3), vec![0..4]);
+        assert_eq!(fold_rows(&[], 0, 16, 3), vec![]);
+    }
+
+    #[test]
+    fn folded_lines_collapse_each_fold() {
+        use FoldedLine::{Fold, Row};
+
+        let lines = folded_lines(190, 16, &fold_rows(&[17, 150], 190, 16, 3));
+        assert_eq!(
+            lines,
+            vec![Row(0), Row(1), Fold(2..9), Row(9), Row(10), Row(11)]
+        );
+        // Every row maps to the line showing it
+        let line_of: Vec<usize> = (0..12).map(|row| line_of_row(&lines, row)).collect();
+        assert_eq!(line_of, vec![0, 1, 2, 2, 2, 2, 2, 2, 2, 3, 4, 5]);
+        assert_eq!(fold_marker(&(2..9), 190, 16), "⋯ (112 bytes unchanged) ⋯");
+        assert_eq!(fold_marker(&(7..12), 190, 16), "⋯ (78 bytes unchanged) ⋯");
+        assert_eq!(folded_lines(40, 16, &[]), vec![Row(0), Row(1), Row(2)]);
+    }
+
+    #[test]
+    fn matching_diffs_have_no_mismatches() {
+        let diff = [(1, 0x00, 0xff), (8, b'a', b'b')];
+        assert_eq!(compare_diffs(&diff, &diff), vec![]);
+        assert_eq!(compare_diffs(&[], &[]), vec![]);
+    }
+
+    #[test]
+    fn mismatches_are_reported_in_offset_order() {
+        let expected = [(1, 0x00, 0xff), (4, 1, 2), (9, 7, 8)];
+        let actual = [(2, 3, 4), (4, 1, 3), (9, 7, 8)];
+        assert_eq!(
+            compare_diffs(&expected, &actual),
+            vec![
+                DiffMismatch::Missing(1, 0x00, 0xff),
+                DiffMismatch::Unexpected(2, 3, 4),
+                DiffMismatch::Changed {
+                    offset: 4,
+                    expected: (1, 2),
+                    actual: (1, 3),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn mismatches_print_one_line_each() {
+        let lines: Vec<String> = compare_diffs(&[(0x10, 1, 2), (0x20, 3, 4)], &[(0x10, 1, 5)])
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "00000010: expected 01 -> 02, found 01 -> 05",
+                "00000020: expected 03 -> 04, no difference found",
+            ]
+        );
+    }
+
+    #[test]
+    fn nibbles_name_the_byte_and_its_half() {
+        let nibbles = [(0, 0x1, 0x9), (3, 0xb, 0xf), (20, 0x3, 0x7), (21, 0xc, 0x0)];
+        assert_eq!(
+            format_nibbles(&nibbles, OffsetBase::Hex),
+            "00000000.h: 1 9\n00000001.l: b f\n0000000a.h: 3 7\n0000000a.l: c 0\n"
+        );
+        assert_eq!(
+            format_nibbles(&nibbles[2..], OffsetBase::Decimal),
+            "10.h: 3 7\n10.l: c 0\n"
+        );
+        assert_eq!(format_nibbles(&[], OffsetBase::Hex), "");
+    }
+
+    #[test]
+    fn offsets_print_in_either_base() {
+        assert_eq!(format_offset(0x1a4f, OffsetBase::Hex), "00001a4f");
+        assert_eq!(format_offset(0x1a4f, OffsetBase::Decimal), "6735");
+        assert_eq!(format_offset(0x1_0000_0000, OffsetBase::Hex), "100000000");
+
+        let diffs = [(0, 0x61), (255, 0x62), (4096, 0x63)];
+        assert_eq!(
+            format_offsets_only(&diffs, OffsetBase::Hex),
+            "00000000\n000000ff\n00001000\n"
+        );
+        assert_eq!(
+            format_offsets_only(&diffs, OffsetBase::Decimal),
+            "0\n255\n4096\n"
+        );
+        assert_eq!(format_offsets_only(&[], OffsetBase::Hex), "");
+    }
+
+    #[test]
+    fn batch_lines_follow_the_offset_base() {
+        let pairs = [(10, 0x61, 0x62)];
+        assert_eq!(format_batch(&pairs, OffsetBase::Hex), "0000000a: 61 62\n");
+        assert_eq!(format_batch(&pairs, OffsetBase::Decimal), "10: 61 62\n");
+    }
+
+    #[test]
+    fn diverging_diffs_are_reported_in_offset_order() {
+        let first = [(2, 0x00, 0x41), (5, 0x00, 0x42), (9, 0x00, 0x43)];
+        let second = [(1, 0x00, 0x44), (5, 0x00, 0x42), (9, 0x00, 0x45)];
+        let divergences = diff_of_diffs(&first, &second);
+        assert_eq!(
+            divergences,
+            vec![
+                Divergence::OnlySecond(1, 0x44),
+                Divergence::OnlyFirst(2, 0x41),
+                Divergence::Differs {
+                    offset: 9,
+                    first: 0x43,
+                    second: 0x45
+                },
+            ]
+        );
+        let lines: Vec<String> = divergences.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            lines,
+            [
+                "00000001: only in second (44)",
+                "00000002: only in first (41)",
+                "00000009: first 43, second 45",
+            ]
+        );
+    }
+
+    #[test]
+    fn identical_diffs_do_not_diverge() {
+        let diff = [(3, 0x00, 0x01), (4, 0x02, 0x03)];
+        assert_eq!(diff_of_diffs(&diff, &diff), vec![]);
+        // Only the new bytes matter
+        assert_eq!(
+            diff_of_diffs(&[(3, 0x10, 0x01)], &[(3, 0x20, 0x01)]),
+            vec![]
+        );
+        assert_eq!(diff_of_diffs(&[], &[]), vec![]);
+    }
+
+    #[test]
+    fn debugger_expressions_assert_or_write_the_byte() {
+        assert_eq!(
+            format_debugger_expr(0x1040, 0xff, DebuggerStyle::Assert, OffsetBase::Hex),
+            "*(unsigned char*)0x1040 == 0xff"
+        );
+        assert_eq!(
+            format_debugger_expr(0x1040, 0x0a, DebuggerStyle::Write, OffsetBase::Hex),
+            "*(unsigned char*)0x1040 = 0x0a"
+        );
+        assert_eq!(
+            format_debugger_expr(4160, 0x00, DebuggerStyle::Assert, OffsetBase::Decimal),
+            "*(unsigned char*)4160 == 0x00"
+        );
+    }
+
+    #[test]
+    fn report_lists_regions_and_annotations_by_offset() {
+        let diffs = [(2, 0), (3, 0), (4, 0), (16, 0), (40, 0), (41, 0), (42, 0)];
+        let annotations = HashMap::from([
+            (40, "checksum | tail".to_string()),
+            (2, "header".to_string()),
+        ]);
+        let report = Report {
+            file1: "old.bin",
+            file2: "new.bin",
+            file1_len: 64,
+            file2_len: 80,
+            diffs: &diffs,
+            annotations: &annotations,
+            base: OffsetBase::Decimal,
+        };
+        assert_eq!(
+            report.to_markdown(),
+            "# Diff report\n\n`old.bin` vs `new.bin`\n\n## Summary\n\n\
+             |Statistic|Value|\n|---|---|\n\
+             |File 1 size|64 bytes|\n|File 2 size|80 bytes|\n\
+             |Differing bytes|7|\n|Regions|3|\n|Largest region|3 bytes at 2|\n\
+             \n## Regions\n\n|Start|End|Bytes|\n|---|---|---|\n\
+             |2|4|3|\n|16|16|1|\n|40|42|3|\n\
+             \n## Annotations\n\n|Offset|Note|\n|---|---|\n\
+             |2|header|\n|40|checksum \\| tail|\n"
+        );
+    }
+
+    #[test]
+    fn report_of_identical_files_says_none() {
+        let report = Report {
+            file1: "a",
+            file2: "b",
+            file1_len: 4,
+            file2_len: 4,
+            diffs: &[],
+            annotations: &HashMap::new(),
+            base: OffsetBase::Hex,
+        };
+        let markdown = report.to_markdown();
+        assert!(markdown.contains("|Largest region|none|"));
+        assert!(markdown.contains("## Regions\n\nNone.\n"));
+        assert!(markdown.ends_with("## Annotations\n\nNone.\n"));
+    }
+
+    #[test]
+    fn relative_offsets_are_signed_distances() {
+        assert_eq!(format_relative(0x30, 0x10, OffsetBase::Hex), "+0x20");
+        assert_eq!(format_relative(0x10, 0x30, OffsetBase::Hex), "-0x20");
+        assert_eq!(format_relative(0x10, 0x10, OffsetBase::Hex), "+0x0");
+        assert_eq!(format_relative(5, 20, OffsetBase::Decimal), "-15");
+    }
+}
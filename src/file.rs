@@ -1,20 +1,132 @@
 use std::{
+    collections::HashMap,
     fs::File,
     io::{self, Read},
+    ops::{Deref, Range},
     path::Path,
+    time::Instant,
 };
 
+#[cfg(all(feature = "mmap", unix))]
+use crate::mmap::Mmap;
+
+/// File argument that reads the data from stdin instead.
+pub const STDIN_PATH: &str = "-";
+
 /// Read the contents of a file into a vector of bytes.
 pub fn read_file<P: AsRef<Path>>(path: P) -> io::Result<Vec<u8>> {
-    let mut file = File::open(path)?;
+    read_from(File::open(path)?)
+}
+
+/// Read a file argument, where [`STDIN_PATH`] reads all of stdin.
+pub fn read_input(path: &str) -> io::Result<Vec<u8>> {
+    if path == STDIN_PATH {
+        read_from(io::stdin().lock())
+    } else {
+        read_file(path)
+    }
+}
+
+/// The contents of a file argument: read into memory, or with the `mmap`
+/// feature, mapped from disk.
+pub enum FileData {
+    Owned(Vec<u8>),
+    #[cfg(all(feature = "mmap", unix))]
+    Mapped(Mmap),
+}
+
+impl Deref for FileData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            FileData::Owned(data) => data,
+            #[cfg(all(feature = "mmap", unix))]
+            FileData::Mapped(map) => map,
+        }
+    }
+}
+
+impl From<Vec<u8>> for FileData {
+    fn from(data: Vec<u8>) -> Self {
+        FileData::Owned(data)
+    }
+}
+
+/// Load a file argument like [`read_input`], except that with the `mmap`
+/// feature a regular file is mapped instead of read, so comparing two large
+/// disk images doesn't need room for both in memory.
+pub fn load_input(path: &str) -> io::Result<FileData> {
+    #[cfg(all(feature = "mmap", unix))]
+    if path != STDIN_PATH {
+        if let Some(map) = Mmap::map(&File::open(path)?)? {
+            return Ok(FileData::Mapped(map));
+        }
+    }
+    read_input(path).map(FileData::Owned)
+}
+
+/// Read everything from `reader` into a vector of bytes.
+pub fn read_from<R: Read>(mut reader: R) -> io::Result<Vec<u8>> {
     let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer)?;
+    reader.read_to_end(&mut buffer)?;
     Ok(buffer)
 }
 
 /// Compare two files and return a vector of tuples containing the index of the
 /// differing byte and the byte itself.
+#[cfg(not(feature = "parallel"))]
+pub fn diff_files(file1: &[u8], file2: &[u8]) -> Vec<(usize, u8)> {
+    diff_files_sequential(file1, file2)
+}
+
+/// Inputs shorter than this are compared on the calling thread, since
+/// spawning threads would cost more than it saves.
+#[cfg(feature = "parallel")]
+const PARALLEL_THRESHOLD: usize = 1 << 20;
+
+/// Compare two files and return a vector of tuples containing the index of the
+/// differing byte and the byte itself. Large inputs are split into one chunk
+/// per available core and compared in parallel; the result is the same as
+/// comparing them sequentially, in ascending offset order.
+#[cfg(feature = "parallel")]
 pub fn diff_files(file1: &[u8], file2: &[u8]) -> Vec<(usize, u8)> {
+    let len = file1.len().min(file2.len());
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    if len < PARALLEL_THRESHOLD || threads == 1 {
+        return diff_files_sequential(file1, file2);
+    }
+    diff_files_chunked(file1, file2, threads)
+}
+
+/// Compare the files in `chunks` roughly equal chunks, one thread each.
+#[cfg(feature = "parallel")]
+fn diff_files_chunked(file1: &[u8], file2: &[u8], chunks: usize) -> Vec<(usize, u8)> {
+    let len = file1.len().min(file2.len());
+    let chunk_len = len.div_ceil(chunks).max(1);
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = file1[..len]
+            .chunks(chunk_len)
+            .zip(file2[..len].chunks(chunk_len))
+            .enumerate()
+            .map(|(chunk, (chunk1, chunk2))| {
+                scope.spawn(move || {
+                    let base = chunk * chunk_len;
+                    let mut diffs = diff_files_sequential(chunk1, chunk2);
+                    diffs.iter_mut().for_each(|(i, _)| *i += base);
+                    diffs
+                })
+            })
+            .collect();
+        // Joining in spawn order keeps the chunks in offset order
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("diff thread panicked"))
+            .collect()
+    })
+}
+
+fn diff_files_sequential(file1: &[u8], file2: &[u8]) -> Vec<(usize, u8)> {
     file1
         .iter()
         .zip(file2.iter())
@@ -22,3 +134,834 @@ pub fn diff_files(file1: &[u8], file2: &[u8]) -> Vec<(usize, u8)> {
         .filter_map(|(i, (&b1, &b2))| if b1 != b2 { Some((i, b1)) } else { None })
         .collect()
 }
+
+/// Compare two files and return a vector of tuples containing the index of the
+/// differing byte, the byte from the first file and the byte from the second.
+pub fn diff_files_pairs(file1: &[u8], file2: &[u8]) -> Vec<(usize, u8, u8)> {
+    iter_diff_pairs(file1, file2).collect()
+}
+
+/// Lazily yield the differences of [`diff_files_pairs`] one at a time, so a
+/// caller writing them out never holds them all in memory.
+pub fn iter_diff_pairs<'a>(
+    file1: &'a [u8],
+    file2: &'a [u8],
+) -> impl Iterator<Item = (usize, u8, u8)> + 'a {
+    file1
+        .iter()
+        .zip(file2.iter())
+        .enumerate()
+        .filter_map(|(i, (&b1, &b2))| if b1 != b2 { Some((i, b1, b2)) } else { None })
+}
+
+/// Like [`diff_files`], but leaves out every offset inside one of the
+/// `ignore` ranges, which may overlap and come in any order.
+pub fn diff_files_ignoring(
+    file1: &[u8],
+    file2: &[u8],
+    ignore: &[Range<usize>],
+) -> Vec<(usize, u8)> {
+    let len = file1.len().min(file2.len());
+    kept_spans(len, ignore)
+        .into_iter()
+        .flat_map(|span| {
+            diff_files(&file1[span.clone()], &file2[span.clone()])
+                .into_iter()
+                .map(move |(i, byte)| (span.start + i, byte))
+        })
+        .collect()
+}
+
+/// Like [`diff_files_pairs`], but leaves out every offset inside one of the
+/// `ignore` ranges. The comparison is that of [`diff_files_ignoring`], so it
+/// runs in parallel like it.
+pub fn diff_files_pairs_ignoring(
+    file1: &[u8],
+    file2: &[u8],
+    ignore: &[Range<usize>],
+) -> Vec<(usize, u8, u8)> {
+    with_second_bytes(&diff_files_ignoring(file1, file2, ignore), file2)
+}
+
+/// Add the second file's byte at each offset of `diffs`, turning the result
+/// of [`diff_files`] into that of [`diff_files_pairs`] without comparing the
+/// files again.
+pub fn with_second_bytes(diffs: &[(usize, u8)], file2: &[u8]) -> Vec<(usize, u8, u8)> {
+    diffs
+        .iter()
+        .map(|&(offset, old)| (offset, old, file2[offset]))
+        .collect()
+}
+
+/// Whether the files differ anywhere outside the `ignore` ranges, counting a
+/// difference in length.
+pub fn differs_ignoring(file1: &[u8], file2: &[u8], ignore: &[Range<usize>]) -> bool {
+    file1.len() != file2.len()
+        || kept_spans(file1.len(), ignore)
+            .into_iter()
+            .any(|span| file1[span.clone()] != file2[span])
+}
+
+/// Whether `offset` falls inside one of the `ignore` ranges.
+pub fn is_ignored(offset: usize, ignore: &[Range<usize>]) -> bool {
+    ignore.iter().any(|range| range.contains(&offset))
+}
+
+/// The spans of `0..len` outside every `ignore` range, in offset order.
+fn kept_spans(len: usize, ignore: &[Range<usize>]) -> Vec<Range<usize>> {
+    let mut ignore = ignore.to_vec();
+    ignore.sort_by_key(|range| range.start);
+    let mut spans = Vec::new();
+    let mut start = 0;
+    for range in ignore {
+        if range.start > start {
+            spans.push(start..range.start.min(len));
+        }
+        start = start.max(range.end);
+        if start >= len {
+            break;
+        }
+    }
+    if start < len {
+        spans.push(start..len);
+    }
+    spans.retain(|span| !span.is_empty());
+    spans
+}
+
+/// Bytes compared between deadline checks in [`diff_files_pairs_until`].
+const DEADLINE_CHECK_INTERVAL: usize = 1 << 16;
+
+/// The diffs [`diff_files_pairs_until`] found before its deadline.
+pub struct PartialDiff {
+    pub pairs: Vec<(usize, u8, u8)>,
+    /// Whether the deadline passed before the whole files were compared, so
+    /// `pairs` only covers a prefix of them.
+    pub truncated: bool,
+}
+
+/// Like [`diff_files_pairs`], but gives up once `deadline` has passed. The
+/// deadline is checked before every block of bytes, so a deadline already in
+/// the past stops the comparison before it starts.
+pub fn diff_files_pairs_until(file1: &[u8], file2: &[u8], deadline: Instant) -> PartialDiff {
+    let mut pairs = Vec::new();
+    for (block, (chunk1, chunk2)) in file1
+        .chunks(DEADLINE_CHECK_INTERVAL)
+        .zip(file2.chunks(DEADLINE_CHECK_INTERVAL))
+        .enumerate()
+    {
+        if Instant::now() >= deadline {
+            return PartialDiff {
+                pairs,
+                truncated: true,
+            };
+        }
+        let base = block * DEADLINE_CHECK_INTERVAL;
+        pairs.extend(
+            diff_files_pairs(chunk1, chunk2)
+                .into_iter()
+                .map(|(i, b1, b2)| (base + i, b1, b2)),
+        );
+    }
+    PartialDiff {
+        pairs,
+        truncated: false,
+    }
+}
+
+/// Compare two files nibble by nibble and return a vector of tuples containing
+/// the index of the differing nibble (two per byte, high nibble first) and the
+/// nibble from each file.
+pub fn diff_nibbles(file1: &[u8], file2: &[u8]) -> Vec<(usize, u8, u8)> {
+    file1
+        .iter()
+        .zip(file2.iter())
+        .enumerate()
+        .flat_map(|(i, (&b1, &b2))| [(i * 2, b1 >> 4, b2 >> 4), (i * 2 + 1, b1 & 0xf, b2 & 0xf)])
+        .filter(|&(_, n1, n2)| n1 != n2)
+        .collect()
+}
+
+/// Return the indices of the `k` pairs whose bytes changed the most, ordered
+/// from the largest delta to the smallest. Ties keep their offset order.
+pub fn largest_deltas(pairs: &[(usize, u8, u8)], k: usize) -> Vec<usize> {
+    let delta = |i: usize| pairs[i].1.abs_diff(pairs[i].2);
+    let mut ranked: Vec<usize> = Vec::with_capacity(k + 1);
+    for i in 0..pairs.len() {
+        let pos = ranked.partition_point(|&j| delta(j) >= delta(i));
+        if pos < k {
+            ranked.insert(pos, i);
+            ranked.truncate(k);
+        }
+    }
+    ranked
+}
+
+/// Count how many of the diffs hold each byte value.
+pub fn byte_histogram(diffs: &[(usize, u8)]) -> [usize; 256] {
+    let mut counts = [0; 256];
+    for &(_, byte) in diffs {
+        counts[byte as usize] += 1;
+    }
+    counts
+}
+
+/// Return a copy of `data` with the byte overrides in `edits` applied. Edits
+/// past the end of the data are ignored.
+pub fn apply_edits(data: &[u8], edits: &HashMap<usize, u8>) -> Vec<u8> {
+    let mut patched = data.to_vec();
+    for (&offset, &byte) in edits {
+        if let Some(slot) = patched.get_mut(offset) {
+            *slot = byte;
+        }
+    }
+    patched
+}
+
+/// Group the diff indices into regions of contiguous file offsets, so each run
+/// of adjacent changed bytes is one region.
+pub fn group_regions(diffs: &[(usize, u8)]) -> Vec<Range<usize>> {
+    let mut regions: Vec<Range<usize>> = Vec::new();
+    for (i, &(offset, _)) in diffs.iter().enumerate() {
+        match regions.last_mut() {
+            Some(region) if diffs[region.end - 1].0 + 1 == offset => region.end = i + 1,
+            _ => regions.push(i..i + 1),
+        }
+    }
+    regions
+}
+
+/// How one byte changed in an aligned diff.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Change {
+    /// The first file's byte was replaced by the second's.
+    Substitute(u8, u8),
+    /// The byte is only in the first file.
+    Delete(u8),
+    /// The byte is only in the second file.
+    Insert(u8),
+}
+
+/// One change of an aligned diff. A deleted byte has no offset of its own in
+/// the second file, so `offset2` is where it would sit there, and likewise
+/// `offset1` for an inserted byte.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AlignedChange {
+    pub offset1: usize,
+    pub offset2: usize,
+    pub change: Change,
+}
+
+/// Edit distance past which [`diff_aligned`] stops looking for a better
+/// alignment of a region and compares it by position instead, so two large
+/// unrelated files still finish in reasonable time.
+const ALIGN_MAX_EDITS: usize = 1 << 12;
+
+/// Align the two files by content, so a byte inserted near the start shows
+/// up as one insertion rather than as every later byte differing. Bytes
+/// between two aligned matches are paired up as substitutions, and whatever
+/// is left over on the longer side is deleted or inserted.
+pub fn diff_aligned(file1: &[u8], file2: &[u8]) -> Vec<AlignedChange> {
+    let mut matches = Vec::new();
+    align_matches(file1, file2, 0, 0, &mut matches);
+    matches.push((file1.len(), file2.len()));
+
+    let mut changes = Vec::new();
+    let (mut start1, mut start2) = (0, 0);
+    for (end1, end2) in matches {
+        let paired = (end1 - start1).min(end2 - start2);
+        for k in 0..paired {
+            let (old, new) = (file1[start1 + k], file2[start2 + k]);
+            // Regions too far apart to align are compared by position, so
+            // they can still hold equal bytes.
+            if old != new {
+                changes.push(AlignedChange {
+                    offset1: start1 + k,
+                    offset2: start2 + k,
+                    change: Change::Substitute(old, new),
+                });
+            }
+        }
+        let deleted = &file1[start1 + paired..end1];
+        changes.extend(deleted.iter().enumerate().map(|(k, &old)| AlignedChange {
+            offset1: start1 + paired + k,
+            offset2: start2 + paired,
+            change: Change::Delete(old),
+        }));
+        let inserted = &file2[start2 + paired..end2];
+        changes.extend(inserted.iter().enumerate().map(|(k, &new)| AlignedChange {
+            offset1: start1 + paired,
+            offset2: start2 + paired + k,
+            change: Change::Insert(new),
+        }));
+        (start1, start2) = (end1 + 1, end2 + 1);
+    }
+    changes
+}
+
+/// Index of the first change of the aligned diff `changes` at or after the
+/// first file's `offset`. Bytes inserted just before `offset` count as at it.
+pub fn aligned_index(changes: &[AlignedChange], offset: usize) -> usize {
+    changes.partition_point(|change| change.offset1 < offset)
+}
+
+/// Offset in the second file that the first file's `offset` lines up with
+/// in the aligned diff `changes`. A deleted byte maps to where it would sit
+/// in the second file.
+pub fn offset_in_second(changes: &[AlignedChange], offset: usize) -> usize {
+    corresponding_offset(changes, offset, true)
+}
+
+/// Map `offset` in the first file to the second, or the other way around if
+/// not `from_first`, through the last change at or before it, past which the
+/// files line up byte for byte again.
+fn corresponding_offset(changes: &[AlignedChange], offset: usize, from_first: bool) -> usize {
+    // Each change's offset on the side mapped from and on the other side, and
+    // which side has its byte
+    let ends = |change: &AlignedChange| {
+        let (from, to) = if from_first {
+            (change.offset1, change.offset2)
+        } else {
+            (change.offset2, change.offset1)
+        };
+        let side = match (change.change, from_first) {
+            (Change::Substitute(..), _) => Side::Both,
+            (Change::Delete(_), true) | (Change::Insert(_), false) => Side::This,
+            (Change::Insert(_), true) | (Change::Delete(_), false) => Side::Other,
+        };
+        (from, to, side)
+    };
+    let before = changes.partition_point(|change| ends(change).0 <= offset);
+    let Some((from, to, side)) = before.checked_sub(1).map(|i| ends(&changes[i])) else {
+        return offset;
+    };
+    match side {
+        // Bytes only in the other file sit just before `from`
+        Side::Other => to + 1 + (offset - from),
+        _ if from == offset => to,
+        Side::Both => to + (offset - from),
+        // `from` has no byte of its own on the other side
+        Side::This => to + (offset - from) - 1,
+    }
+}
+
+/// Which file holds the byte of an aligned change, seen from one of them.
+enum Side {
+    Both,
+    This,
+    Other,
+}
+
+/// Push the offset pairs of matching bytes in a longest common subsequence
+/// of `a` and `b`, which start at `a0` and `b0` in their files.
+fn align_matches(a: &[u8], b: &[u8], a0: usize, b0: usize, matches: &mut Vec<(usize, usize)>) {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    matches.extend((0..prefix).map(|k| (a0 + k, b0 + k)));
+    let (a, b) = (&a[prefix..], &b[prefix..]);
+    let (a0, b0) = (a0 + prefix, b0 + prefix);
+
+    let suffix = a
+        .iter()
+        .rev()
+        .zip(b.iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (a_mid, b_mid) = (&a[..a.len() - suffix], &b[..b.len() - suffix]);
+    if !a_mid.is_empty() && !b_mid.is_empty() {
+        if let Some((x, y)) = middle_snake(a_mid, b_mid) {
+            align_matches(&a_mid[..x], &b_mid[..y], a0, b0, matches);
+            align_matches(&a_mid[x..], &b_mid[y..], a0 + x, b0 + y, matches);
+        }
+    }
+    matches.extend((0..suffix).map(|k| (a0 + a_mid.len() + k, b0 + b_mid.len() + k)));
+}
+
+/// Find a point `(x, y)` on a shortest edit path from `a` to `b` that splits
+/// it roughly in half, by running Myers' greedy search from both ends until
+/// they meet. Gives up with `None` when neither end meets the other within
+/// [`ALIGN_MAX_EDITS`] edits.
+fn middle_snake(a: &[u8], b: &[u8]) -> Option<(usize, usize)> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max_d = ((n + m + 1) / 2).min(ALIGN_MAX_EDITS as isize);
+    let offset = max_d + 1;
+    let len = 2 * offset as usize + 1;
+    // Furthest x reached on each diagonal k = x - y, from the start in
+    // `forward` and from the end (of the reversed inputs) in `backward`.
+    let mut forward = vec![-1isize; len];
+    let mut backward = vec![-1isize; len];
+    forward[offset as usize + 1] = 0;
+    backward[offset as usize + 1] = 0;
+    let delta = n - m;
+    let odd = delta % 2 != 0;
+    let at = |k: isize| (offset + k) as usize;
+    let reaches = |v: &[isize], k: isize| {
+        let i = offset + k;
+        (0..len as isize).contains(&i) && v[i as usize] != -1
+    };
+
+    // Diagonals trimmed from either end once they run off the edit graph.
+    let (mut forward_start, mut forward_end) = (0, 0);
+    let (mut backward_start, mut backward_end) = (0, 0);
+
+    for d in 0..max_d {
+        let mut k = -d + forward_start;
+        while k <= d - forward_end {
+            let mut x = if k == -d || (k != d && forward[at(k - 1)] < forward[at(k + 1)]) {
+                forward[at(k + 1)]
+            } else {
+                forward[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[at(k)] = x;
+            if x > n {
+                forward_end += 2;
+            } else if y > m {
+                forward_start += 2;
+            } else if odd && reaches(&backward, delta - k) && x + backward[at(delta - k)] >= n {
+                return Some((x as usize, y as usize));
+            }
+            k += 2;
+        }
+        let mut k = -d + backward_start;
+        while k <= d - backward_end {
+            let mut x = if k == -d || (k != d && backward[at(k - 1)] < backward[at(k + 1)]) {
+                backward[at(k + 1)]
+            } else {
+                backward[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[(n - x - 1) as usize] == b[(m - y - 1) as usize] {
+                x += 1;
+                y += 1;
+            }
+            backward[at(k)] = x;
+            if x > n {
+                backward_end += 2;
+            } else if y > m {
+                backward_start += 2;
+            } else if !odd && reaches(&forward, delta - k) {
+                let x1 = forward[at(delta - k)];
+                if x1 + x >= n {
+                    return Some((x1 as usize, (x1 - (delta - k)) as usize));
+                }
+            }
+            k += 2;
+        }
+    }
+    None
+}
+
+/// Overview of how two files differ.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DiffSummary {
+    /// Bytes compared, the length of the shorter file.
+    pub compared: usize,
+    pub differing: usize,
+    /// Runs of adjacent differing bytes, as grouped by [`group_regions`].
+    pub regions: usize,
+    /// Length in bytes of the longest region.
+    pub largest_region: usize,
+    pub first: Option<usize>,
+    pub last: Option<usize>,
+}
+
+impl DiffSummary {
+    /// Share of the compared bytes that differ, in percent.
+    pub fn percent_changed(&self) -> f64 {
+        if self.compared == 0 {
+            return 0.0;
+        }
+        self.differing as f64 * 100.0 / self.compared as f64
+    }
+}
+
+/// Summarize the differences between two files.
+pub fn diff_summary(file1: &[u8], file2: &[u8]) -> DiffSummary {
+    let diffs = diff_files(file1, file2);
+    let regions = group_regions(&diffs);
+    DiffSummary {
+        compared: file1.len().min(file2.len()),
+        differing: diffs.len(),
+        regions: regions.len(),
+        largest_region: regions.iter().map(|region| region.len()).max().unwrap_or(0),
+        first: diffs.first().map(|&(offset, _)| offset),
+        last: diffs.last().map(|&(offset, _)| offset),
+    }
+}
+
+/// Which differences the viewer keeps when focusing on text changes.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TextFilter {
+    /// Keep every difference.
+    #[default]
+    Off,
+    /// Keep differences where either byte is printable ASCII.
+    Either,
+    /// Keep differences where the first file's byte is printable ASCII.
+    File1,
+}
+
+/// Whether `byte` is printable ASCII, a space included.
+fn is_printable(byte: u8) -> bool {
+    byte.is_ascii_graphic() || byte == b' '
+}
+
+/// The pairs kept by `filter`, in offset order.
+pub fn filter_text(pairs: &[(usize, u8, u8)], filter: TextFilter) -> Vec<(usize, u8, u8)> {
+    pairs
+        .iter()
+        .filter(|&&(_, old, new)| match filter {
+            TextFilter::Off => true,
+            TextFilter::Either => is_printable(old) || is_printable(new),
+            TextFilter::File1 => is_printable(old),
+        })
+        .copied()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic xorshift bytes, so a failure reproduces.
+    fn random_bytes(seed: &mut u64, len: usize) -> Vec<u8> {
+        (0..len)
+            .map(|_| {
+                *seed ^= *seed << 13;
+                *seed ^= *seed >> 7;
+                *seed ^= *seed << 17;
+                *seed as u8
+            })
+            .collect()
+    }
+
+    /// A copy of `data` with about one byte in `every` changed.
+    fn mutate(seed: &mut u64, data: &[u8], every: usize) -> Vec<u8> {
+        let noise = random_bytes(seed, data.len());
+        data.iter()
+            .zip(noise)
+            .map(|(&byte, noise)| {
+                if usize::from(noise) % every == 0 {
+                    byte.wrapping_add(1)
+                } else {
+                    byte
+                }
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn chunked_diff_matches_sequential() {
+        let mut seed = 0x2545_f491_4f6c_dd1d;
+        // Lengths and chunk counts that don't divide evenly, including more
+        // chunks than bytes
+        for len in [0, 1, 2, 7, 100, 1_000, 4_099] {
+            let file1 = random_bytes(&mut seed, len);
+            let file2 = mutate(&mut seed, &file1, 5);
+            for chunks in [1, 2, 3, 7, 16, 5_000] {
+                assert_eq!(
+                    diff_files_chunked(&file1, &file2, chunks),
+                    diff_files_sequential(&file1, &file2),
+                    "len {} in {} chunks",
+                    len,
+                    chunks
+                );
+            }
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_diff_matches_sequential_past_threshold() {
+        let mut seed = 0x9e37_79b9_7f4a_7c15;
+        let file1 = random_bytes(&mut seed, PARALLEL_THRESHOLD + 12_345);
+        let mut file2 = mutate(&mut seed, &file1, 50);
+        // Unequal lengths only compare the shorter span
+        file2.truncate(PARALLEL_THRESHOLD + 999);
+        assert_eq!(
+            diff_files(&file1, &file2),
+            diff_files_sequential(&file1, &file2)
+        );
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_pairs_match_sequential_past_threshold() {
+        let mut seed = 0xd1b5_4a32_d192_ed03;
+        let file1 = random_bytes(&mut seed, PARALLEL_THRESHOLD * 2 + 777);
+        let file2 = mutate(&mut seed, &file1, 30);
+        let sequential: Vec<_> = iter_diff_pairs(&file1, &file2).collect();
+        assert!(!sequential.is_empty());
+        assert_eq!(diff_files_pairs_ignoring(&file1, &file2, &[]), sequential);
+        // Ignored ranges split the input into spans compared on their own
+        let ignore = [
+            10..PARALLEL_THRESHOLD / 2,
+            PARALLEL_THRESHOLD + 5..PARALLEL_THRESHOLD + 9,
+        ];
+        let kept: Vec<_> = sequential
+            .into_iter()
+            .filter(|&(offset, _, _)| !is_ignored(offset, &ignore))
+            .collect();
+        assert_eq!(diff_files_pairs_ignoring(&file1, &file2, &ignore), kept);
+    }
+
+    #[test]
+    fn an_expired_deadline_truncates_the_diff() {
+        let mut seed = 0x2545_f491_4f6c_dd1d;
+        let file1 = random_bytes(&mut seed, DEADLINE_CHECK_INTERVAL * 3);
+        let file2 = mutate(&mut seed, &file1, 50);
+
+        let expired = diff_files_pairs_until(&file1, &file2, Instant::now());
+        assert!(expired.truncated);
+        assert!(expired.pairs.is_empty());
+
+        let later = Instant::now() + std::time::Duration::from_secs(60);
+        let full = diff_files_pairs_until(&file1, &file2, later);
+        assert!(!full.truncated);
+        assert_eq!(
+            full.pairs,
+            iter_diff_pairs(&file1, &file2).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn nibbles_differ_high_low_or_both() {
+        // Only the high nibble, only the low nibble, both, then neither
+        assert_eq!(
+            diff_nibbles(&[0x1a, 0x2b, 0x3c, 0x4d], &[0x9a, 0x2f, 0x70, 0x4d]),
+            vec![(0, 0x1, 0x9), (3, 0xb, 0xf), (4, 0x3, 0x7), (5, 0xc, 0x0),]
+        );
+        // Past the shorter file, nothing is compared
+        assert_eq!(diff_nibbles(&[0x12], &[0x12, 0x34]), vec![]);
+    }
+
+    #[test]
+    fn histogram_counts_each_byte_value() {
+        let counts = byte_histogram(&[(0, 0x41), (5, 0x00), (9, 0x41), (12, 0xff)]);
+        assert_eq!((counts[0x00], counts[0x41], counts[0xff]), (1, 2, 1));
+        assert_eq!(counts.iter().sum::<usize>(), 4);
+        assert_eq!(byte_histogram(&[]), [0; 256]);
+    }
+
+    #[test]
+    fn kept_spans_merge_overlapping_and_clamp_to_the_end() {
+        assert_eq!(kept_spans(10, &[]), vec![0..10]);
+        // Overlapping and out of order
+        assert_eq!(kept_spans(10, &[6..8, 2..5, 3..7]), vec![0..2, 8..10]);
+        // Touching the start, and running past the end
+        assert_eq!(kept_spans(10, &[0..2, 8..20]), vec![2..8]);
+        assert_eq!(kept_spans(10, &[12..15, 11..20]), vec![0..10]);
+        assert_eq!(kept_spans(10, &[0..4, 4..10]), vec![]);
+    }
+
+    #[test]
+    fn ignored_ranges_leave_out_their_diffs() {
+        let file1 = b"abcdefghij";
+        let file2 = b"aXcdYfgZij";
+        let ignore = [3..5, 4..6, 100..200];
+        assert_eq!(
+            diff_files_ignoring(file1, file2, &ignore),
+            vec![(1, b'b'), (7, b'h')]
+        );
+        assert!(differs_ignoring(file1, file2, &ignore));
+
+        // Every diff ignored: the files count as the same
+        let all = [7..9, 0..5];
+        assert_eq!(diff_files_ignoring(file1, file2, &all), vec![]);
+        assert!(!differs_ignoring(file1, file2, &all));
+        // Unless their lengths differ
+        assert!(differs_ignoring(file1, b"aXcdYfgZijk", &all));
+    }
+
+    #[test]
+    fn summary_counts_regions_and_totals() {
+        let summary = diff_summary(b"abcdefghij", b"aXYdeZgh");
+        assert_eq!(summary.compared, 8);
+        assert_eq!(summary.differing, 3);
+        assert_eq!(summary.regions, 2);
+        assert_eq!(summary.largest_region, 2);
+        assert_eq!((summary.first, summary.last), (Some(1), Some(5)));
+        assert_eq!(summary.percent_changed(), 37.5);
+    }
+
+    #[test]
+    fn summary_of_identical_files_is_empty() {
+        let summary = diff_summary(b"same", b"same");
+        assert_eq!(summary.compared, 4);
+        assert_eq!((summary.differing, summary.regions), (0, 0));
+        assert_eq!(summary.largest_region, 0);
+        assert_eq!((summary.first, summary.last), (None, None));
+        assert_eq!(summary.percent_changed(), 0.0);
+        assert_eq!(diff_summary(b"", b"").percent_changed(), 0.0);
+    }
+
+    #[test]
+    fn pairs_hold_both_bytes_up_to_the_shorter_file() {
+        assert_eq!(
+            diff_files_pairs(b"abcd", b"aXcY"),
+            vec![(1, b'b', b'X'), (3, b'd', b'Y')]
+        );
+        assert_eq!(diff_files_pairs(b"abcdef", b"abZ"), vec![(2, b'c', b'Z')]);
+        assert_eq!(diff_files_pairs(b"ab", b"abcd"), vec![]);
+        assert_eq!(diff_files_pairs(b"", b""), vec![]);
+        assert_eq!(
+            with_second_bytes(&diff_files(b"abcd", b"aXcY"), b"aXcY"),
+            diff_files_pairs(b"abcd", b"aXcY")
+        );
+    }
+
+    #[test]
+    fn text_filter_keeps_printable_changes() {
+        let pairs = [
+            (0, b'a', b'b'),
+            (1, 0x00, 0xff),
+            (2, 0x00, b'x'),
+            (3, b'y', 0x01),
+            (4, b' ', b'~'),
+            (5, 0x1f, 0x7f),
+        ];
+        assert_eq!(filter_text(&pairs, TextFilter::Off), pairs);
+        assert_eq!(
+            filter_text(&pairs, TextFilter::Either),
+            vec![pairs[0], pairs[2], pairs[3], pairs[4]]
+        );
+        assert_eq!(
+            filter_text(&pairs, TextFilter::File1),
+            vec![pairs[0], pairs[3], pairs[4]]
+        );
+    }
+
+    #[test]
+    fn diff_files_reports_offsets_and_first_file_bytes() {
+        assert_eq!(diff_files(b"abcd", b"abxdz"), vec![(2, b'c')]);
+        assert_eq!(diff_files(b"", b"abc"), vec![]);
+    }
+
+    #[test]
+    fn offsets_map_around_an_insertion() {
+        let changes = diff_aligned(b"abcdef", b"abXYcdef");
+        assert_eq!(
+            changes,
+            vec![
+                AlignedChange {
+                    offset1: 2,
+                    offset2: 2,
+                    change: Change::Insert(b'X')
+                },
+                AlignedChange {
+                    offset1: 2,
+                    offset2: 3,
+                    change: Change::Insert(b'Y')
+                },
+            ]
+        );
+        // Offset 2 in the first file lands on the bytes inserted before it
+        let indices: Vec<usize> = (0..=6).map(|x| aligned_index(&changes, x)).collect();
+        assert_eq!(indices, vec![0, 0, 0, 2, 2, 2, 2]);
+        let second: Vec<usize> = (0..=6).map(|x| offset_in_second(&changes, x)).collect();
+        assert_eq!(second, vec![0, 1, 4, 5, 6, 7, 8]);
+        // Both inserted bytes would sit at offset 2 of the first file
+        let first: Vec<usize> = (0..=8)
+            .map(|y| corresponding_offset(&changes, y, false))
+            .collect();
+        assert_eq!(first, vec![0, 1, 2, 2, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn offsets_map_around_a_deletion() {
+        let changes = diff_aligned(b"abXYcdef", b"abcdef");
+        assert_eq!(changes.len(), 2);
+        assert!(changes
+            .iter()
+            .all(|c| matches!(c.change, Change::Delete(_))));
+        let indices: Vec<usize> = (0..=8).map(|x| aligned_index(&changes, x)).collect();
+        assert_eq!(indices, vec![0, 0, 0, 1, 2, 2, 2, 2, 2]);
+        // Both deleted bytes would sit at offset 2 of the second file
+        let second: Vec<usize> = (0..=8).map(|x| offset_in_second(&changes, x)).collect();
+        assert_eq!(second, vec![0, 1, 2, 2, 2, 3, 4, 5, 6]);
+        let first: Vec<usize> = (0..=6)
+            .map(|y| corresponding_offset(&changes, y, false))
+            .collect();
+        assert_eq!(first, vec![0, 1, 4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn offsets_map_past_a_substitution() {
+        let changes = diff_aligned(b"abcdef", b"aBcdXYef");
+        assert_eq!(offset_in_second(&changes, 1), 1);
+        assert_eq!(offset_in_second(&changes, 2), 2);
+        assert_eq!(offset_in_second(&changes, 4), 6);
+        assert_eq!(corresponding_offset(&changes, 1, false), 1);
+        assert_eq!(corresponding_offset(&changes, 7, false), 5);
+        assert_eq!(offset_in_second(&[], 42), 42);
+    }
+
+    #[test]
+    fn offsets_in_both_files_map_onto_each_other() {
+        let mut seed = 0x5851_f42d_4c95_7f2d;
+        for _ in 0..50 {
+            let file1 = random_bytes(&mut seed, 300);
+            // Splice in a run, cut one out and change a few bytes
+            let cuts = random_bytes(&mut seed, 4);
+            let (at, len) = (usize::from(cuts[0]), usize::from(cuts[1] % 20));
+            let mut file2 = file1[..at].to_vec();
+            file2.extend(random_bytes(&mut seed, len));
+            file2.extend(&file1[at..]);
+            let at = usize::from(cuts[2]) + len;
+            file2.drain(at..(at + usize::from(cuts[3] % 20)).min(file2.len()));
+            let file2 = mutate(&mut seed, &file2, 40);
+
+            let changes = diff_aligned(&file1, &file2);
+            let changed: Vec<usize> = changes
+                .iter()
+                .filter(|c| !matches!(c.change, Change::Insert(_)))
+                .map(|c| c.offset1)
+                .collect();
+            for x in (0..file1.len()).filter(|x| !changed.contains(x)) {
+                let y = offset_in_second(&changes, x);
+                assert_eq!(file2[y], file1[x], "offset {} maps to {}", x, y);
+                assert_eq!(corresponding_offset(&changes, y, false), x);
+            }
+        }
+    }
+
+    #[test]
+    fn largest_deltas_do_not_wrap_around() {
+        // 0x00 -> 0xff is a change of 255, not 1
+        let pairs = [
+            (0, 0x01, 0xff),
+            (1, 0x00, 0xff),
+            (2, 0xff, 0x00),
+            (3, 0x80, 0x7f),
+        ];
+        assert_eq!(largest_deltas(&pairs, 4), vec![1, 2, 0, 3]);
+    }
+
+    #[test]
+    fn largest_deltas_keep_ties_in_offset_order() {
+        let pairs = [(0, 1, 2), (5, 9, 1), (9, 2, 1), (12, 1, 9), (20, 0, 1)];
+        assert_eq!(largest_deltas(&pairs, 5), vec![1, 3, 0, 2, 4]);
+        // Cutting off the list drops the later of the tied pairs
+        assert_eq!(largest_deltas(&pairs, 3), vec![1, 3, 0]);
+        assert_eq!(largest_deltas(&pairs, 0), vec![]);
+        assert_eq!(largest_deltas(&[], 3), vec![]);
+    }
+
+    #[test]
+    fn inputs_read_from_paths_and_readers() {
+        assert_eq!(read_from(&b"\x00piped\xff"[..]).unwrap(), b"\x00piped\xff");
+
+        let path = std::env::temp_dir().join(format!("dring-input-{}", std::process::id()));
+        std::fs::write(&path, b"on disk").unwrap();
+        let path = path.to_string_lossy().into_owned();
+        assert_eq!(read_input(&path).unwrap(), b"on disk");
+        assert_eq!(&*load_input(&path).unwrap(), b"on disk");
+        std::fs::remove_file(&path).unwrap();
+        assert!(read_input(&path).is_err());
+    }
+}
@@ -1,6 +1,16 @@
-use crossterm::event::{KeyCode, KeyEvent};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
 
-use crate::{app::App, tui::TerminalSize};
+use crate::{
+    app::{App, Pane, PromptKind},
+    clipboard::CopyFormat,
+    input::InputAction,
+    search::SearchKind,
+    tui::TerminalSize,
+    ui::file_view_rows,
+};
+
+/// Rows scrolled per mouse wheel step.
+const WHEEL_ROWS: isize = 3;
 
 /// Handle key events and update the app state accordingly.
 pub fn handle_key_events(
@@ -8,14 +18,218 @@ pub fn handle_key_events(
     app: &mut App,
     size: TerminalSize,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(prompt) = app.prompt.as_mut() {
+        if key_event.code == KeyCode::Tab && matches!(prompt.kind, PromptKind::Search(_)) {
+            app.cycle_search_scope(size.height);
+            return Ok(());
+        }
+        match prompt.input.handle_key(key_event.code) {
+            InputAction::Submit(text) => app.submit_prompt(&text, size.height),
+            InputAction::Cancel => app.cancel_prompt(),
+            InputAction::Edit => app.update_search(size.height),
+        }
+        return Ok(());
+    }
+
+    if app.match_list.is_some() {
+        match key_event.code {
+            KeyCode::Down | KeyCode::Char('j') => app.move_match_list(1),
+            KeyCode::Up | KeyCode::Char('k') => app.move_match_list(-1),
+            KeyCode::Enter => app.select_match_list(size.height),
+            KeyCode::Esc | KeyCode::Char('L' | 'q') => app.toggle_match_list(),
+            _ => {}
+        }
+        return Ok(());
+    }
+
+    if app.alignment.is_some() {
+        match key_event.code {
+            KeyCode::Down | KeyCode::Char('j') => app.move_alignment(1),
+            KeyCode::Up | KeyCode::Char('k') => app.move_alignment(-1),
+            KeyCode::Char(':') => app.start_goto(),
+            KeyCode::Enter => app.select_alignment(size.height),
+            KeyCode::Esc | KeyCode::Char('=' | 'q') => app.toggle_alignment(),
+            _ => {}
+        }
+        return Ok(());
+    }
+
+    if app.file_view.is_some() {
+        let page = file_view_rows(size.height) as isize;
+        match key_event.code {
+            KeyCode::Down | KeyCode::Char('j') => app.scroll_file_view(1),
+            KeyCode::Up | KeyCode::Char('k') => app.scroll_file_view(-1),
+            KeyCode::PageDown => app.scroll_file_view(page),
+            KeyCode::PageUp => app.scroll_file_view(-page),
+            KeyCode::Char('z') => app.toggle_folds(),
+            KeyCode::Esc | KeyCode::Char('V' | 'q') => app.toggle_file_view(size.height),
+            _ => {}
+        }
+        return Ok(());
+    }
+
+    if app.show_help {
+        // Swallow every other key so the view stays put behind the help
+        if matches!(key_event.code, KeyCode::Char('?') | KeyCode::Esc) {
+            app.show_help = false;
+        }
+        return Ok(());
+    }
+
+    if app.summary.is_some() {
+        if matches!(key_event.code, KeyCode::Char('I') | KeyCode::Esc) {
+            app.toggle_summary();
+        }
+        return Ok(());
+    }
+
+    if app.histogram.is_some() {
+        if matches!(key_event.code, KeyCode::Char('H') | KeyCode::Esc) {
+            app.toggle_histogram();
+        }
+        return Ok(());
+    }
+
+    if let Some(replay) = app.replay.as_mut() {
+        match key_event.code {
+            KeyCode::Char(' ') => replay.toggle_pause(),
+            _ => app.replay = None,
+        }
+        return Ok(());
+    }
+
+    app.status = None;
+    let ctrl = key_event.modifiers.contains(KeyModifiers::CONTROL);
+    let pending_g = std::mem::take(&mut app.pending_g);
+    if key_event.code != KeyCode::Char('D') {
+        app.delta_rank = None;
+    }
     match key_event.code {
+        KeyCode::Home => app.goto_start(),
+        KeyCode::Char('g') if pending_g => app.goto_start(),
+        KeyCode::Char('g') => app.pending_g = true,
+        KeyCode::End | KeyCode::Char('G') => app.goto_end(size.height),
+        KeyCode::PageDown => app.page_down(size.height),
+        KeyCode::PageUp => app.page_up(size.height),
+        KeyCode::Char('f') if ctrl => app.page_down(size.height),
+        KeyCode::Char('b') if ctrl => app.page_up(size.height),
         KeyCode::Char('q') => {
             app.quit();
         }
         KeyCode::Down | KeyCode::Char('j') => app.move_cursor_down(size.height),
-        KeyCode::Up | KeyCode::Char('k') => app.move_cursor_up(),
-        KeyCode::Right | KeyCode::Char('l') => app.move_cursor_right(size.height),
-        KeyCode::Left | KeyCode::Char('h') => app.move_cursor_left(),
+        KeyCode::Up | KeyCode::Char('k') => app.move_cursor_up(size.height),
+        KeyCode::Right | KeyCode::Char('l') if app.focused_pane == Pane::Ascii => {
+            app.move_char_right(size.height)
+        }
+        KeyCode::Left | KeyCode::Char('h') if app.focused_pane == Pane::Ascii => {
+            app.move_char_left(size.height)
+        }
+        KeyCode::Right | KeyCode::Char('l' | 'J') => app.move_cursor_right(size.height),
+        KeyCode::Left | KeyCode::Char('h' | 'K') => app.move_cursor_left(size.height),
+        KeyCode::Char('}') => app.next_region(size.height),
+        KeyCode::Char('{') => app.prev_region(size.height),
+        KeyCode::Char(':') => app.start_goto(),
+        KeyCode::Char('D') => app.jump_to_largest_delta(size.height),
+        KeyCode::Char('a') => app.start_annotation(),
+        KeyCode::Char('*') => app.start_watch(),
+        KeyCode::Char('S') => app.swap_files(size.height),
+        KeyCode::Char('p') => app.toggle_text_filter(size.height),
+        KeyCode::Char('#') => app.cycle_sample(size.height),
+        KeyCode::Char('m') => app.toggle_bookmark(),
+        KeyCode::Char('\'' | '`') => app.next_bookmark(size.height),
+        KeyCode::Char('e') => app.start_edit(),
+        KeyCode::Char('w') => app.start_write(),
+        KeyCode::Char('M') => app.start_report(),
+        KeyCode::Char('R') => app.reload(size.height),
+        KeyCode::Char('f') => app.toggle_follow(),
+        KeyCode::Char('/') => app.start_search(SearchKind::Ascii),
+        KeyCode::Char('\\' | 'x') => app.start_search(SearchKind::Hex),
+        KeyCode::Char('n') => app.next_match(size.height),
+        KeyCode::Char('N') => app.prev_match(size.height),
+        KeyCode::Char('L') => app.toggle_match_list(),
+        KeyCode::Char('=') => app.toggle_alignment(),
+        KeyCode::Char('V') => app.toggle_file_view(size.height),
+        KeyCode::Char(digit @ '1'..='9') => app.start_match_number(digit),
+        KeyCode::Char('X') => app.copy_debugger_expr(),
+        KeyCode::Char('F') => app.next_by_frequency(size.height),
+        KeyCode::Char('r') if ctrl => app.clear_anchor(),
+        KeyCode::Char('r') => app.set_anchor(),
+        KeyCode::Char('?') => app.show_help = true,
+        KeyCode::Char('I') => app.toggle_summary(),
+        KeyCode::Char('H') => app.toggle_histogram(),
+        KeyCode::Char('i') => app.toggle_inspector(size.height),
+        KeyCode::Char('v') => app.toggle_selection(),
+        KeyCode::Char('y') => app.copy_selection(app.copy_format),
+        KeyCode::Char('Y') => app.copy_selection(CopyFormat::Ascii),
+        KeyCode::Char('A') => app.copy_all(),
+        KeyCode::Char('c') => app.cycle_copy_format(),
+        KeyCode::Char('W') => app.cycle_width(size.width, size.height),
+        KeyCode::Char('s') => app.toggle_view(size.width, size.height),
+        KeyCode::Char('_') => app.toggle_split(size.height),
+        KeyCode::Tab => app.cycle_focus(size.height),
+        KeyCode::Char('u') => app.toggle_decode(),
+        KeyCode::Char('o') => app.copy_offsets(),
+        KeyCode::Esc => {
+            app.search.cancel();
+            app.frequency = None;
+            app.selection = None;
+        }
+        _ => {}
+    }
+    // Cursor moves extend the selection
+    if let Some(selection) = app.selection.as_mut() {
+        selection.head = app.cursor_pos;
+    }
+    Ok(())
+}
+
+/// Handle pasted text: it goes into the open prompt, if any, and is ignored
+/// otherwise so a stray paste can't trigger commands.
+pub fn handle_paste_events(text: &str, app: &mut App, size: TerminalSize) {
+    if let Some(prompt) = app.prompt.as_mut() {
+        prompt.input.paste(text);
+        app.update_search(size.height);
+    }
+}
+
+/// Handle mouse events: clicking or dragging on the scrollbar seeks to that
+/// relative position in the diff, clicking a byte moves the cursor to it and
+/// the wheel scrolls the view.
+/// The mouse is ignored while a prompt or overlay is open.
+pub fn handle_mouse_events(
+    mouse_event: MouseEvent,
+    app: &mut App,
+    size: TerminalSize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if app.prompt.is_some()
+        || app.match_list.is_some()
+        || app.alignment.is_some()
+        || app.file_view.is_some()
+        || app.show_help
+        || app.summary.is_some()
+        || app.histogram.is_some()
+    {
+        return Ok(());
+    }
+    match mouse_event.kind {
+        MouseEventKind::Down(MouseButton::Left) => {
+            app.dragging_scrollbar = app.on_scrollbar(mouse_event.column, mouse_event.row);
+            if app.dragging_scrollbar {
+                app.seek_scrollbar(mouse_event.row, size.height);
+            } else if let Some((index, pane)) = app.diff_at(mouse_event.column, mouse_event.row) {
+                app.cursor_pos = index;
+                app.focused_pane = pane;
+                if let Some(selection) = app.selection.as_mut() {
+                    selection.head = index;
+                }
+            }
+        }
+        MouseEventKind::Drag(MouseButton::Left) if app.dragging_scrollbar => {
+            app.seek_scrollbar(mouse_event.row, size.height);
+        }
+        MouseEventKind::Up(MouseButton::Left) => app.dragging_scrollbar = false,
+        MouseEventKind::ScrollDown => app.scroll_by(WHEEL_ROWS, size.height),
+        MouseEventKind::ScrollUp => app.scroll_by(-WHEEL_ROWS, size.height),
         _ => {}
     }
     Ok(())
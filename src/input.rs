@@ -0,0 +1,383 @@
+use crossterm::event::KeyCode;
+
+use crate::export::OffsetBase;
+
+/// Validation callback run against the prompt's text on submit.
+pub type Validator = fn(&str) -> Result<(), String>;
+
+/// Parse a single byte value written in hex, with or without a `0x` prefix.
+pub fn parse_byte(text: &str) -> Option<u8> {
+    let text = text.trim();
+    let digits = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .unwrap_or(text);
+    if digits.is_empty() || digits.len() > 2 {
+        return None;
+    }
+    u8::from_str_radix(digits, 16).ok()
+}
+
+/// Validator accepting a single hex byte value.
+pub fn validate_byte(text: &str) -> Result<(), String> {
+    parse_byte(text)
+        .map(|_| ())
+        .ok_or_else(|| "expected a hex byte, e.g. ff".to_string())
+}
+
+/// Parse a file offset. A `0x` prefix always means hex and `0o` octal;
+/// otherwise the digits are read in `base`, the base offsets are displayed in.
+pub fn parse_offset(text: &str, base: OffsetBase) -> Option<usize> {
+    let text = text.trim();
+    if let Some(digits) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        return usize::from_str_radix(digits, 16).ok();
+    }
+    if let Some(digits) = text.strip_prefix("0o").or_else(|| text.strip_prefix("0O")) {
+        return usize::from_str_radix(digits, 8).ok();
+    }
+    match base {
+        OffsetBase::Hex => usize::from_str_radix(text, 16).ok(),
+        OffsetBase::Decimal => text.parse().ok(),
+    }
+}
+
+/// Validator accepting a hex offset, with or without a `0x` prefix.
+pub fn validate_hex_offset(text: &str) -> Result<(), String> {
+    parse_offset(text, OffsetBase::Hex)
+        .map(|_| ())
+        .ok_or_else(|| "expected a hex offset, e.g. 1a4f0".to_string())
+}
+
+/// Validator accepting a decimal offset, or a hex one with a `0x` prefix.
+pub fn validate_decimal_offset(text: &str) -> Result<(), String> {
+    parse_offset(text, OffsetBase::Decimal)
+        .map(|_| ())
+        .ok_or_else(|| "expected an offset, e.g. 1024 or 0x400".to_string())
+}
+
+/// Validator accepting a match number.
+pub fn validate_match_number(text: &str) -> Result<(), String> {
+    text.parse::<usize>()
+        .map(|_| ())
+        .map_err(|_| "expected a match number, e.g. 5".to_string())
+}
+
+/// Outcome of feeding a key to an [`InputPrompt`].
+#[derive(Debug, PartialEq)]
+pub enum InputAction {
+    /// The text or cursor changed; the prompt stays open.
+    Edit,
+    /// The text passed validation and was submitted.
+    Submit(String),
+    /// The prompt was dismissed without submitting.
+    Cancel,
+}
+
+/// A single-line text prompt shared by every modal input in the app.
+#[derive(Debug)]
+pub struct InputPrompt {
+    pub title: &'static str,
+    text: String,
+    cursor: usize,
+    original: String,
+    validator: Validator,
+    pub error: Option<String>,
+    /// Earlier entries recalled with Up and Down, oldest first.
+    history: Vec<String>,
+    /// Index of the recalled history entry, if any.
+    history_pos: Option<usize>,
+    /// The text typed before recalling history, restored by Down past the
+    /// newest entry.
+    draft: String,
+}
+
+impl InputPrompt {
+    /// Constructs a new prompt pre-filled with `initial`, accepting any text.
+    pub fn new(title: &'static str, initial: &str) -> Self {
+        Self {
+            title,
+            text: initial.to_string(),
+            cursor: initial.chars().count(),
+            original: initial.to_string(),
+            validator: |_| Ok(()),
+            error: None,
+            history: Vec::new(),
+            history_pos: None,
+            draft: String::new(),
+        }
+    }
+
+    /// Sets the callback used to validate the text on submit.
+    pub fn with_validator(mut self, validator: Validator) -> Self {
+        self.validator = validator;
+        self
+    }
+
+    /// Sets the earlier entries, oldest first, that Up and Down step through.
+    pub fn with_history(mut self, history: Vec<String>) -> Self {
+        self.history = history;
+        self
+    }
+
+    /// The current text of the prompt.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The cursor position, in characters from the start of the text.
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Byte index in `text` of the given character position.
+    fn byte_index(&self, pos: usize) -> usize {
+        self.text
+            .char_indices()
+            .nth(pos)
+            .map_or(self.text.len(), |(i, _)| i)
+    }
+
+    /// Insert a character at the cursor.
+    pub fn insert(&mut self, c: char) {
+        let idx = self.byte_index(self.cursor);
+        self.text.insert(idx, c);
+        self.cursor += 1;
+        self.error = None;
+    }
+
+    /// Insert pasted text at the cursor. Line breaks and other control
+    /// characters are dropped, since the prompt holds a single line.
+    pub fn paste(&mut self, text: &str) {
+        for c in text.chars().filter(|c| !c.is_control()) {
+            self.insert(c);
+        }
+    }
+
+    /// Remove the character before the cursor, if any.
+    pub fn backspace(&mut self) {
+        if self.cursor == 0 {
+            return;
+        }
+        self.cursor -= 1;
+        let idx = self.byte_index(self.cursor);
+        self.text.remove(idx);
+        self.error = None;
+    }
+
+    /// Remove the character under the cursor, if any.
+    pub fn delete(&mut self) {
+        if self.cursor < self.text.chars().count() {
+            let idx = self.byte_index(self.cursor);
+            self.text.remove(idx);
+            self.error = None;
+        }
+    }
+
+    pub fn move_left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn move_right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.text.chars().count());
+    }
+
+    pub fn move_home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn move_end(&mut self) {
+        self.cursor = self.text.chars().count();
+    }
+
+    /// Replace the text with the previous history entry, keeping what was
+    /// typed so far to come back to.
+    pub fn history_prev(&mut self) {
+        let pos = match self.history_pos {
+            Some(0) => return,
+            Some(pos) => pos - 1,
+            None if self.history.is_empty() => return,
+            None => {
+                self.draft = self.text.clone();
+                self.history.len() - 1
+            }
+        };
+        self.history_pos = Some(pos);
+        self.set_text(self.history[pos].clone());
+    }
+
+    /// Replace the text with the next history entry, or with the typed text
+    /// after the newest one.
+    pub fn history_next(&mut self) {
+        let Some(pos) = self.history_pos else {
+            return;
+        };
+        if pos + 1 < self.history.len() {
+            self.history_pos = Some(pos + 1);
+            self.set_text(self.history[pos + 1].clone());
+        } else {
+            self.history_pos = None;
+            let draft = std::mem::take(&mut self.draft);
+            self.set_text(draft);
+        }
+    }
+
+    fn set_text(&mut self, text: String) {
+        self.cursor = text.chars().count();
+        self.text = text;
+        self.error = None;
+    }
+
+    /// Validate and return the text. On failure the error is kept on the
+    /// prompt so it can be shown to the user.
+    pub fn submit(&mut self) -> Option<String> {
+        match (self.validator)(&self.text) {
+            Ok(()) => Some(self.text.clone()),
+            Err(err) => {
+                self.error = Some(err);
+                None
+            }
+        }
+    }
+
+    /// Discard any edits, restoring the text the prompt was opened with.
+    pub fn cancel(&mut self) {
+        self.text = self.original.clone();
+        self.cursor = self.text.chars().count();
+        self.error = None;
+    }
+
+    /// Apply a key press to the prompt.
+    pub fn handle_key(&mut self, code: KeyCode) -> InputAction {
+        match code {
+            KeyCode::Enter => match self.submit() {
+                Some(text) => InputAction::Submit(text),
+                None => InputAction::Edit,
+            },
+            KeyCode::Esc => {
+                self.cancel();
+                InputAction::Cancel
+            }
+            KeyCode::Backspace => {
+                self.backspace();
+                InputAction::Edit
+            }
+            KeyCode::Delete => {
+                self.delete();
+                InputAction::Edit
+            }
+            KeyCode::Left => {
+                self.move_left();
+                InputAction::Edit
+            }
+            KeyCode::Right => {
+                self.move_right();
+                InputAction::Edit
+            }
+            KeyCode::Home => {
+                self.move_home();
+                InputAction::Edit
+            }
+            KeyCode::Up => {
+                self.history_prev();
+                InputAction::Edit
+            }
+            KeyCode::Down => {
+                self.history_next();
+                InputAction::Edit
+            }
+            KeyCode::End => {
+                self.move_end();
+                InputAction::Edit
+            }
+            KeyCode::Char(c) => {
+                self.insert(c);
+                InputAction::Edit
+            }
+            _ => InputAction::Edit,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_are_hex_with_or_without_a_prefix() {
+        assert_eq!(parse_byte("ff"), Some(0xff));
+        assert_eq!(parse_byte(" 0x0a "), Some(0x0a));
+        assert_eq!(parse_byte("0X7"), Some(0x07));
+        assert_eq!(parse_byte("g1"), None);
+        assert!(validate_byte("zz").is_err());
+    }
+
+    #[test]
+    fn bytes_reject_overflow_and_empty_input() {
+        assert_eq!(parse_byte("100"), None);
+        assert_eq!(parse_byte("0x1ff"), None);
+        assert_eq!(parse_byte(""), None);
+        assert_eq!(parse_byte("0x"), None);
+        assert_eq!(
+            validate_byte(""),
+            Err("expected a hex byte, e.g. ff".to_string())
+        );
+    }
+
+    #[test]
+    fn offsets_follow_the_display_base_unless_prefixed() {
+        assert_eq!(parse_offset("10", OffsetBase::Hex), Some(0x10));
+        assert_eq!(parse_offset("10", OffsetBase::Decimal), Some(10));
+        assert_eq!(parse_offset("0x10", OffsetBase::Decimal), Some(0x10));
+        assert_eq!(parse_offset("0o17", OffsetBase::Hex), Some(0o17));
+        assert_eq!(parse_offset("0O17", OffsetBase::Decimal), Some(0o17));
+        // Digits the base doesn't have
+        assert_eq!(parse_offset("1a", OffsetBase::Decimal), None);
+        assert_eq!(parse_offset("0o18", OffsetBase::Hex), None);
+        assert!(validate_hex_offset("1a4f0").is_ok());
+        assert!(validate_decimal_offset("1a4f0").is_err());
+    }
+
+    #[test]
+    fn offsets_reject_overflow_and_empty_input() {
+        let too_big = format!("{}0", usize::MAX);
+        assert_eq!(parse_offset(&too_big, OffsetBase::Decimal), None);
+        assert_eq!(parse_offset("0x1ffffffffffffffff", OffsetBase::Hex), None);
+        for text in ["", "  ", "0x", "0o"] {
+            assert_eq!(parse_offset(text, OffsetBase::Hex), None, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn match_numbers_are_decimal() {
+        assert!(validate_match_number("12").is_ok());
+        assert!(validate_match_number("").is_err());
+        assert!(validate_match_number("0x1").is_err());
+    }
+
+    #[test]
+    fn invalid_text_is_kept_with_an_error() {
+        let mut prompt = InputPrompt::new("Offset", "").with_validator(validate_hex_offset);
+        for c in "xyz".chars() {
+            prompt.handle_key(KeyCode::Char(c));
+        }
+        assert_eq!(prompt.handle_key(KeyCode::Enter), InputAction::Edit);
+        assert_eq!(prompt.text(), "xyz");
+        assert!(prompt.error.is_some());
+        // Editing clears the error
+        prompt.handle_key(KeyCode::Backspace);
+        assert_eq!(prompt.error, None);
+    }
+
+    #[test]
+    fn cancel_restores_the_original_text() {
+        let mut prompt = InputPrompt::new("Byte", "ff").with_validator(validate_byte);
+        prompt.handle_key(KeyCode::Backspace);
+        prompt.handle_key(KeyCode::Char('q'));
+        assert_eq!(prompt.text(), "fq");
+        prompt.handle_key(KeyCode::Enter);
+        assert_eq!(prompt.handle_key(KeyCode::Esc), InputAction::Cancel);
+        assert_eq!(prompt.text(), "ff");
+        assert_eq!(prompt.cursor(), 2);
+        assert_eq!(prompt.error, None);
+    }
+}
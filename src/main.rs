@@ -1,47 +1,582 @@
-use std::io;
+use std::{
+    collections::HashMap,
+    io::{self, BufWriter, IsTerminal, Write},
+    time::{Duration, Instant},
+};
 
 use app::App;
+use archive::extract_entries;
+use cli::{parse_args, parse_color, parse_manifest, parse_theme, parse_width, Args, ColorChoice};
 use event::{Event, EventHandler};
-use file::{diff_files, read_file};
-use handler::handle_key_events;
+use export::{
+    compare_diffs, diff_of_diffs, format_aligned, format_batch, format_context, format_folded,
+    format_nibbles, format_offsets_only, parse_json, write_json, Report,
+};
+use file::{
+    diff_aligned, diff_files_ignoring, diff_files_pairs_ignoring, diff_files_pairs_until,
+    diff_nibbles, differs_ignoring, is_ignored, iter_diff_pairs, load_input, read_file, read_input,
+    with_second_bytes, FileData, PartialDiff, STDIN_PATH,
+};
+use handler::{handle_key_events, handle_mouse_events, handle_paste_events};
+use patch::Patch;
+use print::{format_print, format_xxd, write_plain};
 use ratatui::{backend::CrosstermBackend, Terminal};
-use tui::Tui;
+use search::SearchHistory;
+use section::Section;
+use session::Session;
+use theme::Theme;
+use tui::{detect_color_support, Tui};
 
+mod analysis;
 mod app;
+mod archive;
+mod cli;
+mod clipboard;
+mod codepage;
 mod event;
+mod export;
 mod file;
 mod handler;
+mod input;
+#[cfg(all(feature = "mmap", unix))]
+mod mmap;
+mod patch;
+mod print;
+mod replay;
+mod search;
+mod section;
+mod session;
+mod theme;
 mod tui;
 mod ui;
+mod watch;
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() != 3 {
-        eprintln!("Usage: {} <file1> <file2>", args[0]);
-        std::process::exit(1);
+/// Exit status when the files can't be compared, e.g. one can't be read or
+/// an option is invalid, as with `cmp` and `diff`.
+const EXIT_TROUBLE: i32 = 2;
+
+fn main() {
+    match run() {
+        Ok(code) => std::process::exit(code),
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(EXIT_TROUBLE);
+        }
+    }
+}
+
+/// Run the comparison and return the exit status: 0 if the files are
+/// identical, 1 if they differ.
+fn run() -> Result<i32, Box<dyn std::error::Error>> {
+    let args = match parse_args(std::env::args_os()) {
+        Ok(args) => args,
+        // --help and --version exit 0, printed to stdout
+        Err(err) if !err.use_stderr() => err.exit(),
+        Err(err) => {
+            let _ = err.print();
+            return Ok(EXIT_TROUBLE);
+        }
+    };
+    let args = match resolve_settings(args) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}", err);
+            return Ok(EXIT_TROUBLE);
+        }
+    };
+
+    if let Some(path) = &args.apply {
+        let patch = Patch::parse(&read_file(path).map_err(|err| read_error(path, err))?)?;
+        let data = read_input(&args.file1).map_err(|err| read_error(&args.file1, err))?;
+        let patched = patch.apply(&data)?;
+        io::stdout().write_all(&patched)?;
+        return Ok(0);
+    }
+
+    if let Some(path) = &args.manifest {
+        return Ok(run_manifest(path, &args));
     }
 
-    let file1_data = read_file(&args[1])?;
-    let file2_data = read_file(&args[2])?;
-    let diffs = diff_files(&file1_data, &file2_data);
+    if args.diff_of_diffs {
+        return Ok(compare_diff_files(&args.file1, &args.file2));
+    }
+
+    if args.watch && (args.file1 == STDIN_PATH || args.file2 == STDIN_PATH) {
+        eprintln!("--watch can't watch data read from stdin");
+        return Ok(EXIT_TROUBLE);
+    }
+    // Watched files are expected to change, and a mapping of a file truncated
+    // later can't be read safely, so they are read instead
+    let read = |path: &str| {
+        let data = if args.watch {
+            read_input(path).map(FileData::from)
+        } else {
+            load_input(path)
+        };
+        data.map_err(|err| read_error(path, err))
+    };
+    let (file1_data, file2_data) = match (read(&args.file1), read(&args.file2)) {
+        (Ok(file1_data), Ok(file2_data)) => (file1_data, file2_data),
+        (Err(err), _) | (_, Err(err)) => {
+            eprintln!("{}", err);
+            return Ok(EXIT_TROUBLE);
+        }
+    };
+    let (file1_data, file2_data) = match &args.zip_entry {
+        Some(name) => {
+            match extract_entries(name, (&args.file1, &file1_data), (&args.file2, &file2_data)) {
+                Ok((entry1, entry2)) => (entry1.into(), entry2.into()),
+                Err(err) => {
+                    eprintln!("{}", err);
+                    return Ok(EXIT_TROUBLE);
+                }
+            }
+        }
+        None => (file1_data, file2_data),
+    };
+    let (section, file1_data, file2_data) = match &args.section {
+        Some(name) => match Section::extract(name, &file1_data, &file2_data) {
+            Ok((section, file1_data, file2_data)) => {
+                (Some(section), file1_data.into(), file2_data.into())
+            }
+            Err(err) => {
+                eprintln!("{}", err);
+                return Ok(EXIT_TROUBLE);
+            }
+        },
+        None => (None, file1_data, file2_data),
+    };
+    // Without a deadline to enforce, these write each diff as it is found
+    // instead of collecting them first
+    if args.timeout.is_none() && (args.json || args.no_tui) {
+        let status = i32::from(differs_ignoring(&file1_data, &file2_data, &args.ignore));
+        let mut out = BufWriter::new(io::stdout().lock());
+        let pairs = iter_diff_pairs(&file1_data, &file2_data)
+            .filter(|&(offset, _, _)| !is_ignored(offset, &args.ignore));
+        if args.json {
+            write_json(&mut out, pairs, args.hex_offsets)?;
+        } else {
+            write_plain(
+                &mut out,
+                pairs.map(|(offset, old, _)| (offset, old)),
+                args.offset_base,
+            )?;
+        }
+        out.flush()?;
+        return Ok(status);
+    }
+
+    let (diffs, pairs) = match args.timeout {
+        Some(seconds) => {
+            let deadline = Instant::now() + Duration::from_secs(seconds);
+            let PartialDiff {
+                mut pairs,
+                truncated,
+            } = diff_files_pairs_until(&file1_data, &file2_data, deadline);
+            if truncated {
+                eprintln!("diff timed out after {} seconds", seconds);
+                return Ok(3);
+            }
+            pairs.retain(|&(offset, _, _)| !is_ignored(offset, &args.ignore));
+            let diffs = pairs
+                .iter()
+                .map(|&(offset, old, _)| (offset, old))
+                .collect();
+            (diffs, pairs)
+        }
+        None => {
+            let diffs = diff_files_ignoring(&file1_data, &file2_data, &args.ignore);
+            let pairs = with_second_bytes(&diffs, &file2_data);
+            (diffs, pairs)
+        }
+    };
+    // Taken from the diff itself rather than a second pass over the files,
+    // so that --timeout bounds it too
+    let status = i32::from(!diffs.is_empty() || file1_data.len() != file2_data.len());
+
+    if args.json {
+        write_json(&mut io::stdout().lock(), pairs, args.hex_offsets)?;
+        return Ok(status);
+    }
+    if args.no_tui {
+        write_plain(&mut io::stdout().lock(), diffs, args.offset_base)?;
+        return Ok(status);
+    }
+    if let Some(path) = &args.patch {
+        let patch = Patch::new(&file1_data, &file2_data);
+        std::fs::write(path, patch.serialize())?;
+        return Ok(status);
+    }
+    if let Some(path) = &args.report {
+        let report = Report {
+            file1: &args.file1,
+            file2: &args.file2,
+            file1_len: file1_data.len(),
+            file2_len: file2_data.len(),
+            diffs: &diffs,
+            annotations: &HashMap::new(),
+            base: args.offset_base,
+        };
+        std::fs::write(path, report.to_markdown())?;
+        return Ok(status);
+    }
+    if args.print {
+        let color = match args.color.unwrap_or_default() {
+            ColorChoice::Auto => detect_color_support() && io::stdout().is_terminal(),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        };
+        let theme = args.theme.unwrap_or_default();
+        print!(
+            "{}",
+            format_print(&file2_data, &pairs, color.then_some(&theme))
+        );
+        return Ok(status);
+    }
+    if args.dump {
+        print!("{}", format_xxd(&pairs));
+        return Ok(status);
+    }
+    if args.offsets_only {
+        print!("{}", format_offsets_only(&diffs, args.offset_base));
+        return Ok(status);
+    }
+    if args.batch && args.align {
+        let mut changes = diff_aligned(&file1_data, &file2_data);
+        changes.retain(|change| !is_ignored(change.offset1, &args.ignore));
+        print!("{}", format_aligned(&changes, args.offset_base));
+        return Ok(i32::from(!changes.is_empty()));
+    }
+    if args.batch {
+        match (args.context, args.fold) {
+            (Some(context), _) => print!("{}", format_context(&file1_data, &pairs, context)),
+            (None, Some(threshold)) => print!("{}", format_folded(&file1_data, &pairs, threshold)),
+            (None, None) if args.nibble => {
+                let mut nibbles = diff_nibbles(&file1_data, &file2_data);
+                nibbles.retain(|&(nibble, _, _)| !is_ignored(nibble / 2, &args.ignore));
+                print!("{}", format_nibbles(&nibbles, args.offset_base));
+            }
+            (None, None) => print!("{}", format_batch(&pairs, args.offset_base)),
+        }
+        return Ok(status);
+    }
+    if let Some(path) = &args.expect {
+        return Ok(check_expected(path, &pairs));
+    }
 
     let backend = CrosstermBackend::new(io::stderr());
     let terminal = Terminal::new(backend)?;
-    let events = EventHandler::new(1_000);
+    let events = EventHandler::new(args.tick_rate);
     let mut tui = Tui::new(terminal, events);
     tui.init()?;
 
-    let mut app = App::new(file1_data, file2_data, diffs);
+    let mut app = App::new(file1_data, file2_data, diffs, pairs);
+    app.ignore = args.ignore.clone();
+    app.reverse_bytes = args.reverse_bytes;
+    app.nibble = args.nibble;
+    app.printable = args.printable;
+    app.copy_whitespace = args.copy_whitespace;
+    app.show_timer = args.timer;
+    app.incremental_search = !args.no_incremental_search;
+    app.search.history = SearchHistory::load();
+    app.separators = args.separators;
+    app.debugger_style = args.debugger_style;
+    if let Some(filter) = args.text_only {
+        app.text_filter = filter;
+        app.text_filter_mode = filter;
+        app.apply_text_filter();
+    }
+    app.codepage = args.codepage;
+    app.width = args.width;
+    let color = match args.color.unwrap_or_default() {
+        ColorChoice::Auto => detect_color_support(),
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+    };
+    let theme = if color {
+        args.theme.unwrap_or_default()
+    } else {
+        Theme::MONOCHROME
+    };
+    app.offset_base = args.offset_base;
+    app.file1_path = args.file1;
+    app.file2_path = args.file2;
+    app.section = section;
+    app.zip_entry = args.zip_entry;
+    if let Some(path) = &args.session {
+        // Restoring the view needs the layout of the first draw
+        tui.draw(&mut app, &theme)?;
+        match Session::load(path) {
+            Ok(Some(session)) => app.restore_session(&session, tui.size().height),
+            Ok(None) => {}
+            Err(err) => app.set_status(format!("Could not load the session: {}", err)),
+        }
+    }
+    if args.tail {
+        // The layout is only known after the first draw
+        tui.draw(&mut app, &theme)?;
+        app.following = true;
+        app.goto_end(tui.size().height);
+    }
+    if args.watch {
+        watch::spawn(
+            [app.file1_path.clone(), app.file2_path.clone()],
+            tui.events.sender(),
+        );
+    }
+    if let Some(seconds) = args.replay {
+        tui.draw(&mut app, &theme)?;
+        let ticks = (seconds * 1_000 / args.tick_rate).max(1);
+        app.start_replay(ticks, tui.size().height);
+    }
+    let mut redraw = true;
     while app.running {
-        tui.draw(&mut app)?;
+        if redraw {
+            tui.draw(&mut app, &theme)?;
+        }
+        // Only ticks that change the screen redraw it, so an idle viewer
+        // without the timer does no work between ticks
+        redraw = true;
         match tui.events.next()? {
-            Event::Tick => app.tick()?,
+            Event::Tick => redraw = app.tick(tui.size().height),
             Event::Key(key_event) => handle_key_events(key_event, &mut app, tui.size())?,
-            Event::Mouse(_) => {}
-            Event::Resize(_, _) => {}
+            Event::Mouse(mouse_event) => handle_mouse_events(mouse_event, &mut app, tui.size())?,
+            Event::Resize(width, height) => app.resize(width, height),
+            Event::Paste(text) => handle_paste_events(&text, &mut app, tui.size()),
+            Event::FilesChanged => app.reload(tui.size().height),
         }
     }
 
     tui.exit()?;
-    Ok(())
+    if let Some(path) = &args.session {
+        if let Err(err) = app.session().save(path) {
+            eprintln!("Could not save the session to {}: {}", path, err);
+        }
+    }
+    // Reloading may have changed the data since the comparison started
+    Ok(i32::from(differs_ignoring(
+        &app.file1_data,
+        &app.file2_data,
+        &app.ignore,
+    )))
+}
+
+/// Explain that reading `path` failed and why.
+fn read_error(path: &str, err: io::Error) -> String {
+    let message = err.to_string();
+    // Drop the "(os error 2)" suffix, which only repeats the reason
+    let reason = match message.find(" (os error") {
+        Some(end) => &message[..end],
+        None => &message,
+    };
+    format!("could not read {}: {}", path, reason)
+}
+
+/// Fill in the settings left off the command line from the environment. Each
+/// setting is resolved in this order: command-line flag, then environment
+/// variable, then config file (not supported yet), then the built-in default.
+fn resolve_settings(mut args: Args) -> Result<Args, String> {
+    if args.color.is_none() {
+        args.color = env_setting("DEAD_RINGER_COLOR", parse_color)?;
+    }
+    if args.width.is_none() {
+        args.width = env_setting("DEAD_RINGER_WIDTH", parse_width)?;
+    }
+    if args.theme.is_none() {
+        args.theme = env_setting("DEAD_RINGER_THEME", parse_theme)?;
+    }
+    Ok(args)
+}
+
+/// Read and parse the environment variable `name`, if it is set and not empty.
+fn env_setting<T>(name: &str, parse: fn(&str) -> Result<T, String>) -> Result<Option<T>, String> {
+    match std::env::var(name) {
+        Ok(value) if !value.is_empty() => parse(&value)
+            .map(Some)
+            .map_err(|err| format!("{} {}", name, err)),
+        _ => Ok(None),
+    }
+}
+
+/// Compare every pair of files listed in the manifest at `path`, printing a
+/// summary line per pair or, with `--batch`, each pair's diff under a
+/// header. Returns the exit code: 0 if every pair matches, 1 if any differ
+/// and 2 if the manifest or any file can't be read.
+fn run_manifest(path: &str, args: &Args) -> i32 {
+    let pairs = match std::fs::read_to_string(path)
+        .map_err(|err| err.to_string())
+        .and_then(|text| parse_manifest(&text))
+    {
+        Ok(pairs) => pairs,
+        Err(err) => {
+            eprintln!("could not load manifest {}: {}", path, err);
+            return 2;
+        }
+    };
+
+    let (mut identical, mut differing, mut failed) = (0, 0, 0);
+    for (file1, file2) in &pairs {
+        let (file1_data, file2_data) = match (read_file(file1), read_file(file2)) {
+            (Ok(file1_data), Ok(file2_data)) => (file1_data, file2_data),
+            (Err(err), _) | (_, Err(err)) => {
+                println!("{} {}: could not read: {}", file1, file2, err);
+                failed += 1;
+                if args.fail_fast {
+                    break;
+                }
+                continue;
+            }
+        };
+
+        let diff = diff_files_pairs_ignoring(&file1_data, &file2_data, &args.ignore);
+        let matches = diff.is_empty() && file1_data.len() == file2_data.len();
+        if matches {
+            identical += 1;
+        } else {
+            differing += 1;
+        }
+        if args.batch {
+            println!("== {} {}", file1, file2);
+            if args.align {
+                let mut changes = diff_aligned(&file1_data, &file2_data);
+                changes.retain(|change| !is_ignored(change.offset1, &args.ignore));
+                print!("{}", format_aligned(&changes, args.offset_base));
+            } else {
+                print!("{}", format_batch(&diff, args.offset_base));
+            }
+        } else if matches {
+            println!("{} {}: identical", file1, file2);
+        } else {
+            println!("{} {}: {} differing bytes", file1, file2, diff.len());
+        }
+    }
+
+    println!(
+        "{} identical, {} differing, {} unreadable",
+        identical, differing, failed
+    );
+    if failed > 0 {
+        2
+    } else if differing > 0 {
+        1
+    } else {
+        0
+    }
+}
+
+/// Load a JSON diff from `path`, reporting why it couldn't be loaded.
+fn load_json_diff(path: &str) -> Result<Vec<(usize, u8, u8)>, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|err| format!("could not read diff {}: {}", path, err))?;
+    parse_json(&contents).map_err(|err| format!("malformed diff {}: {}", path, err))
+}
+
+/// Print where two recorded JSON diffs of the same baseline diverge. Returns
+/// the exit code: 0 if they agree, 1 if they diverge and 2 on a load error.
+fn compare_diff_files(first: &str, second: &str) -> i32 {
+    let (first, second) = match (load_json_diff(first), load_json_diff(second)) {
+        (Ok(first), Ok(second)) => (first, second),
+        (Err(err), _) | (_, Err(err)) => {
+            eprintln!("{}", err);
+            return 2;
+        }
+    };
+
+    let divergences = diff_of_diffs(&first, &second);
+    for divergence in &divergences {
+        println!("{}", divergence);
+    }
+    i32::from(!divergences.is_empty())
+}
+
+/// Compare the diff against a recorded JSON diff, printing any discrepancies.
+/// Returns the exit code: 0 on a match, 1 on a mismatch and 2 if the recorded
+/// diff cannot be loaded.
+fn check_expected(path: &str, pairs: &[(usize, u8, u8)]) -> i32 {
+    let expected = match load_json_diff(path) {
+        Ok(expected) => expected,
+        Err(err) => {
+            eprintln!("{}", err);
+            return 2;
+        }
+    };
+
+    let mismatches = compare_diffs(&expected, pairs);
+    for mismatch in &mismatches {
+        println!("{}", mismatch);
+    }
+    if mismatches.is_empty() {
+        0
+    } else {
+        println!("{} discrepancies against {}", mismatches.len(), path);
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write `contents` to a file in the temp directory unique to this test
+    /// run, returning its path.
+    fn temp_file(name: &str, contents: &str) -> String {
+        let path = std::env::temp_dir().join(format!("dring-{}-{}", std::process::id(), name));
+        std::fs::write(&path, contents).expect("temp dir is writable");
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn expected_diff_exit_codes() {
+        let pairs = [(3, 0x61, 0x62)];
+        let recorded = temp_file(
+            "expect-match.json",
+            r#"[{"offset": 3, "old": 97, "new": 98}]"#,
+        );
+        assert_eq!(check_expected(&recorded, &pairs), 0);
+
+        let other = temp_file(
+            "expect-mismatch.json",
+            r#"[{"offset": 4, "old": 97, "new": 98}]"#,
+        );
+        assert_eq!(check_expected(&other, &pairs), 1);
+
+        let malformed = temp_file("expect-malformed.json", r#"[{"offset": 3, "old": 97"#);
+        assert_eq!(check_expected(&malformed, &pairs), EXIT_TROUBLE);
+        assert!(load_json_diff(&malformed)
+            .unwrap_err()
+            .starts_with("malformed diff"));
+
+        let missing = format!("{}-gone", recorded);
+        assert_eq!(check_expected(&missing, &pairs), EXIT_TROUBLE);
+
+        for path in [recorded, other, malformed] {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn manifest_exit_codes() {
+        let args = parse_args(["dring", "--manifest", "unused"]).unwrap();
+        let same1 = temp_file("manifest-same1", "abc");
+        let same2 = temp_file("manifest-same2", "abc");
+        let longer = temp_file("manifest-longer", "abcd");
+        let identical = temp_file("manifest-identical", &format!("{} {}\n", same1, same2));
+        assert_eq!(run_manifest(&identical, &args), 0);
+
+        let differing = temp_file(
+            "manifest-differing",
+            &format!("# lengths differ\n{} {}\n{} {}\n", same1, same2, same1, longer),
+        );
+        assert_eq!(run_manifest(&differing, &args), 1);
+
+        let missing = temp_file(
+            "manifest-missing",
+            &format!("{} {}\n{} does-not-exist\n", same1, longer, same1),
+        );
+        assert_eq!(run_manifest(&missing, &args), 2);
+
+        let malformed = temp_file("manifest-malformed", "one-file-only\n");
+        assert_eq!(run_manifest(&malformed, &args), 2);
+        assert_eq!(run_manifest("does-not-exist.manifest", &args), 2);
+    }
 }
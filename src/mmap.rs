@@ -0,0 +1,75 @@
+use std::{fs::File, io, ops::Deref, os::unix::io::AsRawFd, ptr, slice};
+
+/// A whole file mapped read-only into memory, so its pages are read from
+/// disk as they are compared instead of all up front. As with any mapping,
+/// the bytes change if the file is written to while it's mapped, and
+/// reading past the end of a file truncated meanwhile raises SIGBUS.
+pub struct Mmap {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+impl Mmap {
+    /// Map all of `file`, or return `None` for one that can't be mapped: an
+    /// empty file, or something other than a regular file, like a pipe.
+    pub fn map(file: &File) -> io::Result<Option<Self>> {
+        let metadata = file.metadata()?;
+        if !metadata.is_file() || metadata.len() == 0 {
+            return Ok(None);
+        }
+        let len = usize::try_from(metadata.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "file too large to map"))?;
+        // SAFETY: a new private, read-only mapping of an open file, with a
+        // non-zero length; the kernel picks the address.
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Some(Self { ptr, len }))
+    }
+}
+
+impl Deref for Mmap {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: the mapping covers `len` readable bytes until it's dropped.
+        unsafe { slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        // SAFETY: unmaps exactly the mapping made in `map`, which no slice
+        // can outlive.
+        unsafe {
+            libc::munmap(self.ptr, self.len);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_a_file_and_skips_empty_ones() {
+        let path = std::env::temp_dir().join(format!("dring-mmap-{}", std::process::id()));
+        std::fs::write(&path, b"\x00mapped\xff").unwrap();
+        let map = Mmap::map(&File::open(&path).unwrap()).unwrap().unwrap();
+        assert_eq!(&*map, b"\x00mapped\xff");
+
+        std::fs::write(&path, b"").unwrap();
+        assert!(Mmap::map(&File::open(&path).unwrap()).unwrap().is_none());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
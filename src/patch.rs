@@ -0,0 +1,173 @@
+use crate::file::iter_diff_pairs;
+
+/// Magic bytes identifying a dead-ringer patch, followed by a format version.
+const MAGIC: &[u8; 5] = b"DRNG\x01";
+
+/// A byte-level patch reconstructing one file from another.
+#[derive(Debug, PartialEq)]
+pub struct Patch {
+    /// Length of the reconstructed file.
+    pub len: usize,
+    /// Offsets within the shared length and the byte they change to.
+    pub records: Vec<(usize, u8)>,
+    /// Bytes appended past the end of the original file.
+    pub tail: Vec<u8>,
+}
+
+impl Patch {
+    /// Build the patch turning `file1` into `file2`. It always records every
+    /// difference, whatever the viewer is hiding with `--ignore`, so applying
+    /// it rebuilds `file2` exactly.
+    pub fn new(file1: &[u8], file2: &[u8]) -> Self {
+        Self {
+            len: file2.len(),
+            records: iter_diff_pairs(file1, file2)
+                .map(|(offset, _, new)| (offset, new))
+                .collect(),
+            tail: file2.get(file1.len()..).unwrap_or_default().to_vec(),
+        }
+    }
+
+    /// Encode the patch: the magic, the target length, the record count, each
+    /// record as a little-endian `u64` offset and its byte, then the tail.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut out =
+            Vec::with_capacity(MAGIC.len() + 16 + self.records.len() * 9 + self.tail.len());
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&(self.len as u64).to_le_bytes());
+        out.extend_from_slice(&(self.records.len() as u64).to_le_bytes());
+        for &(offset, byte) in &self.records {
+            out.extend_from_slice(&(offset as u64).to_le_bytes());
+            out.push(byte);
+        }
+        out.extend_from_slice(&self.tail);
+        out
+    }
+
+    /// Decode a patch written by [`Patch::serialize`].
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        let rest = data
+            .strip_prefix(MAGIC)
+            .ok_or_else(|| "not a dead-ringer patch".to_string())?;
+        let (len, rest) = read_u64(rest)?;
+        let (count, mut rest) = read_u64(rest)?;
+
+        let mut records = Vec::new();
+        for _ in 0..count {
+            let (offset, after) = read_u64(rest)?;
+            let (&byte, after) = after
+                .split_first()
+                .ok_or_else(|| "patch is truncated".to_string())?;
+            records.push((offset, byte));
+            rest = after;
+        }
+        Ok(Self {
+            len,
+            records,
+            tail: rest.to_vec(),
+        })
+    }
+
+    /// Reconstruct the patched file from `file1`.
+    pub fn apply(&self, file1: &[u8]) -> Result<Vec<u8>, String> {
+        let mut out = file1[..file1.len().min(self.len)].to_vec();
+        for &(offset, byte) in &self.records {
+            let slot = out.get_mut(offset).ok_or_else(|| {
+                format!("patch offset {:08x} is past the end of the input", offset)
+            })?;
+            *slot = byte;
+        }
+        out.extend_from_slice(&self.tail);
+        if out.len() != self.len {
+            return Err(format!(
+                "patch expects a {} byte result but produced {} bytes",
+                self.len,
+                out.len()
+            ));
+        }
+        Ok(out)
+    }
+}
+
+/// Split a little-endian `u64` off the front of `data`.
+fn read_u64(data: &[u8]) -> Result<(usize, &[u8]), String> {
+    if data.len() < 8 {
+        return Err("patch is truncated".to_string());
+    }
+    let (bytes, rest) = data.split_at(8);
+    let value = u64::from_le_bytes(bytes.try_into().expect("slice is 8 bytes"));
+    let value = usize::try_from(value).map_err(|_| "patch offset is too large".to_string())?;
+    Ok((value, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::diff_files_pairs_ignoring;
+
+    /// Serialize the patch from `file1` to `file2`, parse it back and apply
+    /// it to `file1`.
+    fn round_trip(file1: &[u8], file2: &[u8]) -> Vec<u8> {
+        let patch = Patch::new(file1, file2);
+        let parsed = Patch::parse(&patch.serialize()).expect("patch parses");
+        assert_eq!(parsed, patch);
+        parsed.apply(file1).expect("patch applies")
+    }
+
+    #[test]
+    fn round_trip_rebuilds_the_second_file() {
+        let cases: [(&[u8], &[u8]); 6] = [
+            (b"hello world", b"hellO World"),
+            (b"same", b"same"),
+            (b"short", b"shorter and longer"),
+            (b"much longer input", b"much"),
+            (b"", b"from nothing"),
+            (b"to nothing", b""),
+        ];
+        for (file1, file2) in cases {
+            assert_eq!(round_trip(file1, file2), file2);
+        }
+    }
+
+    #[test]
+    fn round_trip_keeps_differences_hidden_by_ignore() {
+        let (file1, file2) = (b"header v1 body".as_slice(), b"HEADER v2 body".as_slice());
+        let ignore = vec![0..6, 20..30];
+        // The viewer only shows the version byte...
+        assert_eq!(diff_files_pairs_ignoring(file1, file2, &ignore).len(), 1);
+        // ...but the patch still rebuilds the ignored header
+        assert_eq!(round_trip(file1, file2), file2);
+    }
+
+    #[test]
+    fn rejects_a_bad_magic() {
+        let mut data = Patch::new(b"a", b"a").serialize();
+        data[0] = b'X';
+        assert_eq!(
+            Patch::parse(&data),
+            Err("not a dead-ringer patch".to_string())
+        );
+        // A patch from a newer version of the format
+        data[0] = b'D';
+        data[4] = 2;
+        assert!(Patch::parse(&data).is_err());
+    }
+
+    #[test]
+    fn rejects_a_truncated_patch() {
+        let data = Patch::new(b"abc", b"xyz").serialize();
+        // Cut inside the header, then inside the last record
+        for len in [MAGIC.len() + 4, MAGIC.len() + 16 + 9 * 2 + 3] {
+            assert_eq!(
+                Patch::parse(&data[..len]),
+                Err("patch is truncated".to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn refuses_an_input_it_does_not_fit() {
+        let patch = Patch::new(b"abcdef", b"abcdeF");
+        assert!(patch.apply(b"abc").is_err());
+    }
+}
@@ -0,0 +1,230 @@
+use std::io::{self, Write};
+
+use ratatui::style::{Color, Modifier, Style};
+
+use crate::{
+    clipboard::{format_ascii, format_hex},
+    export::{format_offset, OffsetBase},
+    theme::Theme,
+};
+
+/// Bytes shown per line of the printed hexdump.
+const BYTES_PER_LINE: usize = 16;
+
+/// Format the diff as a `hexdump -C`-style listing of `data`, the second file,
+/// covering every line with a change. Changed bytes are colored by the
+/// `theme`'s [`Theme::byte_style`] and the rest dimmed; without a theme,
+/// changed bytes are marked with `*` instead. Skipped lines are shown as a
+/// single `*` line.
+pub fn format_print(data: &[u8], pairs: &[(usize, u8, u8)], theme: Option<&Theme>) -> String {
+    let mut out = String::new();
+    let mut changed = pairs.iter().map(|&(offset, _, _)| offset).peekable();
+    let mut next_line = 0;
+    while let Some(&offset) = changed.peek() {
+        let start = offset - offset % BYTES_PER_LINE;
+        if start >= data.len() {
+            break;
+        }
+        if start > next_line {
+            out.push_str("*\n");
+        }
+        let end = (start + BYTES_PER_LINE).min(data.len());
+
+        let mut hex = String::new();
+        let mut ascii = String::new();
+        for (offset, &byte) in (start..end).zip(&data[start..end]) {
+            let marked = changed.next_if(|&o| o == offset).is_some();
+            let c = if byte.is_ascii_graphic() || byte == b' ' {
+                byte as char
+            } else {
+                '.'
+            };
+            let Some(theme) = theme else {
+                hex.push_str(&format!("{}{:02x}", if marked { '*' } else { ' ' }, byte));
+                ascii.push(c);
+                continue;
+            };
+            let style = if marked {
+                theme.byte_style(byte).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().add_modifier(Modifier::DIM)
+            };
+            hex.push_str(&format!(" {}", paint(&format!("{:02x}", byte), style)));
+            ascii.push_str(&paint(&c.to_string(), style));
+        }
+
+        let padding = " ".repeat((BYTES_PER_LINE - (end - start)) * 3);
+        let offset = format!("{:08x}", start);
+        let offset = match theme {
+            Some(theme) => paint(&offset, theme.gutter),
+            None => offset,
+        };
+        out.push_str(&format!("{} {}{}  |{}|\n", offset, hex, padding, ascii));
+        next_line = end;
+    }
+    out
+}
+
+/// Write the differing bytes of the first file to `out` as a plain hexdump,
+/// 16 diff entries per line, each line starting with the file offset of its
+/// first entry. Lines are written as the diffs come, so they can stream.
+pub fn write_plain<W: Write>(
+    out: &mut W,
+    diffs: impl IntoIterator<Item = (usize, u8)>,
+    base: OffsetBase,
+) -> io::Result<()> {
+    let mut diffs = diffs.into_iter().peekable();
+    let mut bytes = Vec::with_capacity(BYTES_PER_LINE);
+    while let Some(&(offset, _)) = diffs.peek() {
+        bytes.clear();
+        bytes.extend(diffs.by_ref().take(BYTES_PER_LINE).map(|(_, byte)| byte));
+        writeln!(
+            out,
+            "{}  {:<width$}  |{}|",
+            format_offset(offset, base),
+            format_hex(&bytes),
+            // Whitespace would break the line apart
+            format_ascii(&bytes, false),
+            width = BYTES_PER_LINE * 3 - 1
+        )?;
+    }
+    Ok(())
+}
+
+/// Format the changed bytes of the second file in `xxd`'s hexdump format, so
+/// `xxd -r` can write them over a copy of the first file. Each line holds a
+/// run of adjacent changes within one 16-byte block, at its real offset, so a
+/// diff of every byte reads exactly like `xxd`'s own output.
+pub fn format_xxd(pairs: &[(usize, u8, u8)]) -> String {
+    let mut out = String::new();
+    let mut start = 0;
+    while start < pairs.len() {
+        let first = pairs[start].0;
+        let len = pairs[start..]
+            .iter()
+            .enumerate()
+            .take_while(|&(i, &(offset, _, _))| {
+                offset == first + i && offset / BYTES_PER_LINE == first / BYTES_PER_LINE
+            })
+            .count();
+        let bytes: Vec<u8> = pairs[start..start + len]
+            .iter()
+            .map(|&(_, _, new)| new)
+            .collect();
+        // xxd groups the hex digits two bytes at a time
+        let groups: Vec<String> = bytes
+            .chunks(2)
+            .map(|group| format_hex(group).replace(' ', ""))
+            .collect();
+        out.push_str(&format!(
+            "{:08x}: {:<width$}  {}\n",
+            first,
+            groups.join(" "),
+            // xxd -r reads one entry per line
+            format_ascii(&bytes, false),
+            width = BYTES_PER_LINE / 2 * 5 - 1
+        ));
+        start += len;
+    }
+    out
+}
+
+/// Wrap `text` in the ANSI escape codes for `style`, resetting afterwards.
+fn paint(text: &str, style: Style) -> String {
+    let mut codes = Vec::new();
+    if style.add_modifier.contains(Modifier::BOLD) {
+        codes.push("1".to_string());
+    }
+    if style.add_modifier.contains(Modifier::DIM) {
+        codes.push("2".to_string());
+    }
+    if style.add_modifier.contains(Modifier::UNDERLINED) {
+        codes.push("4".to_string());
+    }
+    if style.add_modifier.contains(Modifier::REVERSED) {
+        codes.push("7".to_string());
+    }
+    if let Some(fg) = style.fg.and_then(ansi_color) {
+        codes.push(fg.to_string());
+    }
+    if codes.is_empty() {
+        return text.to_string();
+    }
+    format!("\x1b[{}m{}\x1b[0m", codes.join(";"), text)
+}
+
+/// ANSI foreground code for the basic colors used by the viewer.
+fn ansi_color(color: Color) -> Option<u8> {
+    match color {
+        Color::Black => Some(30),
+        Color::Red => Some(31),
+        Color::Green => Some(32),
+        Color::Yellow => Some(33),
+        Color::Blue => Some(34),
+        Color::Magenta => Some(35),
+        Color::Cyan => Some(36),
+        Color::Gray => Some(37),
+        Color::DarkGray => Some(90),
+        Color::LightRed => Some(91),
+        Color::LightGreen => Some(92),
+        Color::LightYellow => Some(93),
+        Color::LightBlue => Some(94),
+        Color::LightMagenta => Some(95),
+        Color::LightCyan => Some(96),
+        Color::White => Some(97),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn print_marks_changed_bytes_and_skips_unchanged_lines() {
+        assert_eq!(
+            format_print(b"hello world!", &[(4, b'x', b'o')], None),
+            "00000000  68 65 6c 6c*6f 20 77 6f 72 6c 64 21              |hello world!|\n"
+        );
+
+        let data = b"0123456789abcdef".repeat(3);
+        assert_eq!(
+            format_print(&data, &[(1, b'x', b'1'), (34, b'x', b'2')], None),
+            "00000000  30*31 32 33 34 35 36 37 38 39 61 62 63 64 65 66  |0123456789abcdef|\n\
+             *\n\
+             00000020  30 31*32 33 34 35 36 37 38 39 61 62 63 64 65 66  |0123456789abcdef|\n"
+        );
+    }
+
+    #[test]
+    fn print_of_identical_files_is_empty() {
+        assert_eq!(format_print(b"same", &[], None), "");
+        assert_eq!(format_print(b"same", &[], Some(&Theme::default())), "");
+    }
+
+    #[test]
+    fn xxd_lines_match_xxd_for_a_whole_block() {
+        let pairs: Vec<(usize, u8, u8)> = b"0123456789abcdef"
+            .iter()
+            .enumerate()
+            .map(|(offset, &new)| (offset, 0, new))
+            .collect();
+        assert_eq!(
+            format_xxd(&pairs),
+            "00000000: 3031 3233 3435 3637 3839 6162 6364 6566  0123456789abcdef\n"
+        );
+        assert_eq!(format_xxd(&[]), "");
+    }
+
+    #[test]
+    fn xxd_lines_break_at_gaps_and_block_boundaries() {
+        let pairs = [(14, 0, b'A'), (15, 0, b'B'), (16, 0, b'C'), (20, b'x', 0)];
+        assert_eq!(
+            format_xxd(&pairs),
+            format!(
+                "0000000e: {:<39}  AB\n00000010: {:<39}  C\n00000014: {:<39}  .\n",
+                "4142", "43", "00"
+            )
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use std::ops::Range;
+
+/// Index of the region shown after `tick` ticks when each region stays on
+/// screen for `ticks_per_region` ticks. The replay loops back to the first
+/// region after the last. Returns `None` if there are no regions.
+pub fn region_at(tick: u64, ticks_per_region: u64, count: usize) -> Option<usize> {
+    if count == 0 {
+        return None;
+    }
+    let step = tick / ticks_per_region.max(1);
+    Some((step % count as u64) as usize)
+}
+
+/// A timed walkthrough of the diff regions, one region at a time.
+#[derive(Debug)]
+pub struct Replay {
+    regions: Vec<Range<usize>>,
+    ticks_per_region: u64,
+    elapsed: u64,
+    pub paused: bool,
+}
+
+impl Replay {
+    pub fn new(regions: Vec<Range<usize>>, ticks_per_region: u64) -> Self {
+        Self {
+            regions,
+            ticks_per_region,
+            elapsed: 0,
+            paused: false,
+        }
+    }
+
+    /// Index of the region currently shown.
+    pub fn current(&self) -> Option<usize> {
+        region_at(self.elapsed, self.ticks_per_region, self.regions.len())
+    }
+
+    /// Diff indices covered by the region currently shown.
+    pub fn current_region(&self) -> Option<Range<usize>> {
+        self.current().map(|idx| self.regions[idx].clone())
+    }
+
+    /// Number of regions in the walkthrough.
+    pub fn len(&self) -> usize {
+        self.regions.len()
+    }
+
+    /// Advance the clock unless paused. Returns whether the shown region
+    /// changed.
+    pub fn tick(&mut self) -> bool {
+        if self.paused {
+            return false;
+        }
+        let before = self.current();
+        self.elapsed += 1;
+        self.current() != before
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regions_advance_every_few_ticks_and_loop() {
+        assert_eq!(region_at(0, 3, 2), Some(0));
+        assert_eq!(region_at(2, 3, 2), Some(0));
+        assert_eq!(region_at(3, 3, 2), Some(1));
+        assert_eq!(region_at(6, 3, 2), Some(0));
+        // A zero rate advances every tick instead of dividing by zero
+        assert_eq!(region_at(5, 0, 4), Some(1));
+        assert_eq!(region_at(7, 3, 0), None);
+    }
+
+    #[test]
+    fn ticks_step_through_the_regions_unless_paused() {
+        let mut replay = Replay::new(vec![0..2, 2..3, 3..7], 2);
+        assert_eq!(replay.len(), 3);
+        assert_eq!(replay.current_region(), Some(0..2));
+        assert!(!replay.tick());
+        assert!(replay.tick());
+        assert_eq!(replay.current_region(), Some(2..3));
+
+        replay.toggle_pause();
+        assert!(!replay.tick());
+        assert!(!replay.tick());
+        assert_eq!(replay.current(), Some(1));
+
+        replay.toggle_pause();
+        replay.tick();
+        assert!(replay.tick());
+        assert_eq!(replay.current_region(), Some(3..7));
+        replay.tick();
+        assert!(replay.tick());
+        assert_eq!(replay.current(), Some(0));
+    }
+
+    #[test]
+    fn an_empty_replay_shows_nothing() {
+        let mut replay = Replay::new(vec![], 2);
+        assert!(!replay.tick());
+        assert_eq!(replay.current_region(), None);
+    }
+}
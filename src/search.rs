@@ -0,0 +1,523 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// What a search query is matched as.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SearchKind {
+    /// The query's characters, as bytes.
+    Ascii,
+    /// The query as hex byte pairs, e.g. `de ad be ef`, where `??` matches
+    /// any byte.
+    Hex,
+}
+
+/// Which bytes a search looks through.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SearchScope {
+    /// The differing bytes, as shown in the viewer.
+    #[default]
+    Diffs,
+    /// All of the first file.
+    File1,
+    /// All of the second file.
+    File2,
+}
+
+impl SearchScope {
+    /// The scope after this one, cycling back to the diffs.
+    pub fn next(self) -> Self {
+        match self {
+            SearchScope::Diffs => SearchScope::File1,
+            SearchScope::File1 => SearchScope::File2,
+            SearchScope::File2 => SearchScope::Diffs,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SearchScope::Diffs => "diffs",
+            SearchScope::File1 => "file1",
+            SearchScope::File2 => "file2",
+        }
+    }
+}
+
+/// The bytes handed to a search, matching its [`SearchScope`].
+pub enum SearchSource<'a> {
+    Diffs(&'a [(usize, u8)]),
+    File(&'a [u8]),
+}
+
+/// How far a hex query is from being a searchable pattern.
+#[derive(Debug, PartialEq)]
+pub enum HexQueryState {
+    /// Every digit pairs up into a byte.
+    Complete,
+    /// The last byte is missing its second digit, fine while typing.
+    Incomplete,
+    /// The query contains a character that isn't a hex digit, `?` or
+    /// whitespace, or a byte with only one wildcard digit.
+    Invalid(char),
+}
+
+/// Classify a hex query, ignoring whitespace between digits.
+pub fn hex_query_state(query: &str) -> HexQueryState {
+    let digits: Vec<char> = query.chars().filter(|c| !c.is_whitespace()).collect();
+    if let Some(&c) = digits.iter().find(|&&c| !c.is_ascii_hexdigit() && c != '?') {
+        return HexQueryState::Invalid(c);
+    }
+    // Only whole bytes can be wildcards.
+    if digits
+        .chunks_exact(2)
+        .any(|pair| (pair[0] == '?') != (pair[1] == '?'))
+    {
+        return HexQueryState::Invalid('?');
+    }
+    if digits.len().is_multiple_of(2) {
+        HexQueryState::Complete
+    } else {
+        HexQueryState::Incomplete
+    }
+}
+
+/// Validator for the hex search prompt: only complete queries are submitted,
+/// so a typo never silently searches for a truncated pattern.
+pub fn validate_hex_query(query: &str) -> Result<(), String> {
+    match hex_query_state(query) {
+        HexQueryState::Complete => Ok(()),
+        HexQueryState::Incomplete => Err("incomplete byte".to_string()),
+        HexQueryState::Invalid(c) => Err(format!("invalid char '{}'", c)),
+    }
+}
+
+/// Parse a hex string such as `e8 ?? ?? 5d` into a pattern, where `None`
+/// matches any byte. Characters that aren't hex digits or `?` and a trailing
+/// unpaired digit are dropped.
+pub fn parse_hex_pattern(query: &str) -> Vec<Option<u8>> {
+    let digits: Vec<Option<u8>> = query
+        .chars()
+        .filter(|&c| c.is_ascii_hexdigit() || c == '?')
+        .map(|c| c.to_digit(16).map(|d| d as u8))
+        .collect();
+    digits
+        .chunks_exact(2)
+        .map(|pair| Some((pair[0]? << 4) | pair[1]?))
+        .collect()
+}
+
+/// Return the indices at which `pattern` starts in `bytes`, including
+/// overlapping matches. `None` in the pattern matches any byte.
+pub fn find_matches(bytes: &[u8], pattern: &[Option<u8>]) -> Vec<usize> {
+    if pattern.is_empty() || pattern.len() > bytes.len() {
+        return Vec::new();
+    }
+    match pattern {
+        [Some(byte)] => bytes
+            .iter()
+            .enumerate()
+            .filter(|&(_, b)| b == byte)
+            .map(|(i, _)| i)
+            .collect(),
+        _ => horspool(bytes, pattern),
+    }
+}
+
+/// Boyer-Moore-Horspool search for `pattern` in `bytes`, where `None`
+/// matches any byte. After each window the search skips ahead by how far the
+/// window's last byte is from its last occurrence earlier in the pattern, a
+/// distance that can't jump over a match, so overlapping matches are found
+/// too.
+fn horspool(bytes: &[u8], pattern: &[Option<u8>]) -> Vec<usize> {
+    let last = pattern.len() - 1;
+    // A wildcard matches every byte, so no byte may skip past it
+    let wildcard_shift = pattern[..last]
+        .iter()
+        .rposition(Option::is_none)
+        .map_or(pattern.len(), |i| last - i);
+    let mut shifts = [wildcard_shift; 256];
+    for (i, want) in pattern[..last].iter().enumerate() {
+        if let Some(byte) = want {
+            shifts[*byte as usize] = shifts[*byte as usize].min(last - i);
+        }
+    }
+
+    let mut matches = Vec::new();
+    let mut start = 0;
+    while start + last < bytes.len() {
+        let window = &bytes[start..=start + last];
+        if window
+            .iter()
+            .zip(pattern)
+            .all(|(byte, want)| want.is_none_or(|want| *byte == want))
+        {
+            matches.push(start);
+        }
+        start += shifts[window[last] as usize];
+    }
+    matches
+}
+
+/// Most queries kept per search kind.
+const HISTORY_LEN: usize = 100;
+
+/// Submitted search queries, oldest first, kept separately for each kind.
+#[derive(Debug, Default)]
+pub struct SearchHistory {
+    ascii: Vec<String>,
+    hex: Vec<String>,
+}
+
+impl SearchHistory {
+    fn queries_mut(&mut self, kind: SearchKind) -> &mut Vec<String> {
+        match kind {
+            SearchKind::Ascii => &mut self.ascii,
+            SearchKind::Hex => &mut self.hex,
+        }
+    }
+
+    /// The queries of `kind`, oldest first.
+    pub fn queries(&self, kind: SearchKind) -> &[String] {
+        match kind {
+            SearchKind::Ascii => &self.ascii,
+            SearchKind::Hex => &self.hex,
+        }
+    }
+
+    /// Add a submitted query, unless it repeats the last one, dropping the
+    /// oldest once there are more than [`HISTORY_LEN`].
+    pub fn remember(&mut self, kind: SearchKind, query: &str) {
+        let queries = self.queries_mut(kind);
+        if query.is_empty() || queries.last().is_some_and(|last| last == query) {
+            return;
+        }
+        queries.push(query.to_string());
+        if queries.len() > HISTORY_LEN {
+            queries.remove(0);
+        }
+    }
+
+    /// Where the history is kept between sessions:
+    /// `$XDG_DATA_HOME/dead-ringer/history`, falling back to
+    /// `~/.local/share/dead-ringer/history`.
+    pub fn path() -> Option<PathBuf> {
+        let data_home = std::env::var_os("XDG_DATA_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| {
+                std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/share"))
+            })?;
+        Some(data_home.join("dead-ringer").join("history"))
+    }
+
+    /// Parse a saved history, one `ascii` or `hex` tag, a tab and the query
+    /// per line. Lines that don't fit are skipped.
+    pub fn parse(text: &str) -> Self {
+        let mut history = Self::default();
+        for line in text.lines() {
+            let (kind, query) = match line.split_once('\t') {
+                Some(("ascii", query)) => (SearchKind::Ascii, query),
+                Some(("hex", query)) => (SearchKind::Hex, query),
+                _ => continue,
+            };
+            history.remember(kind, query);
+        }
+        history
+    }
+
+    /// Serialize the history in the layout read by [`SearchHistory::parse`].
+    pub fn serialize(&self) -> String {
+        self.ascii
+            .iter()
+            .map(|query| format!("ascii\t{}\n", query))
+            .chain(self.hex.iter().map(|query| format!("hex\t{}\n", query)))
+            .collect()
+    }
+
+    /// Load the saved history, or start an empty one if there is none.
+    pub fn load() -> Self {
+        Self::path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map_or_else(Self::default, |text| Self::parse(&text))
+    }
+
+    /// Save the history for the next session.
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.serialize())
+    }
+}
+
+/// The last submitted search and its matches: diff indices when searching
+/// the diffs, file offsets when searching a whole file.
+#[derive(Debug, Default)]
+pub struct SearchState {
+    pub query: String,
+    pub kind: Option<SearchKind>,
+    pub pattern_len: usize,
+    pub matches: Vec<usize>,
+    pub current_match: Option<usize>,
+    pub scope: SearchScope,
+    pub history: SearchHistory,
+    /// The diff bytes searched, kept across searches until the diff changes.
+    bytes: Option<Vec<u8>>,
+}
+
+impl SearchState {
+    /// Search the bytes of `source` for `query`, selecting the first match.
+    pub fn submit(&mut self, query: &str, kind: SearchKind, source: SearchSource) {
+        let pattern = match kind {
+            SearchKind::Ascii => query.bytes().map(Some).collect(),
+            SearchKind::Hex => parse_hex_pattern(query),
+        };
+        self.query = query.to_string();
+        self.kind = Some(kind);
+        self.pattern_len = pattern.len();
+        self.matches = match source {
+            SearchSource::Diffs(diffs) => {
+                let bytes = self
+                    .bytes
+                    .get_or_insert_with(|| diffs.iter().map(|&(_, b)| b).collect());
+                find_matches(bytes, &pattern)
+            }
+            SearchSource::File(data) => find_matches(data, &pattern),
+        };
+        self.current_match = if self.matches.is_empty() {
+            None
+        } else {
+            Some(0)
+        };
+    }
+
+    /// Select the next match, wrapping around to the first.
+    pub fn next_match(&mut self) {
+        if let Some(current) = self.current_match {
+            self.current_match = Some((current + 1) % self.matches.len());
+        }
+    }
+
+    /// Select the previous match, wrapping around to the last.
+    pub fn prev_match(&mut self) {
+        if let Some(current) = self.current_match {
+            self.current_match = Some(current.checked_sub(1).unwrap_or(self.matches.len() - 1));
+        }
+    }
+
+    /// Select the `n`th match, counting from 1. Numbers past the last match
+    /// select the last one.
+    pub fn goto_match(&mut self, n: usize) {
+        if !self.matches.is_empty() {
+            self.current_match = Some(n.clamp(1, self.matches.len()) - 1);
+        }
+    }
+
+    /// Position among the matches for the info bar, e.g. `[3/17]`, or
+    /// `[no matches]`. `None` when there is no search.
+    pub fn indicator(&self) -> Option<String> {
+        if self.kind.is_none() || self.query.is_empty() {
+            return None;
+        }
+        Some(match self.current_match {
+            Some(current) => format!("[{}/{}]", current + 1, self.matches.len()),
+            None => "[no matches]".to_string(),
+        })
+    }
+
+    /// Where the selected match starts: a diff index, or a file offset when
+    /// searching a whole file.
+    pub fn current_match_pos(&self) -> Option<usize> {
+        self.current_match.map(|current| self.matches[current])
+    }
+
+    /// Whether the diff at index `pos` and file offset `offset` falls inside
+    /// any match, and if so whether that match is the selected one.
+    pub fn match_at(&self, pos: usize, offset: usize) -> Option<bool> {
+        let pos = match self.scope {
+            SearchScope::Diffs => pos,
+            SearchScope::File1 | SearchScope::File2 => offset,
+        };
+        let idx = self.matches.partition_point(|&start| start <= pos);
+        let start = *self.matches[..idx].last()?;
+        if pos >= start + self.pattern_len {
+            return None;
+        }
+        let current = self
+            .current_match_pos()
+            .is_some_and(|current| current <= pos && pos < current + self.pattern_len);
+        Some(current)
+    }
+
+    /// Forget the search and its matches.
+    pub fn cancel(&mut self) {
+        *self = Self {
+            bytes: self.bytes.take(),
+            scope: self.scope,
+            history: std::mem::take(&mut self.history),
+            ..Self::default()
+        };
+    }
+
+    /// Forget the search and the cached diff bytes, for when the diff
+    /// changes.
+    pub fn invalidate(&mut self) {
+        *self = Self {
+            scope: self.scope,
+            history: std::mem::take(&mut self.history),
+            ..Self::default()
+        };
+    }
+}
+
+/// Number of bytes shown from the start of each match in the match list.
+pub const PREVIEW_LEN: usize = 8;
+
+/// Preview of the bytes at the start of a match, as hex pairs followed by
+/// their ASCII rendering, padded to `len` bytes.
+pub fn match_preview(bytes: &[u8], len: usize) -> String {
+    let hex: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    let ascii: String = bytes
+        .iter()
+        .map(|&b| if b.is_ascii_graphic() { b as char } else { '.' })
+        .collect();
+    format!(
+        "{:<width$}  |{}|",
+        hex.join(" "),
+        ascii,
+        width = len * 3 - 1
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn previews_pad_short_matches_to_the_full_width() {
+        assert_eq!(
+            match_preview(b"ab\x00\xff", PREVIEW_LEN),
+            format!("61 62 00 ff{}  |ab..|", " ".repeat(12))
+        );
+        assert_eq!(
+            match_preview(b"01234567", PREVIEW_LEN),
+            "30 31 32 33 34 35 36 37  |01234567|"
+        );
+        assert_eq!(match_preview(b"", 2), "       ||");
+    }
+
+    /// Every start where the pattern fits and each byte matches.
+    fn naive_matches(bytes: &[u8], pattern: &[Option<u8>]) -> Vec<usize> {
+        if pattern.is_empty() || pattern.len() > bytes.len() {
+            return Vec::new();
+        }
+        (0..=bytes.len() - pattern.len())
+            .filter(|&start| {
+                pattern
+                    .iter()
+                    .zip(&bytes[start..])
+                    .all(|(want, byte)| want.is_none_or(|want| *byte == want))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finds_overlapping_matches() {
+        let pattern = [Some(b'a'), Some(b'a')];
+        assert_eq!(find_matches(b"aaaa", &pattern), vec![0, 1, 2]);
+        let pattern = [Some(b'a'), Some(b'b'), Some(b'a')];
+        assert_eq!(find_matches(b"ababa", &pattern), vec![0, 2]);
+    }
+
+    #[test]
+    fn pattern_longer_than_haystack_matches_nothing() {
+        let pattern = [Some(1), Some(2), Some(3)];
+        assert_eq!(find_matches(&[1, 2], &pattern), Vec::<usize>::new());
+        assert_eq!(find_matches(&[], &[Some(1)]), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn empty_pattern_matches_nothing() {
+        assert_eq!(find_matches(b"abc", &[]), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn wildcards_match_any_byte() {
+        let pattern = [Some(0xe8), None, None, Some(0x5d)];
+        let bytes = [0xe8, 0x01, 0x02, 0x5d, 0xe8, 0x5d, 0x5d, 0x5d];
+        assert_eq!(find_matches(&bytes, &pattern), vec![0, 4]);
+        // A wildcard last, first, or alone
+        assert_eq!(find_matches(b"abac", &[Some(b'a'), None]), vec![0, 2]);
+        assert_eq!(find_matches(b"abcb", &[None, Some(b'b')]), vec![0, 2]);
+        assert_eq!(find_matches(b"xyz", &[None]), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn odd_digit_count_is_incomplete() {
+        assert_eq!(hex_query_state("de a"), HexQueryState::Incomplete);
+        assert_eq!(
+            validate_hex_query("dea"),
+            Err("incomplete byte".to_string())
+        );
+        // The unpaired digit is dropped rather than guessed at
+        assert_eq!(parse_hex_pattern("dea"), vec![Some(0xde)]);
+    }
+
+    #[test]
+    fn non_hex_character_is_invalid() {
+        assert_eq!(hex_query_state("de g0"), HexQueryState::Invalid('g'));
+        assert_eq!(
+            validate_hex_query("0x12"),
+            Err("invalid char 'x'".to_string())
+        );
+    }
+
+    #[test]
+    fn whitespace_between_digits_is_ignored() {
+        assert_eq!(hex_query_state("  d e\tad\n"), HexQueryState::Complete);
+        assert_eq!(validate_hex_query(" de ad "), Ok(()));
+        assert_eq!(parse_hex_pattern(" d e  ad "), vec![Some(0xde), Some(0xad)]);
+        assert_eq!(hex_query_state(""), HexQueryState::Complete);
+    }
+
+    #[test]
+    fn wildcards_cover_whole_bytes() {
+        assert_eq!(hex_query_state("e8 ?? 5d"), HexQueryState::Complete);
+        assert_eq!(
+            parse_hex_pattern("e8 ?? 5d"),
+            vec![Some(0xe8), None, Some(0x5d)]
+        );
+        // Half a wildcard byte is an error, not a nibble mask
+        assert_eq!(hex_query_state("e8 ?5"), HexQueryState::Invalid('?'));
+        assert_eq!(hex_query_state("e8 ?"), HexQueryState::Incomplete);
+    }
+
+    #[test]
+    fn horspool_agrees_with_naive_search() {
+        let mut seed = 0x853c_49e6_748f_ea9b_u64;
+        let mut next = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed
+        };
+        for _ in 0..2_000 {
+            // A small alphabet so matches, overlapping ones included, are common
+            let bytes: Vec<u8> = (0..next() % 64).map(|_| (next() % 3) as u8).collect();
+            let pattern: Vec<Option<u8>> = (0..next() % 6)
+                .map(|_| (next() % 4 != 0).then(|| (next() % 3) as u8))
+                .collect();
+            assert_eq!(
+                find_matches(&bytes, &pattern),
+                naive_matches(&bytes, &pattern),
+                "{:?} in {:?}",
+                pattern,
+                bytes
+            );
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use std::ops::Range;
+
+/// A named section of two object files compared on its own, with where it
+/// starts in each file so section-relative offsets can be mapped back.
+#[derive(Clone, Debug)]
+pub struct Section {
+    pub name: String,
+    pub file1_start: usize,
+    pub file2_start: usize,
+}
+
+impl Section {
+    /// Cut the section `name` out of both files, returning it with the two
+    /// slices of section data.
+    pub fn extract(
+        name: &str,
+        file1: &[u8],
+        file2: &[u8],
+    ) -> Result<(Self, Vec<u8>, Vec<u8>), String> {
+        let range1 = find_section(file1, name)?;
+        let range2 = find_section(file2, name)?;
+        let section = Self {
+            name: name.to_string(),
+            file1_start: range1.start,
+            file2_start: range2.start,
+        };
+        Ok((section, file1[range1].to_vec(), file2[range2].to_vec()))
+    }
+}
+
+/// File range of the section called `name` in the ELF, PE or other object
+/// file in `data`.
+#[cfg(feature = "sections")]
+fn find_section(data: &[u8], name: &str) -> Result<Range<usize>, String> {
+    use object::{Object, ObjectSection};
+
+    let file =
+        object::File::parse(data).map_err(|err| format!("not a supported object file: {}", err))?;
+    let section = file
+        .section_by_name(name)
+        .ok_or_else(|| format!("no section named {}", name))?;
+    let (start, size) = section
+        .file_range()
+        .ok_or_else(|| format!("section {} has no data in the file", name))?;
+    let start = start as usize;
+    let end = start + size as usize;
+    if end > data.len() {
+        return Err(format!("section {} extends past the end of the file", name));
+    }
+    Ok(start..end)
+}
+
+#[cfg(not(feature = "sections"))]
+fn find_section(_data: &[u8], _name: &str) -> Result<Range<usize>, String> {
+    Err("--section requires dring to be built with the `sections` feature".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A minimal relocatable ELF object with a `.data` section holding `data`.
+    #[cfg(feature = "sections")]
+    fn elf_with_data(data: &[u8]) -> Vec<u8> {
+        use object::{write::Object, Architecture, BinaryFormat, Endianness, SectionKind};
+
+        let mut obj = Object::new(BinaryFormat::Elf, Architecture::X86_64, Endianness::Little);
+        let section = obj.add_section(Vec::new(), b".data".to_vec(), SectionKind::Data);
+        obj.append_section_data(section, data, 1);
+        obj.write().unwrap()
+    }
+
+    #[cfg(feature = "sections")]
+    #[test]
+    fn extracts_a_named_section_from_both_files() {
+        let file1 = elf_with_data(b"old bytes");
+        let file2 = elf_with_data(b"new bytes!");
+        let (section, data1, data2) = Section::extract(".data", &file1, &file2).unwrap();
+        assert_eq!(data1, b"old bytes");
+        assert_eq!(data2, b"new bytes!");
+        assert_eq!(section.name, ".data");
+        assert_eq!(&file1[section.file1_start..][..9], b"old bytes");
+        assert_eq!(&file2[section.file2_start..][..10], b"new bytes!");
+    }
+
+    #[cfg(feature = "sections")]
+    #[test]
+    fn unknown_sections_and_non_objects_are_errors() {
+        let file = elf_with_data(b"data");
+        assert_eq!(
+            Section::extract(".text", &file, &file).unwrap_err(),
+            "no section named .text"
+        );
+        assert!(Section::extract(".data", b"not an object", &file)
+            .unwrap_err()
+            .starts_with("not a supported object file"));
+    }
+
+    #[cfg(not(feature = "sections"))]
+    #[test]
+    fn sections_need_the_feature() {
+        assert!(Section::extract(".data", b"", b"")
+            .unwrap_err()
+            .contains("`sections` feature"));
+    }
+}
@@ -0,0 +1,224 @@
+use std::{collections::BTreeMap, fs, io};
+
+use crate::search::{SearchKind, SearchScope};
+
+/// Where a viewing session left off, saved with `--session` on quit and
+/// restored on the next launch.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Session {
+    pub file1: String,
+    pub file2: String,
+    /// Number of differences when the session was saved, to tell whether
+    /// the files have changed since.
+    pub diffs: usize,
+    /// File offset under the cursor.
+    pub cursor: Option<usize>,
+    /// Diff index at the start of the top row, which survives a different
+    /// terminal width.
+    pub top: usize,
+    pub bookmarks: Vec<usize>,
+    /// Notes by file offset.
+    pub annotations: BTreeMap<usize, String>,
+    pub search: Option<SavedSearch>,
+}
+
+/// The last submitted search of a session.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SavedSearch {
+    pub kind: SearchKind,
+    pub scope: SearchScope,
+    pub query: String,
+}
+
+impl Session {
+    /// Serialize the session as `key = value` lines, a small subset of TOML.
+    pub fn serialize(&self) -> String {
+        let mut out = format!(
+            "file1 = {}\nfile2 = {}\ndiffs = {}\n",
+            quote(&self.file1),
+            quote(&self.file2),
+            self.diffs
+        );
+        if let Some(cursor) = self.cursor {
+            out.push_str(&format!("cursor = {}\n", cursor));
+        }
+        let bookmarks: Vec<String> = self.bookmarks.iter().map(usize::to_string).collect();
+        out.push_str(&format!(
+            "top = {}\nbookmarks = [{}]\n",
+            self.top,
+            bookmarks.join(", ")
+        ));
+        for (offset, note) in &self.annotations {
+            out.push_str(&format!("note.{} = {}\n", offset, quote(note)));
+        }
+        if let Some(search) = &self.search {
+            let kind = match search.kind {
+                SearchKind::Ascii => "ascii",
+                SearchKind::Hex => "hex",
+            };
+            out.push_str(&format!(
+                "search_kind = {}\nsearch_scope = {}\nsearch = {}\n",
+                quote(kind),
+                quote(search.scope.label()),
+                quote(&search.query)
+            ));
+        }
+        out
+    }
+
+    /// Parse a session written by [`Session::serialize`]. Blank lines and
+    /// `#` comments are skipped, as are keys it doesn't know.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut session = Session::default();
+        let (mut kind, mut scope, mut query) = (None, SearchScope::Diffs, None);
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: &str| format!("line {}: {}", idx + 1, message);
+            let (key, value) = line
+                .split_once('=')
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .ok_or_else(|| error("expected key = value"))?;
+            let number = || value.parse().map_err(|_| error("expected a number"));
+            let string = || unquote(value).ok_or_else(|| error("expected a quoted string"));
+            match key {
+                "file1" => session.file1 = string()?,
+                "file2" => session.file2 = string()?,
+                "diffs" => session.diffs = number()?,
+                "cursor" => session.cursor = Some(number()?),
+                "top" => session.top = number()?,
+                "bookmarks" => {
+                    let list = value
+                        .strip_prefix('[')
+                        .and_then(|list| list.strip_suffix(']'))
+                        .ok_or_else(|| error("expected a list of offsets"))?;
+                    session.bookmarks = list
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|item| !item.is_empty())
+                        .map(|item| item.parse().map_err(|_| error("expected an offset")))
+                        .collect::<Result<_, _>>()?;
+                }
+                "search_kind" => {
+                    kind = Some(match string()?.as_str() {
+                        "ascii" => SearchKind::Ascii,
+                        "hex" => SearchKind::Hex,
+                        _ => return Err(error("expected ascii or hex")),
+                    })
+                }
+                "search_scope" => {
+                    scope = match string()?.as_str() {
+                        "diffs" => SearchScope::Diffs,
+                        "file1" => SearchScope::File1,
+                        "file2" => SearchScope::File2,
+                        _ => return Err(error("expected diffs, file1 or file2")),
+                    }
+                }
+                "search" => query = Some(string()?),
+                _ if key.starts_with("note.") => {
+                    let offset = key["note.".len()..]
+                        .parse()
+                        .map_err(|_| error("expected an offset after note."))?;
+                    session.annotations.insert(offset, string()?);
+                }
+                _ => {}
+            }
+        }
+        session.bookmarks.sort_unstable();
+        session.bookmarks.dedup();
+        if let (Some(kind), Some(query)) = (kind, query) {
+            session.search = Some(SavedSearch { kind, scope, query });
+        }
+        Ok(session)
+    }
+
+    /// Load the session saved at `path`, or `None` if there is none yet.
+    pub fn load(path: &str) -> Result<Option<Self>, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text).map(Some),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    /// Save the session to `path`, replacing any earlier one.
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.serialize())
+    }
+}
+
+/// Quote `text` as a TOML basic string.
+fn quote(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Read back a string written by [`quote`].
+fn unquote(value: &str) -> Option<String> {
+    let inner = value.strip_prefix('"')?.strip_suffix('"')?;
+    let mut out = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        out.push(match c {
+            '\\' => match chars.next()? {
+                '"' => '"',
+                '\\' => '\\',
+                't' => '\t',
+                'n' => '\n',
+                'r' => '\r',
+                _ => return None,
+            },
+            '"' => return None,
+            c => c,
+        });
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn annotations_survive_a_round_trip() {
+        let session = Session {
+            file1: "a \"quoted\" = name".to_string(),
+            file2: "b.bin".to_string(),
+            diffs: 3,
+            cursor: Some(16),
+            bookmarks: vec![4, 16],
+            annotations: BTreeMap::from([
+                (4, "version = 2".to_string()),
+                (16, "the \"CRC\"\nover\tthe header \\ body".to_string()),
+                (usize::MAX, "=".to_string()),
+            ]),
+            ..Session::default()
+        };
+        assert_eq!(Session::parse(&session.serialize()), Ok(session));
+    }
+
+    #[test]
+    fn annotations_need_an_offset_and_a_quoted_note() {
+        assert_eq!(
+            Session::parse("note.x = \"CRC\""),
+            Err("line 1: expected an offset after note.".to_string())
+        );
+        assert_eq!(
+            Session::parse("\nnote.12 = CRC"),
+            Err("line 2: expected a quoted string".to_string())
+        );
+    }
+}
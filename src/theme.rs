@@ -0,0 +1,161 @@
+use ratatui::style::{Color, Modifier, Style};
+
+/// Styles for the byte types and cell states drawn by the viewer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Theme {
+    /// Null bytes.
+    pub zero: Style,
+    /// Printable ASCII bytes.
+    pub printable: Style,
+    /// ASCII whitespace and control bytes.
+    pub whitespace: Style,
+    /// Bytes 0x80–0xFF.
+    pub high: Style,
+    pub cursor: Style,
+    /// The cursor in the pane without the focus.
+    pub unfocused_cursor: Style,
+    pub selection: Style,
+    /// Bytes with a pending edit.
+    pub edit: Style,
+    pub search_match: Style,
+    /// The selected search match.
+    pub current_match: Style,
+    /// Watched byte values, assigned in watch order.
+    pub watch: &'static [Style],
+    pub gutter: Style,
+    /// The gutter of rows holding a bookmark.
+    pub bookmark: Style,
+    /// Prompt text that fails validation.
+    pub error: Style,
+}
+
+impl Theme {
+    /// Colors for dark terminal backgrounds, the default.
+    pub const DARK: Theme = Theme {
+        zero: Style::new().fg(Color::Gray),
+        printable: Style::new().fg(Color::Cyan),
+        whitespace: Style::new().fg(Color::Green),
+        high: Style::new().fg(Color::Yellow),
+        cursor: Style::new()
+            .fg(Color::White)
+            .add_modifier(Modifier::REVERSED),
+        unfocused_cursor: Style::new().fg(Color::White).bg(Color::DarkGray),
+        selection: Style::new().fg(Color::White).bg(Color::Blue),
+        edit: Style::new().fg(Color::Red).add_modifier(Modifier::BOLD),
+        search_match: Style::new().fg(Color::Black).bg(Color::Yellow),
+        current_match: Style::new()
+            .fg(Color::Black)
+            .bg(Color::LightYellow)
+            .add_modifier(Modifier::BOLD),
+        watch: &[
+            Style::new().fg(Color::Black).bg(Color::Magenta),
+            Style::new().fg(Color::Black).bg(Color::LightRed),
+            Style::new().fg(Color::Black).bg(Color::LightBlue),
+            Style::new().fg(Color::Black).bg(Color::LightGreen),
+        ],
+        gutter: Style::new().fg(Color::DarkGray),
+        bookmark: Style::new()
+            .fg(Color::LightMagenta)
+            .add_modifier(Modifier::BOLD),
+        error: Style::new().fg(Color::Red),
+    };
+
+    /// Colors that stay readable on light terminal backgrounds.
+    pub const LIGHT: Theme = Theme {
+        zero: Style::new().fg(Color::DarkGray),
+        printable: Style::new().fg(Color::Blue),
+        whitespace: Style::new().fg(Color::Green),
+        high: Style::new().fg(Color::Magenta),
+        cursor: Style::new()
+            .fg(Color::Black)
+            .add_modifier(Modifier::REVERSED),
+        unfocused_cursor: Style::new().fg(Color::Black).bg(Color::Gray),
+        selection: Style::new().fg(Color::White).bg(Color::Blue),
+        edit: Style::new().fg(Color::Red).add_modifier(Modifier::BOLD),
+        search_match: Style::new().fg(Color::Black).bg(Color::Yellow),
+        current_match: Style::new()
+            .fg(Color::Black)
+            .bg(Color::Yellow)
+            .add_modifier(Modifier::BOLD.union(Modifier::UNDERLINED)),
+        watch: &[
+            Style::new().fg(Color::White).bg(Color::Magenta),
+            Style::new().fg(Color::White).bg(Color::Red),
+            Style::new().fg(Color::White).bg(Color::Blue),
+            Style::new().fg(Color::White).bg(Color::Green),
+        ],
+        gutter: Style::new().fg(Color::Gray),
+        bookmark: Style::new().fg(Color::Magenta).add_modifier(Modifier::BOLD),
+        error: Style::new().fg(Color::Red),
+    };
+
+    /// No colors: byte types and states are told apart by text attributes.
+    /// Also used whenever colors are turned off.
+    pub const MONOCHROME: Theme = Theme {
+        zero: Style::new().add_modifier(Modifier::DIM),
+        printable: Style::new(),
+        whitespace: Style::new().add_modifier(Modifier::UNDERLINED),
+        high: Style::new().add_modifier(Modifier::BOLD),
+        cursor: Style::new().add_modifier(Modifier::REVERSED.union(Modifier::BOLD)),
+        unfocused_cursor: Style::new().add_modifier(Modifier::REVERSED.union(Modifier::ITALIC)),
+        selection: Style::new().add_modifier(Modifier::REVERSED),
+        edit: Style::new().add_modifier(Modifier::BOLD.union(Modifier::UNDERLINED)),
+        search_match: Style::new().add_modifier(Modifier::UNDERLINED),
+        current_match: Style::new().add_modifier(Modifier::UNDERLINED.union(Modifier::REVERSED)),
+        watch: &[Style::new().add_modifier(Modifier::REVERSED.union(Modifier::DIM))],
+        gutter: Style::new().add_modifier(Modifier::DIM),
+        bookmark: Style::new().add_modifier(Modifier::REVERSED),
+        error: Style::new().add_modifier(Modifier::BOLD),
+    };
+
+    /// Style of a byte by its type.
+    pub fn byte_style(&self, byte: u8) -> Style {
+        if byte == 0 {
+            self.zero
+        } else if byte.is_ascii_graphic() {
+            self.printable
+        } else if byte.is_ascii_whitespace() || byte.is_ascii() {
+            self.whitespace
+        } else {
+            self.high
+        }
+    }
+
+    /// Style of the watched value at `index` in watch order.
+    pub fn watch_style(&self, index: usize) -> Style {
+        self.watch[index % self.watch.len()]
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::DARK
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watch_styles_cycle_through_each_preset() {
+        for theme in [Theme::DARK, Theme::LIGHT, Theme::MONOCHROME] {
+            let count = theme.watch.len();
+            assert!(count > 0);
+            for index in 0..count {
+                assert_eq!(theme.watch_style(index), theme.watch[index]);
+                assert_eq!(theme.watch_style(index + count), theme.watch[index]);
+            }
+        }
+        assert_eq!(
+            Theme::DARK.watch_style(1),
+            Style::new().fg(Color::Black).bg(Color::LightRed)
+        );
+    }
+
+    #[test]
+    fn monochrome_watch_style_uses_only_attributes() {
+        let style = Theme::MONOCHROME.watch_style(5);
+        assert_eq!((style.fg, style.bg), (None, None));
+        assert!(style.add_modifier.contains(Modifier::REVERSED));
+    }
+}
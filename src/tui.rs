@@ -1,14 +1,86 @@
 use crate::app::App;
 use crate::event::EventHandler;
+use crate::theme::Theme;
 use crate::ui;
-use crossterm::cursor::MoveTo;
-use crossterm::event::{DisableMouseCapture, EnableMouseCapture};
+use crossterm::cursor::{MoveTo, Show};
+use crossterm::event::{
+    DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+};
+use crossterm::style::{Attribute, ResetColor, SetAttribute};
 use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
 use ratatui::backend::Backend;
 use ratatui::Terminal;
-use std::io;
+use std::io::{self, Write};
 use std::panic;
 
+/// Whether a terminal supports color, judged from the `TERM` and `COLORTERM`
+/// variables and whether `NO_COLOR` is set.
+pub fn color_support(term: Option<&str>, colorterm: Option<&str>, no_color: bool) -> bool {
+    if no_color {
+        return false;
+    }
+    if colorterm.is_some_and(|value| !value.is_empty()) {
+        return true;
+    }
+    match term {
+        None | Some("") | Some("dumb") => false,
+        Some(term) => {
+            term.contains("color")
+                || [
+                    "xterm",
+                    "screen",
+                    "tmux",
+                    "rxvt",
+                    "linux",
+                    "vt100",
+                    "alacritty",
+                    "kitty",
+                ]
+                .iter()
+                .any(|known| term.starts_with(known))
+        }
+    }
+}
+
+/// Whether the current terminal supports color, according to the environment.
+pub fn detect_color_support() -> bool {
+    let term = std::env::var("TERM").ok();
+    let colorterm = std::env::var("COLORTERM").ok();
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    color_support(term.as_deref(), colorterm.as_deref(), no_color)
+}
+
+/// Write the escape sequences that undo everything the interface may have left
+/// behind: colors and attributes, bracketed paste, mouse capture, the hidden
+/// cursor and the alternate screen.
+fn write_reset_sequence<W: Write>(out: &mut W) -> io::Result<()> {
+    crossterm::queue!(
+        out,
+        ResetColor,
+        SetAttribute(Attribute::Reset),
+        DisableBracketedPaste,
+        DisableMouseCapture,
+        LeaveAlternateScreen,
+        Clear(ClearType::All),
+        MoveTo(0, 0),
+        Show
+    )?;
+    out.flush()
+}
+
+/// Chain a panic hook that resets the terminal properties, writing the reset
+/// sequence to the writer `out` returns, before the previous hook prints the
+/// panic message.
+fn install_reset_hook<W: Write>(out: impl Fn() -> W + Send + Sync + 'static) {
+    let panic_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |panic| {
+        // Panicking again here would abort before the message is printed
+        let _ = terminal::disable_raw_mode();
+        let _ = write_reset_sequence(&mut out());
+        panic_hook(panic);
+    }));
+}
+
 /// Representation of a terminal user interface.
 #[derive(Debug)]
 pub struct Tui<B: Backend> {
@@ -30,14 +102,14 @@ impl<B: Backend> Tui<B> {
     /// Initializes the terminal interface.
     pub fn init(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         terminal::enable_raw_mode()?;
-        crossterm::execute!(io::stderr(), EnterAlternateScreen, EnableMouseCapture)?;
+        crossterm::execute!(
+            io::stderr(),
+            EnterAlternateScreen,
+            EnableMouseCapture,
+            EnableBracketedPaste
+        )?;
 
-        // Define a custom panic hook to reset the terminal properties.
-        let panic_hook = panic::take_hook();
-        panic::set_hook(Box::new(move |panic| {
-            Self::reset().expect("failed to reset the terminal");
-            panic_hook(panic);
-        }));
+        install_reset_hook(io::stderr);
 
         self.terminal.hide_cursor()?;
         self.terminal.clear()?;
@@ -45,21 +117,18 @@ impl<B: Backend> Tui<B> {
     }
 
     /// [`Draw`] the terminal interface by [`rendering`] the widgets.
-    pub fn draw(&mut self, app: &mut App) -> Result<(), Box<dyn std::error::Error>> {
-        self.terminal.draw(|frame| ui::render(app, frame))?;
+    pub fn draw(&mut self, app: &mut App, theme: &Theme) -> Result<(), Box<dyn std::error::Error>> {
+        self.terminal.draw(|frame| ui::render(app, theme, frame))?;
         Ok(())
     }
 
-    /// Resets the terminal interface.
+    /// Resets the terminal interface. Every step is attempted even if an
+    /// earlier one fails, since a panic may have interrupted a draw halfway
+    /// through an escape sequence.
     fn reset() -> Result<(), Box<dyn std::error::Error>> {
-        terminal::disable_raw_mode()?;
-        crossterm::execute!(
-            io::stderr(),
-            LeaveAlternateScreen,
-            DisableMouseCapture,
-            Clear(ClearType::All),
-            MoveTo(0, 0)
-        )?;
+        let raw_mode = terminal::disable_raw_mode();
+        write_reset_sequence(&mut io::stderr())?;
+        raw_mode?;
         Ok(())
     }
 
@@ -79,3 +148,95 @@ impl<B: Backend> Tui<B> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The sequence written into a buffer, as text.
+    fn reset_sequence(mut out: Vec<u8>) -> String {
+        write_reset_sequence(&mut out).expect("writing to a Vec can't fail");
+        String::from_utf8(out).expect("escape sequences are ASCII")
+    }
+
+    #[test]
+    fn color_support_follows_the_environment() {
+        // Truecolor, 256-color and basic terminals
+        assert!(color_support(Some("xterm"), Some("truecolor"), false));
+        assert!(color_support(None, Some("24bit"), false));
+        assert!(color_support(Some("xterm-256color"), None, false));
+        assert!(color_support(Some("screen-256color"), None, false));
+        assert!(color_support(Some("vt100"), None, false));
+        assert!(color_support(Some("linux"), None, false));
+        // Terminals without color
+        assert!(!color_support(Some("dumb"), None, false));
+        assert!(!color_support(Some(""), Some(""), false));
+        assert!(!color_support(None, None, false));
+        assert!(!color_support(Some("unknown"), None, false));
+        // NO_COLOR wins over everything
+        assert!(!color_support(
+            Some("xterm-256color"),
+            Some("truecolor"),
+            true
+        ));
+    }
+
+    #[test]
+    fn reset_sequence_restores_the_terminal() {
+        let out = reset_sequence(Vec::new());
+        // Colors and attributes, bracketed paste, mouse capture, the
+        // alternate screen and the cursor, in that order
+        let steps = [
+            "\x1b[0m",
+            "\x1b[?2004l",
+            "\x1b[?1000l",
+            "\x1b[?1049l",
+            "\x1b[?25h",
+        ];
+        let positions: Vec<usize> = steps
+            .iter()
+            .map(|step| {
+                out.find(step)
+                    .unwrap_or_else(|| panic!("{:?} missing from {:?}", step, out))
+            })
+            .collect();
+        assert!(positions.is_sorted(), "out of order: {:?}", out);
+    }
+
+    /// A writer appending to a buffer shared with the test.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn panic_hook_resets_after_an_interrupted_draw() {
+        let out = SharedBuffer::default();
+        // A draw that panics after writing half of a color sequence
+        out.clone().write_all(b"\x1b[38;5;").unwrap();
+        let hook_out = out.clone();
+        install_reset_hook(move || hook_out.clone());
+        let draw = panic::catch_unwind(|| panic!("draw failed"));
+        // Back to the default hook, dropping ours
+        let _ = panic::take_hook();
+        assert!(draw.is_err());
+
+        let out = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+        assert!(out.starts_with("\x1b[38;5;\x1b[0m"), "{:?}", out);
+        assert!(out.contains("\x1b[?1049l"));
+        assert!(
+            out.ends_with("\x1b[?25h"),
+            "cursor not shown last: {:?}",
+            out
+        );
+    }
+}
@@ -1,127 +1,1376 @@
 use ratatui::{
-    layout::{Constraint, Direction, Layout},
-    style::{Color, Modifier, Style},
+    layout::{Constraint, Direction, Layout, Margin, Rect},
+    style::{Modifier, Style},
     text::{Line, Span, Text},
-    widgets::{Block, Borders, Paragraph},
+    widgets::{
+        Block, Borders, Clear, List, ListItem, ListState, Paragraph, Scrollbar,
+        ScrollbarOrientation, ScrollbarState,
+    },
     Frame,
 };
 
-use crate::app::App;
+use std::{cmp::Reverse, time::Duration};
 
-/// Render the user interface.
-pub fn render(app: &mut App, frame: &mut Frame) {
-    let size = frame.size();
+use unicode_width::UnicodeWidthChar;
 
-    let hex_section_width = (size.width as f32 * 0.7).floor() as usize;
-    let padding_and_borders = 4;
-    let adjusted_width = hex_section_width - padding_and_borders;
-    app.bytes_per_line = adjusted_width / 3;
+use crate::{
+    analysis::inspect_values,
+    app::{
+        AlignmentList, App, DecodeMode, FileView, Pane, PromptKind, SplitFocus, ViewMode,
+        FILE_VIEW_BYTES_PER_ROW,
+    },
+    export::{fold_marker, format_aligned_change, format_offset, format_relative, FoldedLine},
+    file::{DiffSummary, TextFilter},
+    input::InputPrompt,
+    search::{hex_query_state, match_preview, HexQueryState, SearchKind, SearchScope, PREVIEW_LEN},
+    theme::Theme,
+};
 
-    let hex_width = (app.bytes_per_line * 3 + 2) as u16;
-    let ascii_width = (app.bytes_per_line + 2) as u16;
+/// Columns taken by the offset gutter, including its trailing space.
+const GUTTER_WIDTH: usize = 9;
+
+/// Smallest terminal that fits one byte per row: the hex pane, 70% of the
+/// width, needs its borders, padding, the gutter and one hex cell.
+const MIN_WIDTH: u16 = 23;
+
+/// Smallest terminal that fits one row between the pane borders and the info
+/// bar.
+const MIN_HEIGHT: u16 = 6;
+
+/// The offset gutter for a hex row, showing the file offset of its first
+/// byte. Rows holding a bookmark are marked.
+fn gutter_span(app: &App, theme: &Theme, row: &[(usize, u8, u8)]) -> Span<'static> {
+    let bookmarked = row
+        .iter()
+        .any(|&(offset, _, _)| app.bookmarks.binary_search(&offset).is_ok());
+    let text = format!(
+        "{:>width$}{}",
+        gutter_offset(app, row[0].0),
+        if bookmarked { '>' } else { ' ' },
+        width = GUTTER_WIDTH - 1
+    );
+    Span::styled(
+        text,
+        if bookmarked {
+            theme.bookmark
+        } else {
+            theme.gutter
+        },
+    )
+}
+
+/// Row offset for the gutter: relative to the anchor if one is set, falling
+/// back to dropping the `0x` and then to the absolute offset when the
+/// distance is too long for the gutter.
+fn gutter_offset(app: &App, offset: usize) -> String {
+    let Some(anchor) = app.anchor else {
+        return format_offset(offset, app.offset_base);
+    };
+    let relative = format_relative(offset, anchor, app.offset_base);
+    if relative.len() < GUTTER_WIDTH {
+        return relative;
+    }
+    let short = relative.replacen("0x", "", 1);
+    if short.len() < GUTTER_WIDTH {
+        short
+    } else {
+        format_offset(offset, app.offset_base)
+    }
+}
+
+/// Bytes shown per row for a terminal `terminal_width` columns wide: the
+/// `--width` setting if given, even when the rows then get cut off, otherwise
+/// as many as fit in the hex pane, or in both hex panes side by side. Always
+/// at least 1.
+pub fn bytes_per_line(terminal_width: u16, width: Option<usize>, view: ViewMode) -> usize {
+    if let Some(width) = width {
+        return width.max(1);
+    }
+    let hex_section_width = (terminal_width as f32 * 0.7).floor() as usize;
+    let padding_and_borders = 4;
+    let (panes, cell_width) = match view {
+        ViewMode::Single => (1, 3),
+        ViewMode::SideBySide => (2, 6),
+    };
+    let adjusted_width =
+        hex_section_width.saturating_sub(panes * padding_and_borders + GUTTER_WIDTH);
+    (adjusted_width / cell_width).max(1)
+}
 
-    let lines = (size.height - 3) as usize;
+/// Render the user interface in the colors of `theme`.
+pub fn render(app: &mut App, theme: &Theme, frame: &mut Frame) {
+    let size = frame.size();
+    app.bytes_per_line = bytes_per_line(size.width, app.width, app.view);
+    if size.width < MIN_WIDTH || size.height < MIN_HEIGHT {
+        app.scrollbar = None;
+        app.hex_cells = None;
+        app.hex2_cells = None;
+        app.ascii_cells = None;
+        frame.render_widget(Paragraph::new("Terminal too small"), size);
+        return;
+    }
 
+    let inspector = inspector_height(app);
     let hex_chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
-            Constraint::Min(size.height.saturating_sub(3)),
+            Constraint::Min(size.height.saturating_sub(3 + inspector)),
+            Constraint::Length(inspector),
             Constraint::Length(3),
         ])
         .split(size);
 
+    match app.split {
+        None => render_pane(app, theme, frame, hex_chunks[0], app.scroll, true),
+        Some(split) => {
+            let (top_height, _) = split_heights(hex_chunks[0].height);
+            let areas = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(top_height), Constraint::Min(0)])
+                .split(hex_chunks[0]);
+            let (active, frozen) = match split.focus {
+                SplitFocus::Top => (areas[0], areas[1]),
+                SplitFocus::Bottom => (areas[1], areas[0]),
+            };
+            render_pane(
+                app,
+                theme,
+                frame,
+                frozen,
+                split.top / app.bytes_per_line,
+                false,
+            );
+            render_pane(app, theme, frame, active, app.scroll, true);
+        }
+    }
+
+    if app.show_inspector {
+        render_inspector(app, theme, frame, hex_chunks[1]);
+    }
+
+    // Info bar
+    if let Some(prompt) = &app.prompt {
+        let hint = match prompt.kind {
+            PromptKind::Search(SearchKind::Hex) => match hex_query_state(prompt.input.text()) {
+                HexQueryState::Complete => app.search.indicator().map(|count| (count, false)),
+                HexQueryState::Incomplete => Some(("incomplete byte".to_string(), false)),
+                HexQueryState::Invalid(c) => Some((format!("invalid char '{}'", c), true)),
+            },
+            PromptKind::Search(SearchKind::Ascii) => {
+                app.search.indicator().map(|count| (count, false))
+            }
+            _ => None,
+        };
+        let title = match prompt.kind {
+            PromptKind::Search(_) => format!(
+                "{} in {} (Tab to change)",
+                prompt.input.title,
+                app.search.scope.label()
+            ),
+            _ => prompt.input.title.to_string(),
+        };
+        let info_paragraph = Paragraph::new(prompt_line(&prompt.input, theme.error, hint))
+            .block(Block::default().borders(Borders::ALL).title(title));
+        frame.render_widget(info_paragraph, hex_chunks[2]);
+    } else if let Some((status, _)) = &app.status {
+        let info_paragraph = Paragraph::new(status.as_str())
+            .block(Block::default().borders(Borders::ALL).title("Info"));
+        frame.render_widget(info_paragraph, hex_chunks[2]);
+    } else if app.cursor_pos < app.diffs.len() {
+        let offset = app.diffs[app.cursor_pos].0;
+        let position = format_offset(offset, app.offset_base);
+        let mut info = match &app.section {
+            Some(section) => {
+                let file1 = format_offset(section.file1_start + offset, app.offset_base);
+                let file2 = format_offset(section.file2_start + offset, app.offset_base);
+                let file = if file1 == file2 {
+                    file1
+                } else {
+                    format!("{} / {}", file1, file2)
+                };
+                format!("Position: {}+{} (file {})", section.name, position, file)
+            }
+            None => format!("Position: {}", position),
+        };
+        info.push_str(&format!(
+            "  byte {} of {} diffs ({}%)",
+            app.cursor_pos + 1,
+            app.diffs.len(),
+            (app.cursor_pos + 1) * 100 / app.diffs.len()
+        ));
+        if let Some(anchor) = app.anchor {
+            info.push_str(&format!(
+                " ({} from {})",
+                format_relative(offset, anchor, app.offset_base),
+                format_offset(anchor, app.offset_base)
+            ));
+        }
+        let (_, old, new) = app.pairs[app.cursor_pos];
+        let old = app.display_byte(offset, old);
+        info.push_str(&format!("  {:02x} ({}) -> {:02x} ({})", old, old, new, new));
+        if let Some(indicator) = app.search.indicator() {
+            info.push_str(&format!("  {}", indicator));
+        }
+        if app.swapped {
+            info.push_str("  [swapped]");
+        }
+        if app.following {
+            info.push_str("  [following]");
+        }
+        if app.text_filter != TextFilter::Off {
+            info.push_str("  [text only]");
+        }
+        if app.sample_stride > 1 {
+            info.push_str(&format!("  [sampled 1/{}]", app.sample_stride));
+        }
+        if let Some(frequency) = app.frequency {
+            info.push_str(&format!(
+                "  Value {:02x}: {}/{}",
+                frequency.value,
+                frequency.nth + 1,
+                frequency.count
+            ));
+        }
+        if app.show_timer {
+            info.push_str(&format!("  [{}]", format_elapsed(app.started.elapsed())));
+        }
+        if let Some(replay) = &app.replay {
+            let current = replay.current().map_or(0, |idx| idx + 1);
+            info.push_str(&format!("  [replay {}/{}", current, replay.len()));
+            info.push_str(if replay.paused { ", paused]" } else { "]" });
+        }
+        if let Some(note) = app.current_annotation() {
+            info.push_str(&format!("  Note: {}", note));
+        }
+        let watched: Vec<String> = app
+            .watch_counts()
+            .iter()
+            .map(|(value, count)| format!("{:02x} ({})", value, count))
+            .collect();
+        if !watched.is_empty() {
+            info.push_str(&format!("  Watch: {}", watched.join(" ")));
+        }
+        let info_text = Text::from(Span::from(info));
+        let info_paragraph =
+            Paragraph::new(info_text).block(Block::default().borders(Borders::ALL).title("Info"));
+        frame.render_widget(info_paragraph, hex_chunks[2]);
+    } else {
+        let info_paragraph = Paragraph::new(no_diff_info(app))
+            .block(Block::default().borders(Borders::ALL).title("Info"));
+        frame.render_widget(info_paragraph, hex_chunks[2]);
+    }
+
+    if let Some(selected) = app.match_list {
+        render_match_list(app, selected, frame);
+    }
+    if let Some(list) = &app.alignment {
+        render_alignment(app, list, frame);
+    }
+    if let Some(view) = &app.file_view {
+        render_file_view(app, view, theme, frame);
+    }
+    if let Some(summary) = &app.summary {
+        render_summary(app, summary, frame);
+    }
+    if let Some(counts) = &app.histogram {
+        render_histogram(counts, theme, frame);
+    }
+    if app.show_help {
+        render_help(frame);
+    }
+}
+
+/// What the info pane says when there is no diff to show.
+fn no_diff_info(app: &App) -> String {
+    let (len1, len2) = (app.file1_data.len(), app.file2_data.len());
+    if !app.all_pairs.is_empty() {
+        "No differences pass the text filter".to_string()
+    } else if len1 != len2 {
+        format!("Files differ only in length: {} vs {} bytes", len1, len2)
+    } else {
+        "Files are identical".to_string()
+    }
+}
+
+/// Rows of the data inspector: a header, one row per integer width and byte
+/// order, and the borders.
+const INSPECTOR_HEIGHT: u16 = 9;
+
+/// Rows taken by the data inspector below the panes, 0 when it's hidden.
+pub fn inspector_height(app: &App) -> u16 {
+    if app.show_inspector {
+        INSPECTOR_HEIGHT
+    } else {
+        0
+    }
+}
+
+/// Draw the data inspector in `area`: the integers starting at the cursor's
+/// offset in both files, unsigned and signed, with `--` where a file ends
+/// too soon.
+fn render_inspector(app: &App, theme: &Theme, frame: &mut Frame, area: Rect) {
+    let Some(offset) = app.cursor_offset() else {
+        let block = Block::default().borders(Borders::ALL).title("Inspector");
+        frame.render_widget(
+            Paragraph::new("No byte under the cursor").block(block),
+            area,
+        );
+        return;
+    };
+    let cell = |value: Option<String>| format!("{:>21}", value.as_deref().unwrap_or("--"));
+    let mut lines = vec![Line::styled(
+        format!(
+            "{:<10}{:>21}{:>21}{:>21}{:>21}",
+            "", "file1 unsigned", "file1 signed", "file2 unsigned", "file2 signed"
+        ),
+        theme.gutter,
+    )];
+    let file1 = inspect_values(&app.file1_data, offset);
+    let file2 = inspect_values(&app.file2_data, offset);
+    for (old, new) in file1.into_iter().zip(file2) {
+        lines.push(Line::from(vec![
+            Span::styled(format!("{:<10}", old.label), theme.gutter),
+            Span::from(cell(old.unsigned.map(|value| value.to_string()))),
+            Span::from(cell(old.signed.map(|value| value.to_string()))),
+            Span::from(cell(new.unsigned.map(|value| value.to_string()))),
+            Span::from(cell(new.signed.map(|value| value.to_string()))),
+        ]));
+    }
+    let title = format!("Inspector at {}", format_offset(offset, app.offset_base));
+    let block = Block::default().borders(Borders::ALL).title(title);
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// Rows of the top and bottom pane of a split view sharing `height` rows.
+pub fn split_heights(height: u16) -> (u16, u16) {
+    (height / 2, height - height / 2)
+}
+
+/// Draw the hex and ASCII panes in `area`, scrolled down `scroll` rows. Only
+/// the `active` pane, the one holding the cursor, is hit-tested by the mouse
+/// and gets a scrollbar; the other half of a split view is marked frozen.
+fn render_pane(
+    app: &mut App,
+    theme: &Theme,
+    frame: &mut Frame,
+    area: Rect,
+    scroll: usize,
+    active: bool,
+) {
+    let hex_width = (GUTTER_WIDTH + app.bytes_per_line * 3 + 2) as u16;
+    let hex2_width = match app.view {
+        ViewMode::Single => 0,
+        ViewMode::SideBySide => (app.bytes_per_line * 3 + 2) as u16,
+    };
+    let ascii_width = (app.bytes_per_line + 2) as u16;
+
+    let lines = area.height.saturating_sub(2) as usize;
+
     let hex_ascii_chunks = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([
             Constraint::Length(hex_width),   // Hex view
+            Constraint::Length(hex2_width),  // File2 hex view, side by side
             Constraint::Length(ascii_width), // ASCII view
         ])
-        .split(hex_chunks[0]);
+        .split(area);
+
+    let hex_lines = hex_rows(app, theme, scroll, lines, true, |app, offset, old, _| {
+        app.display_byte(offset, old)
+    });
+    let mut hex_title = "Hex".to_string();
+    if app.view == ViewMode::SideBySide {
+        hex_title = app.file1_path.clone();
+        let hex2_lines = hex_rows(app, theme, scroll, lines, false, |_, _, _, new| new);
+        let hex2_paragraph = Paragraph::new(hex2_lines).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(app.file2_path.as_str()),
+        );
+        frame.render_widget(hex2_paragraph, hex_ascii_chunks[1]);
+    }
+    if !active {
+        hex_title.push_str(" (frozen, Tab to focus)");
+    }
+
+    let hex_paragraph =
+        Paragraph::new(hex_lines).block(Block::default().borders(Borders::ALL).title(hex_title));
+    let ascii_title = match app.decode {
+        DecodeMode::Ascii => "ASCII",
+        DecodeMode::Utf8 => "UTF-8",
+    };
+    let ascii_paragraph = Paragraph::new(ascii_rows(app, theme, scroll, lines))
+        .block(Block::default().borders(Borders::ALL).title(ascii_title));
 
-    // Prepare hex and ASCII lines
-    let hex_lines = app
-        .diffs
+    frame.render_widget(hex_paragraph, hex_ascii_chunks[0]);
+    frame.render_widget(ascii_paragraph, hex_ascii_chunks[2]);
+    if !active {
+        return;
+    }
+    let margin = Margin::new(1, 1);
+    let hex_inner = hex_ascii_chunks[0].inner(&margin);
+    let gutter = (GUTTER_WIDTH as u16).min(hex_inner.width);
+    app.hex_cells = Some(Rect {
+        x: hex_inner.x + gutter,
+        width: hex_inner.width - gutter,
+        ..hex_inner
+    });
+    app.hex2_cells = (app.view == ViewMode::SideBySide).then(|| hex_ascii_chunks[1].inner(&margin));
+    app.ascii_cells = Some(hex_ascii_chunks[2].inner(&margin));
+    render_scrollbar(app, hex_ascii_chunks[2], frame);
+}
+
+/// Build the visible rows of the ASCII pane, scrolled down `scroll` rows.
+fn ascii_rows(app: &App, theme: &Theme, scroll: usize, lines: usize) -> Vec<Line<'static>> {
+    app.diffs
         .chunks(app.bytes_per_line)
-        .skip(app.scroll)
+        .skip(scroll)
         .take(lines)
         .enumerate()
         .map(|(line_idx, chunk)| {
-            let spans: Vec<Span> = chunk
+            let texts = match app.decode {
+                DecodeMode::Ascii => chunk
+                    .iter()
+                    .map(|&(offset, byte)| {
+                        fit_char(ascii_glyph(app, app.display_byte(offset, byte)), 1)
+                    })
+                    .collect(),
+                DecodeMode::Utf8 => utf8_cells(app, chunk),
+            };
+            let cells: Vec<Vec<Span>> = chunk
                 .iter()
+                .zip(texts)
                 .enumerate()
-                .map(|(idx, &(_, byte))| {
-                    let pos = (line_idx + app.scroll) * app.bytes_per_line + idx;
-                    let style = if pos == app.cursor_pos {
-                        Style::default()
-                            .fg(Color::White)
-                            .add_modifier(Modifier::REVERSED)
-                    } else {
-                        byte_style(byte)
-                    };
-                    Span::styled(format!("{:02x} ", byte), style)
+                .map(|(idx, (&(offset, byte), text))| {
+                    let pos = (line_idx + scroll) * app.bytes_per_line + idx;
+                    let byte = app.display_byte(offset, byte);
+                    let style = cell_style(app, theme, Pane::Ascii, pos, offset, byte);
+                    vec![Span::styled(text, style)]
                 })
                 .collect();
-            Line::from(spans)
+            display_row(cells, app, line_idx + scroll, " ")
+        })
+        .collect()
+}
+
+/// Key bindings shown by the help overlay, grouped by category.
+const HELP: &[(&str, &[(&str, &str)])] = &[
+    (
+        "Navigation",
+        &[
+            ("h j k l, arrows", "Move the cursor"),
+            ("J / K", "Next/previous diff entry"),
+            ("PgDn / PgUp, C-f / C-b", "Page down/up"),
+            ("Home, gg / End, G", "First/last diff"),
+            ("} / {", "Next/previous region"),
+            (":", "Go to a file offset"),
+            ("=", "List the changes aligned by content"),
+            ("V", "View the whole file, z folds unchanged runs"),
+            ("D", "Largest change"),
+            ("m", "Bookmark the cursor, or remove its bookmark"),
+            ("' or `", "Next bookmark"),
+            ("F", "Next diff by byte frequency"),
+        ],
+    ),
+    (
+        "Search",
+        &[
+            ("/", "Search as ASCII"),
+            ("\\ or x", "Search as hex, ?? matches any byte"),
+            ("Tab", "While typing: search diffs, file1 or file2"),
+            ("Up / Down", "While typing: recall earlier searches"),
+            ("n / N", "Next/previous match"),
+            ("1-9", "Go to a match by number"),
+            ("L", "List the matches"),
+            ("Esc", "Clear the search"),
+        ],
+    ),
+    (
+        "Selection",
+        &[
+            ("v", "Start or stop selecting"),
+            ("y / Y", "Copy in the copy format/as ASCII"),
+            ("A", "Copy every diff byte"),
+            ("c", "Cycle the copy format"),
+            ("o", "Copy the offsets"),
+            ("X", "Copy a debugger expression"),
+        ],
+    ),
+    (
+        "View",
+        &[
+            ("W", "Cycle the row width"),
+            ("s", "Show both files side by side"),
+            ("_", "Split the view in two"),
+            (
+                "Tab",
+                "Focus the ASCII pane, then the hex pane of the other split half",
+            ),
+            ("u", "Decode the text pane as UTF-8"),
+            ("I", "Summarize the comparison"),
+            ("i", "Toggle the data inspector"),
+            ("H", "Histogram of the diff byte values"),
+            ("p", "Show only text changes"),
+            ("#", "Sample every 16th, 64th, 256th or 1024th diff"),
+            ("S", "Swap the files"),
+            ("r", "Anchor relative offsets at cursor"),
+            ("C-r", "Back to absolute offsets"),
+            ("*", "Watch a byte value"),
+            ("f / R", "Follow changes/reload"),
+        ],
+    ),
+    (
+        "Edit",
+        &[
+            ("a", "Annotate the offset"),
+            ("e / w", "Edit the byte/write the file"),
+            ("M", "Write a markdown report"),
+        ],
+    ),
+    ("Quit", &[("?", "Toggle this help"), ("q", "Quit")]),
+];
+
+/// Draw the key bindings as a centered overlay.
+fn render_help(frame: &mut Frame) {
+    let mut lines = Vec::new();
+    for (category, bindings) in HELP {
+        if !lines.is_empty() {
+            lines.push(Line::from(""));
+        }
+        lines.push(Line::styled(
+            *category,
+            Style::default().add_modifier(Modifier::BOLD),
+        ));
+        for (keys, action) in *bindings {
+            lines.push(Line::from(format!("  {:<24}{}", keys, action)));
+        }
+    }
+
+    let area = centered_rect(frame.size(), 70, 90);
+    let help = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Help (? or Esc to close)"),
+    );
+    frame.render_widget(Clear, area);
+    frame.render_widget(help, area);
+}
+
+/// Draw the summary of the whole comparison as a centered overlay.
+fn render_summary(app: &App, summary: &DiffSummary, frame: &mut Frame) {
+    let offset = |offset: Option<usize>| {
+        offset.map_or("-".to_string(), |offset| {
+            format_offset(offset, app.offset_base)
+        })
+    };
+    let rows = [
+        (
+            "File sizes",
+            format!("{} / {}", app.file1_data.len(), app.file2_data.len()),
+        ),
+        ("Bytes compared", summary.compared.to_string()),
+        ("Differing bytes", summary.differing.to_string()),
+        ("Changed", format!("{:.2}%", summary.percent_changed())),
+        ("Regions", summary.regions.to_string()),
+        (
+            "Largest region",
+            format!("{} bytes", summary.largest_region),
+        ),
+        ("First difference", offset(summary.first)),
+        ("Last difference", offset(summary.last)),
+    ];
+    let lines: Vec<Line> = rows
+        .into_iter()
+        .map(|(label, value)| Line::from(format!("{:<18}{}", label, value)))
+        .collect();
+
+    let area = centered_rect(frame.size(), 50, 50);
+    let overlay = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Summary (I or Esc to close)"),
+    );
+    frame.render_widget(Clear, area);
+    frame.render_widget(overlay, area);
+}
+
+/// Shades for the histogram cells, from the rarest byte value to the most
+/// common one.
+const HISTOGRAM_SHADES: [char; 4] = ['░', '▒', '▓', '█'];
+
+/// Draw how often each byte value occurs among the diffs as a centered 16x16
+/// grid, one cell per value with the high nibble as the row and the low
+/// nibble as the column. The shade of a cell grows with its count relative to
+/// the most common value; values that never occur are left blank.
+fn render_histogram(counts: &[usize; 256], theme: &Theme, frame: &mut Frame) {
+    let max = counts.iter().copied().max().unwrap_or(0);
+    let mut lines = vec![Line::from(format!(
+        "   {}",
+        (0..16)
+            .map(|low| format!("{:x}  ", low))
+            .collect::<String>()
+    ))];
+    for high in 0..16 {
+        let mut spans = vec![Span::styled(format!("{:x}0 ", high), theme.gutter)];
+        for low in 0..16 {
+            let value = high * 16 + low;
+            let count = counts[value];
+            let cell = if count == 0 {
+                "   ".to_string()
+            } else {
+                let shade = HISTOGRAM_SHADES[(count * HISTOGRAM_SHADES.len() - 1) / max];
+                format!("{0}{0} ", shade)
+            };
+            spans.push(Span::styled(cell, theme.byte_style(value as u8)));
+        }
+        lines.push(Line::from(spans));
+    }
+
+    let mut ranked: Vec<(usize, usize)> = counts
+        .iter()
+        .copied()
+        .enumerate()
+        .filter(|&(_, count)| count > 0)
+        .collect();
+    ranked.sort_by_key(|&(_, count)| Reverse(count));
+    let common: Vec<String> = ranked
+        .iter()
+        .take(4)
+        .map(|&(value, count)| format!("{:02x} x{}", value, count))
+        .collect();
+    lines.push(Line::from(""));
+    lines.push(Line::from(if common.is_empty() {
+        "No differences".to_string()
+    } else {
+        format!("Most common: {}", common.join(", "))
+    }));
+
+    let size = frame.size();
+    let width = 55.min(size.width);
+    let height = (lines.len() as u16 + 2).min(size.height);
+    let area = Rect {
+        x: size.x + (size.width - width) / 2,
+        y: size.y + (size.height - height) / 2,
+        width,
+        height,
+    };
+    let overlay = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Diff byte values (H or Esc to close)"),
+    );
+    frame.render_widget(Clear, area);
+    frame.render_widget(overlay, area);
+}
+
+/// Draw the search matches as a centered list, one entry per match with its
+/// file offset and a preview of the bytes it covers.
+fn render_match_list(app: &App, selected: usize, frame: &mut Frame) {
+    let items: Vec<ListItem> = app
+        .search
+        .matches
+        .iter()
+        .enumerate()
+        .map(|(idx, &start)| {
+            let (offset, bytes) = match app.search.scope {
+                SearchScope::Diffs => (
+                    app.diffs[start].0,
+                    app.diffs[start..]
+                        .iter()
+                        .take(PREVIEW_LEN)
+                        .map(|&(_, b)| b)
+                        .collect(),
+                ),
+                SearchScope::File1 | SearchScope::File2 => {
+                    let data = if app.search.scope == SearchScope::File1 {
+                        &app.file1_data
+                    } else {
+                        &app.file2_data
+                    };
+                    let end = (start + PREVIEW_LEN).min(data.len());
+                    (start, data[start..end].to_vec())
+                }
+            };
+            ListItem::new(format!(
+                "{:>5}  {}  {}",
+                idx + 1,
+                format_offset(offset, app.offset_base),
+                match_preview(&bytes, PREVIEW_LEN)
+            ))
+        })
+        .collect();
+
+    let area = centered_rect(frame.size(), 70, 60);
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("Matches ({})", app.search.matches.len())),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut state = ListState::default().with_selected(Some(selected));
+    frame.render_widget(Clear, area);
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
+/// Draw the changes of the aligned diff as a centered list, one
+/// `offset1 offset2: old new` entry per change.
+fn render_alignment(app: &App, list: &AlignmentList, frame: &mut Frame) {
+    let items: Vec<ListItem> = list
+        .changes
+        .iter()
+        .map(|change| ListItem::new(format_aligned_change(change, app.offset_base)))
+        .collect();
+
+    let area = centered_rect(frame.size(), 70, 60);
+    let list_widget = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(format!(
+            "Aligned diff ({} changes, : to go to an offset)",
+            list.changes.len()
+        )))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut state = ListState::default().with_selected(Some(list.selected));
+    frame.render_widget(Clear, area);
+    frame.render_stateful_widget(list_widget, area, &mut state);
+}
+
+/// Share of the terminal's width and height taken by the whole-file view.
+const FILE_VIEW_PERCENT: u16 = 90;
+
+/// Lines of the whole-file view that fit between its borders.
+pub fn file_view_rows(terminal_height: u16) -> usize {
+    ((terminal_height * FILE_VIEW_PERCENT / 100).saturating_sub(2) as usize).max(1)
+}
+
+/// Draw the whole first file as a hexdump in a centered overlay, with the
+/// changed bytes emphasized, the rest dimmed and folded runs of unchanged
+/// rows drawn as a single marker line.
+fn render_file_view(app: &App, view: &FileView, theme: &Theme, frame: &mut Frame) {
+    let data = &app.file1_data;
+    let cursor = app.cursor_offset();
+    let style = |offset: usize, byte: u8| {
+        let changed = app
+            .all_pairs
+            .binary_search_by_key(&offset, |&(offset, _, _)| offset)
+            .is_ok();
+        if Some(offset) == cursor {
+            theme.cursor
+        } else if changed {
+            theme
+                .byte_style(byte)
+                .add_modifier(Modifier::BOLD | Modifier::UNDERLINED)
+        } else {
+            Style::default().add_modifier(Modifier::DIM)
+        }
+    };
+
+    let area = centered_rect(frame.size(), FILE_VIEW_PERCENT, FILE_VIEW_PERCENT);
+    let lines: Vec<Line> = view
+        .lines
+        .iter()
+        .skip(view.scroll)
+        .take(file_view_rows(frame.size().height))
+        .map(|line| match line {
+            FoldedLine::Row(row) => {
+                let start = row * FILE_VIEW_BYTES_PER_ROW;
+                let end = (start + FILE_VIEW_BYTES_PER_ROW).min(data.len());
+                let mut spans = vec![Span::styled(
+                    format!("{} ", format_offset(start, app.offset_base)),
+                    theme.gutter,
+                )];
+                let bytes: Vec<(usize, u8)> = (start..end)
+                    .zip(&data[start..end])
+                    .map(|(offset, &byte)| (offset, app.display_byte(offset, byte)))
+                    .collect();
+                for &(offset, byte) in &bytes {
+                    spans.push(Span::styled(format!("{:02x}", byte), style(offset, byte)));
+                    spans.push(Span::from(" "));
+                }
+                let padding = (FILE_VIEW_BYTES_PER_ROW - bytes.len()) * 3;
+                spans.push(Span::from(" ".repeat(padding + 1)));
+                for &(offset, byte) in &bytes {
+                    spans.push(Span::styled(
+                        ascii_glyph(app, byte).to_string(),
+                        style(offset, byte),
+                    ));
+                }
+                Line::from(spans)
+            }
+            FoldedLine::Fold(rows) => Line::styled(
+                fold_marker(rows, data.len(), FILE_VIEW_BYTES_PER_ROW),
+                Style::default().add_modifier(Modifier::DIM | Modifier::ITALIC),
+            ),
+        })
+        .collect();
+
+    let action = if view.folded { "expand" } else { "fold" };
+    let file_view = Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(
+        format!("{} (z to {} unchanged runs)", app.file1_path, action),
+    ));
+    frame.render_widget(Clear, area);
+    frame.render_widget(file_view, area);
+}
+
+/// Draw the scrollbar on the right border of `area`, the ASCII pane, and
+/// remember where its track is so mouse clicks on it can be handled. When
+/// every row fits on screen there is nothing to scroll and no bar is drawn.
+fn render_scrollbar(app: &mut App, area: Rect, frame: &mut Frame) {
+    let track = Rect {
+        x: (area.x + area.width).saturating_sub(1),
+        y: area.y + 1,
+        width: 1,
+        height: area.height.saturating_sub(2),
+    };
+    let lines = track.height as usize;
+    let rows = app.diffs.len().div_ceil(app.bytes_per_line.max(1));
+    if rows <= lines {
+        app.scrollbar = None;
+        return;
+    }
+    app.scrollbar = Some(track);
+
+    let mut state = ScrollbarState::new(rows.saturating_sub(lines))
+        .position(app.scroll)
+        .viewport_content_length(lines);
+    let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight)
+        .begin_symbol(None)
+        .end_symbol(None);
+    frame.render_stateful_widget(scrollbar, track, &mut state);
+}
+
+/// A rectangle centered in `area` taking the given percentages of its size.
+fn centered_rect(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
+    let width = area.width * percent_x / 100;
+    let height = area.height * percent_y / 100;
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
+
+/// Style of the cell at diff index `pos` in `pane`: the cursor takes
+/// precedence over the selection, then edited bytes, then search matches,
+/// then watched values, then the byte's own style. The cursor is dimmed
+/// outside the focused pane.
+fn cell_style(app: &App, theme: &Theme, pane: Pane, pos: usize, offset: usize, byte: u8) -> Style {
+    let watch = app.watch_values.iter().position(|&v| v == byte);
+    let search_match = app.search.match_at(pos, offset);
+    let selected = app
+        .selection
+        .is_some_and(|selection| selection.contains(pos));
+    if pos == app.cursor_pos && pane == app.focused_pane {
+        theme.cursor
+    } else if pos == app.cursor_pos {
+        theme.unfocused_cursor
+    } else if selected {
+        theme.selection
+    } else if app.edits.contains_key(&offset) {
+        theme.edit
+    } else if let Some(current) = search_match {
+        if current {
+            theme.current_match
+        } else {
+            theme.search_match
+        }
+    } else if let Some(idx) = watch {
+        theme.watch_style(idx)
+    } else {
+        theme.byte_style(byte)
+    }
+}
+
+/// Character drawn for `byte` in the ASCII pane under the code page and
+/// printable set.
+fn ascii_glyph(app: &App, byte: u8) -> char {
+    app.codepage
+        .glyph(byte)
+        .unwrap_or_else(|| app.printable.render(byte))
+}
+
+/// Text for each cell of an ASCII pane row decoded as UTF-8. A multibyte
+/// character is drawn in the cell of its first byte on the row, spilling into
+/// the cells of its other bytes, so one cut off by the start of the row is
+/// still shown. Bytes that aren't valid UTF-8 are drawn as U+FFFD, and ASCII
+/// bytes as in the ASCII pane.
+fn utf8_cells(app: &App, chunk: &[(usize, u8)]) -> Vec<String> {
+    let decoded: Vec<(usize, char)> = chunk
+        .iter()
+        .map(|&(offset, _)| match app.utf8_char_at(offset) {
+            (lead, c) if c.is_ascii() => (lead, ascii_glyph(app, c as u8)),
+            other => other,
         })
-        .collect::<Vec<_>>();
+        .collect();
+    let mut texts = Vec::with_capacity(chunk.len());
+    let mut idx = 0;
+    while idx < decoded.len() {
+        let (lead, c) = decoded[idx];
+        // Cells of the same character's bytes
+        let span = decoded[idx..]
+            .iter()
+            .take_while(|&&(other, _)| other == lead)
+            .count();
+        let width = c.width().unwrap_or(0);
+        if width == 0 || width > span {
+            texts.extend(std::iter::repeat_n(fit_char(c, 1), span));
+        } else {
+            texts.push(c.to_string());
+            texts.extend(std::iter::repeat_n(String::new(), width - 1));
+            texts.extend(std::iter::repeat_n(" ".to_string(), span - width));
+        }
+        idx += span;
+    }
+    texts
+}
 
-    let ascii_lines = app
-        .diffs
+/// Render `c` into exactly `columns` terminal cells, the space its bytes take
+/// in the hex pane, so the ASCII pane stays aligned with it. Zero-width and
+/// combining characters, wide characters that would overflow, and control
+/// characters, which would move the terminal cursor, become `.`.
+fn fit_char(c: char, columns: usize) -> String {
+    match c.width() {
+        Some(0) | None => format!("{:<columns$}", '.'),
+        Some(width) if width > columns => ".".repeat(columns),
+        Some(width) => format!("{}{}", c, " ".repeat(columns - width)),
+    }
+}
+
+/// Format a session duration as `m:ss`, or `h:mm:ss` from an hour on.
+fn format_elapsed(elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs();
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+/// Which bytes the ASCII pane shows as characters rather than `.`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PrintableSet {
+    /// Graphic characters and whitespace; whitespace is drawn as a space.
+    #[default]
+    Whitespace,
+    /// Graphic characters only; whitespace is drawn as `.` too.
+    Graphic,
+}
+
+impl PrintableSet {
+    /// Character shown for `byte`. Tabs and newlines are never returned as
+    /// themselves, since they would break the row apart.
+    pub fn render(self, byte: u8) -> char {
+        if byte.is_ascii_graphic() {
+            byte as char
+        } else if self == PrintableSet::Whitespace && byte.is_ascii_whitespace() {
+            ' '
+        } else {
+            '.'
+        }
+    }
+}
+
+/// Lay out the cells of display row `row` in display order. With reversed
+/// bytes, a short final row is right-aligned so every byte keeps the column it
+/// would have in a full row. With separators on, a row ending a region is
+/// underlined.
+fn display_row<'a>(
+    mut cells: Vec<Vec<Span<'a>>>,
+    app: &App,
+    row: usize,
+    blank: &'a str,
+) -> Line<'a> {
+    if app.reverse_bytes {
+        cells.reverse();
+        let padding = app.bytes_per_line.saturating_sub(cells.len());
+        cells.splice(0..0, std::iter::repeat_n(vec![Span::from(blank)], padding));
+    }
+    let mut line = Line::from(cells.concat());
+    if app.separators && separated_after(&app.diffs, row, app.bytes_per_line) {
+        line.patch_style(Style::default().add_modifier(Modifier::UNDERLINED));
+    }
+    line
+}
+
+/// Whether a separator belongs below display row `row`: the file offset jumps
+/// by more than a row's worth of bytes from its last entry to the next row's
+/// first, so the two rows come from unrelated regions.
+fn separated_after(diffs: &[(usize, u8)], row: usize, bytes_per_line: usize) -> bool {
+    let next = (row + 1) * bytes_per_line;
+    match (diffs.get(next.wrapping_sub(1)), diffs.get(next)) {
+        (Some(&(last, _)), Some(&(first, _))) => first - last > bytes_per_line,
+        _ => false,
+    }
+}
+
+/// Build the visible rows of a hex pane scrolled down `scroll` rows, led by
+/// the offset gutter if `gutter` is set. The byte shown for a diff is picked
+/// by `byte_of` from its offset and its file1 and file2 bytes. Changed
+/// nibbles are emphasized with `--nibble` and always in the side-by-side
+/// view, except under the cursor.
+fn hex_rows(
+    app: &App,
+    theme: &Theme,
+    scroll: usize,
+    lines: usize,
+    gutter: bool,
+    byte_of: impl Fn(&App, usize, u8, u8) -> u8,
+) -> Vec<Line<'static>> {
+    app.pairs
         .chunks(app.bytes_per_line)
-        .skip(app.scroll)
+        .skip(scroll)
         .take(lines)
         .enumerate()
         .map(|(line_idx, chunk)| {
-            let spans: Vec<Span> = chunk
+            let cells: Vec<Vec<Span>> = chunk
                 .iter()
                 .enumerate()
-                .map(|(idx, &(_, byte))| {
-                    let pos = (line_idx + app.scroll) * app.bytes_per_line + idx;
-                    let style = if pos == app.cursor_pos {
-                        Style::default()
-                            .fg(Color::White)
-                            .add_modifier(Modifier::REVERSED)
+                .map(|(idx, &(offset, old, new))| {
+                    let pos = (line_idx + scroll) * app.bytes_per_line + idx;
+                    let byte = byte_of(app, offset, old, new);
+                    let style = cell_style(app, theme, Pane::Hex, pos, offset, byte);
+                    let marker = if app.annotations.contains_key(&offset) {
+                        '*'
                     } else {
-                        byte_style(byte)
+                        ' '
                     };
-                    let ascii_char = if byte.is_ascii_graphic() || byte.is_ascii_whitespace() {
-                        byte as char
+                    let emphasize = app.nibble || app.view == ViewMode::SideBySide;
+                    if emphasize && pos != app.cursor_pos {
+                        let mut spans = nibble_spans(byte, old ^ new, style);
+                        spans.push(Span::styled(marker.to_string(), style));
+                        spans
                     } else {
-                        '.'
-                    };
-                    Span::styled(ascii_char.to_string(), style)
+                        vec![Span::styled(format!("{:02x}{}", byte, marker), style)]
+                    }
                 })
                 .collect();
-            Line::from(spans)
+            let mut line = display_row(cells, app, line_idx + scroll, "   ");
+            if gutter {
+                line.spans.insert(0, gutter_span(app, theme, chunk));
+            }
+            line
         })
-        .collect::<Vec<_>>();
+        .collect()
+}
 
-    let hex_paragraph =
-        Paragraph::new(hex_lines).block(Block::default().borders(Borders::ALL).title("Hex"));
-    let ascii_paragraph =
-        Paragraph::new(ascii_lines).block(Block::default().borders(Borders::ALL).title("ASCII"));
+/// Split a hex cell into its two nibbles, emphasizing the ones set in
+/// `changed` (the XOR of the old and new byte) and dimming the rest.
+fn nibble_spans(byte: u8, changed: u8, style: Style) -> Vec<Span<'static>> {
+    [
+        (byte >> 4, changed & 0xf0 != 0),
+        (byte & 0xf, changed & 0x0f != 0),
+    ]
+    .into_iter()
+    .map(|(nibble, differs)| {
+        let modifier = if differs {
+            Modifier::BOLD | Modifier::UNDERLINED
+        } else {
+            Modifier::DIM
+        };
+        Span::styled(format!("{:x}", nibble), style.add_modifier(modifier))
+    })
+    .collect()
+}
 
-    frame.render_widget(hex_paragraph, hex_ascii_chunks[0]);
-    frame.render_widget(ascii_paragraph, hex_ascii_chunks[1]);
+/// Build the line for a text prompt, highlighting the cursor cell. A live
+/// `hint` is shown after the text, marking the text as an error when its flag
+/// is set; a validation error from a rejected submit takes its place.
+fn prompt_line(
+    input: &InputPrompt,
+    error_style: Style,
+    hint: Option<(String, bool)>,
+) -> Line<'static> {
+    let text: Vec<char> = input.text().chars().collect();
+    let cursor = input.cursor();
+    let before: String = text[..cursor].iter().collect();
+    let under = text.get(cursor).map_or(' ', |&c| c);
+    let after: String = text.iter().skip(cursor + 1).collect();
 
-    // Info bar
-    if app.cursor_pos < app.diffs.len() {
-        let offset = app.diffs[app.cursor_pos].0;
-        let info_text = Text::from(Span::from(format!("Position: {:08x}", offset)));
-        let info_paragraph =
-            Paragraph::new(info_text).block(Block::default().borders(Borders::ALL).title("Info"));
-        frame.render_widget(info_paragraph, hex_chunks[1]);
+    let (message, is_error) = match (&input.error, hint) {
+        (Some(error), _) => (Some(error.clone()), true),
+        (None, Some((hint, is_error))) => (Some(hint), is_error),
+        (None, None) => (None, false),
+    };
+    let text_style = if is_error {
+        error_style
+    } else {
+        Style::default()
+    };
+
+    let mut spans = vec![
+        Span::styled(before, text_style),
+        Span::styled(
+            under.to_string(),
+            text_style.add_modifier(Modifier::REVERSED),
+        ),
+        Span::styled(after, text_style),
+    ];
+    if let Some(message) = message {
+        let style = if is_error {
+            error_style
+        } else {
+            Style::default().add_modifier(Modifier::DIM)
+        };
+        spans.push(Span::styled(format!("  {}", message), style));
     }
+    Line::from(spans)
 }
 
-pub fn byte_style(byte: u8) -> Style {
-    if byte == 0 {
-        Style::default().fg(Color::Gray)
-    } else if byte.is_ascii_graphic() {
-        Style::default().fg(Color::Cyan)
-    } else if byte.is_ascii_whitespace() || byte.is_ascii() {
-        Style::default().fg(Color::Green)
-    } else {
-        Style::default().fg(Color::Yellow)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::buffer::Buffer;
+
+    #[test]
+    fn no_diff_info_reports_a_length_difference() {
+        let identical = App::new(b"abc".to_vec(), b"abc".to_vec(), vec![], vec![]);
+        assert_eq!(no_diff_info(&identical), "Files are identical");
+
+        let longer = App::new(b"abc".to_vec(), b"abcde".to_vec(), vec![], vec![]);
+        assert_eq!(
+            no_diff_info(&longer),
+            "Files differ only in length: 3 vs 5 bytes"
+        );
+
+        let filtered = App::new(
+            b"abc".to_vec(),
+            b"abd".to_vec(),
+            vec![],
+            vec![(2, b'c', b'd')],
+        );
+        assert_eq!(
+            no_diff_info(&filtered),
+            "No differences pass the text filter"
+        );
+    }
+
+    /// The screen drawn by `draw` on a `width` by `height` terminal.
+    fn draw_buffer(width: u16, height: u16, draw: impl FnOnce(&mut Frame)) -> Buffer {
+        let mut terminal =
+            ratatui::Terminal::new(ratatui::backend::TestBackend::new(width, height))
+                .expect("the test backend can't fail");
+        let frame = terminal.draw(draw).expect("the test backend can't fail");
+        frame.buffer.clone()
+    }
+
+    /// The text of every cell drawn by `draw` on a `width` by `height` screen.
+    fn draw_text(width: u16, height: u16, draw: impl FnOnce(&mut Frame)) -> String {
+        let buffer = draw_buffer(width, height, draw);
+        buffer.content.iter().map(|cell| cell.symbol()).collect()
+    }
+
+    #[test]
+    fn histogram_scales_counts_and_survives_no_diffs() {
+        let theme = Theme::default();
+        let mut counts = [0; 256];
+        counts[0x41] = 4;
+        counts[0x00] = 1;
+        let text = draw_text(60, 24, |frame| render_histogram(&counts, &theme, frame));
+        assert!(text.contains("██"));
+        assert!(text.contains("░░"));
+        assert!(text.contains("Most common: 41 x4, 00 x1"));
+
+        let text = draw_text(60, 24, |frame| render_histogram(&[0; 256], &theme, frame));
+        assert!(text.contains("No differences"));
+        assert!(!text.contains('█'));
+    }
+
+    /// The text of each span of `line`.
+    fn span_texts(line: &Line) -> Vec<String> {
+        line.spans
+            .iter()
+            .map(|span| span.content.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn reversed_rows_mirror_their_cells_and_right_align() {
+        let mut app = App::new(b"abc".to_vec(), b"xyz".to_vec(), vec![], vec![]);
+        app.bytes_per_line = 4;
+        let cells = || {
+            vec![
+                vec![Span::from("61")],
+                vec![Span::from("62")],
+                vec![Span::from("63")],
+            ]
+        };
+        assert_eq!(
+            span_texts(&display_row(cells(), &app, 0, "  ")),
+            ["61", "62", "63"]
+        );
+
+        app.reverse_bytes = true;
+        assert_eq!(
+            span_texts(&display_row(cells(), &app, 0, "  ")),
+            ["  ", "63", "62", "61"]
+        );
+        // A full row has no padding
+        app.bytes_per_line = 3;
+        assert_eq!(
+            span_texts(&display_row(cells(), &app, 0, "  ")),
+            ["63", "62", "61"]
+        );
+    }
+
+    #[test]
+    fn reversed_rows_keep_a_backwards_selection_on_its_bytes() {
+        let (file1, file2) = (b"abc", b"xyz");
+        let mut app = App::new(
+            file1.to_vec(),
+            file2.to_vec(),
+            crate::file::diff_files(file1, file2),
+            crate::file::diff_files_pairs(file1, file2),
+        );
+        app.reverse_bytes = true;
+        app.cursor_pos = 2;
+        // Selected from the last byte back to the first
+        app.selection = Some(crate::clipboard::Selection { anchor: 2, head: 0 });
+        let theme = Theme::default();
+        let buffer = draw_buffer(80, 12, |frame| render(&mut app, &theme, frame));
+        let row: Vec<&str> = (0..80).map(|x| buffer.get(x, 1).symbol()).collect();
+        let text = row.concat();
+        assert!(text.contains("63 62 61"), "{:?}", text);
+        assert!(text.contains("cba"), "{:?}", text);
+
+        // The cursor is on "c", drawn first; the other two keep the selection
+        let ascii = (0..78).find(|&x| row[x..x + 3].concat() == "cba").unwrap() as u16;
+        let selected = buffer.get(ascii + 1, 1).style();
+        assert_eq!(selected.bg, theme.selection.bg);
+        assert_eq!(buffer.get(ascii + 2, 1).style(), selected);
+        assert_ne!(buffer.get(ascii, 1).style(), selected);
+    }
+
+    #[test]
+    fn fit_char_fills_exactly_the_columns_given() {
+        assert_eq!(fit_char('A', 1), "A");
+        assert_eq!(fit_char('A', 3), "A  ");
+        // CJK is two columns wide
+        assert_eq!(fit_char('中', 2), "中");
+        assert_eq!(fit_char('中', 3), "中 ");
+        assert_eq!(fit_char('中', 1), ".");
+        // Combining marks and controls take no columns of their own
+        assert_eq!(fit_char('\u{301}', 2), ". ");
+        assert_eq!(fit_char('\n', 1), ".");
+    }
+
+    /// The UTF-8 cells of a row covering every byte of `file1`.
+    fn row_cells(file1: &[u8]) -> Vec<String> {
+        let app = App::new(file1.to_vec(), file1.to_vec(), vec![], vec![]);
+        let row: Vec<(usize, u8)> = file1.iter().copied().enumerate().collect();
+        utf8_cells(&app, &row)
+    }
+
+    #[test]
+    fn utf8_cells_spill_characters_over_their_bytes() {
+        assert_eq!(row_cells("a中é".as_bytes()), ["a", "中", "", " ", "é", " "]);
+        // A combining mark is drawn as a placeholder in each of its cells
+        assert_eq!(row_cells("e\u{301}".as_bytes()), ["e", ".", "."]);
+    }
+
+    #[test]
+    fn utf8_cells_of_a_cut_off_sequence_are_replacement_characters() {
+        // Cut off by the end of the file
+        assert_eq!(row_cells(b"ab\xe4\xb8"), ["a", "b", "\u{fffd}", "\u{fffd}"]);
+        // Cut off by the end of the row, the character is still drawn
+        let app = App::new("a中".as_bytes().to_vec(), vec![], vec![], vec![]);
+        assert_eq!(
+            utf8_cells(&app, &[(0, b'a'), (1, 0xe4), (2, 0xb8)]),
+            ["a", "中", ""]
+        );
+    }
+
+    #[test]
+    fn whitespace_never_renders_as_itself() {
+        for byte in [b'\t', b'\n', b'\r', 0x0c] {
+            assert_eq!(PrintableSet::Graphic.render(byte), '.');
+            assert_eq!(PrintableSet::Whitespace.render(byte), ' ');
+        }
+        assert_eq!(PrintableSet::Graphic.render(b' '), '.');
+        assert_eq!(PrintableSet::Graphic.render(b'~'), '~');
+        assert_eq!(PrintableSet::Whitespace.render(0x00), '.');
+    }
+
+    #[test]
+    fn graphic_set_keeps_tabs_and_newlines_out_of_the_row() {
+        let mut app = App::new(b"a\tb\nc".to_vec(), vec![], vec![], vec![]);
+        app.printable = PrintableSet::Graphic;
+        let glyphs: String = b"a\tb\nc".iter().map(|&b| ascii_glyph(&app, b)).collect();
+        assert_eq!(glyphs, "a.b.c");
+    }
+
+    #[test]
+    fn elapsed_time_gains_hours_only_when_needed() {
+        assert_eq!(format_elapsed(Duration::from_millis(999)), "0:00");
+        assert_eq!(format_elapsed(Duration::from_secs(59)), "0:59");
+        assert_eq!(format_elapsed(Duration::from_secs(60)), "1:00");
+        assert_eq!(format_elapsed(Duration::from_secs(3599)), "59:59");
+        assert_eq!(format_elapsed(Duration::from_secs(3600)), "1:00:00");
+        assert_eq!(format_elapsed(Duration::from_secs(36_061)), "10:01:01");
+    }
+
+    #[test]
+    fn separators_fall_between_unrelated_rows() {
+        let diffs = |offsets: &[usize]| -> Vec<(usize, u8)> {
+            offsets.iter().map(|&offset| (offset, 0)).collect()
+        };
+        let jump = diffs(&[0, 1, 2, 3, 40, 41]);
+        assert!(separated_after(&jump, 0, 4));
+        // The last row has nothing below it to separate from
+        assert!(!separated_after(&jump, 1, 4));
+        assert!(!separated_after(&jump, 5, 4));
+
+        // Up to a row's worth of bytes apart still counts as one region
+        assert!(!separated_after(&diffs(&[0, 1, 2, 3, 7]), 0, 4));
+        assert!(separated_after(&diffs(&[0, 1, 2, 3, 8]), 0, 4));
+        assert!(!separated_after(&diffs(&[0, 1, 2, 3, 4, 5]), 0, 4));
+        assert!(!separated_after(&[], 0, 4));
+    }
+
+    #[test]
+    fn narrow_terminals_still_get_a_byte_per_row() {
+        for width in 1..=10 {
+            assert_eq!(bytes_per_line(width, None, ViewMode::Single), 1);
+            assert_eq!(bytes_per_line(width, None, ViewMode::SideBySide), 1);
+        }
+        assert_eq!(bytes_per_line(MIN_WIDTH, None, ViewMode::Single), 1);
+        assert_eq!(bytes_per_line(100, None, ViewMode::Single), 19);
+        // An explicit width wins, but never 0
+        assert_eq!(bytes_per_line(5, Some(16), ViewMode::Single), 16);
+        assert_eq!(bytes_per_line(100, Some(0), ViewMode::Single), 1);
+    }
+
+    #[test]
+    fn terminals_below_the_minimum_size_are_told_so() {
+        let mut app = App::new(
+            b"ab".to_vec(),
+            b"aX".to_vec(),
+            vec![(1, b'b')],
+            vec![(1, b'b', b'X')],
+        );
+        let theme = Theme::default();
+        for width in 1..MIN_WIDTH {
+            let text = draw_text(width, 10, |frame| render(&mut app, &theme, frame));
+            assert!(text.starts_with(&"Terminal too small"[..width.min(18) as usize]));
+            assert!(app.hex_cells.is_none());
+            assert_eq!(app.bytes_per_line, 1);
+        }
+        let text = draw_text(MIN_WIDTH, MIN_HEIGHT, |frame| {
+            render(&mut app, &theme, frame)
+        });
+        assert!(!text.contains("too small"));
+        assert!(app.hex_cells.is_some());
+    }
+
+    #[test]
+    fn gutter_shortens_relative_offsets_that_do_not_fit() {
+        let mut app = App::new(vec![], vec![], vec![], vec![]);
+        assert_eq!(gutter_offset(&app, 0x40), "00000040");
+        app.anchor = Some(0x10);
+        assert_eq!(gutter_offset(&app, 0x40), "+0x30");
+        assert_eq!(gutter_offset(&app, 0x0), "-0x10");
+        // Without the 0x, then absolute once even that is too long
+        assert_eq!(gutter_offset(&app, 0x123_4577), "+1234567");
+        assert_eq!(gutter_offset(&app, 0x1_2345_6789), "123456789");
     }
 }
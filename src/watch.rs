@@ -0,0 +1,44 @@
+use std::{
+    fs,
+    sync::mpsc,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use crate::event::Event;
+
+/// How often `--watch` checks the files for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Size and modification time of the file at `path`, or `None` while it is
+/// missing, as it briefly is when an editor saves by renaming over it.
+fn stamp(path: &str) -> Option<(u64, SystemTime)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.len(), metadata.modified().ok()?))
+}
+
+/// Watch both files from a background thread and send
+/// [`Event::FilesChanged`] after either changes. A change is only reported
+/// once the files have stayed the same for a whole poll interval and both
+/// exist, so a save made of several writes or a rename is one event.
+pub fn spawn(paths: [String; 2], sender: mpsc::Sender<Event>) {
+    thread::spawn(move || {
+        let stamps = || paths.each_ref().map(|path| stamp(path));
+        let mut last = stamps();
+        let mut changed = false;
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let now = stamps();
+            if now != last {
+                last = now;
+                changed = true;
+            } else if changed && now.iter().all(Option::is_some) {
+                changed = false;
+                // The viewer has quit once nobody is listening
+                if sender.send(Event::FilesChanged).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+}